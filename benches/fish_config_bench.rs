@@ -129,7 +129,10 @@ fn create_large_config() -> Edn<'static> {
     );
 
     let mut prompt_config = BTreeMap::new();
-    prompt_config.insert(Edn::Key("style"), Edn::Str("robbyrussell"));
+    prompt_config.insert(
+        Edn::Key("left"),
+        Edn::Vector(vec![Edn::Key("cwd"), Edn::Key("git-branch")]),
+    );
     prompt_config.insert(Edn::Key("show-git"), Edn::Bool(true));
 
     let mut config_map = BTreeMap::new();
@@ -196,7 +199,7 @@ fn benchmark_config_parsing_and_generation(c: &mut Criterion) {
                         :paths ["/usr/local/bin" "~/.local/bin"]
                         :functions {:mkcd "mkdir -p $argv[1]; and cd $argv[1]"}
                         :fish ["set -g fish_prompt_pwd_dir_length 3"]
-                        :prompt {:style "robbyrussell" :show-git true}}"#;
+                        :prompt {:left [:cwd :git-branch] :show-git true}}"#;
 
     c.bench_function("parse_and_generate", |b| {
         b.iter(|| {
@@ -0,0 +1,180 @@
+//! Fish color variables and the color specs they hold
+//!
+//! Each check returns the reason a spec is rejected, for use in a
+//! [`DiagnosticKind::InvalidValue`](crate::DiagnosticKind::InvalidValue).
+
+/// The variables fish reads syntax highlighting and pager colors from
+pub(crate) const COLOR_VARIABLES: &[&str] = &[
+    "fish_color_autosuggestion",
+    "fish_color_cancel",
+    "fish_color_command",
+    "fish_color_comment",
+    "fish_color_cwd",
+    "fish_color_cwd_root",
+    "fish_color_end",
+    "fish_color_error",
+    "fish_color_escape",
+    "fish_color_history_current",
+    "fish_color_host",
+    "fish_color_host_remote",
+    "fish_color_keyword",
+    "fish_color_match",
+    "fish_color_normal",
+    "fish_color_operator",
+    "fish_color_option",
+    "fish_color_param",
+    "fish_color_quote",
+    "fish_color_redirection",
+    "fish_color_search_match",
+    "fish_color_selection",
+    "fish_color_status",
+    "fish_color_user",
+    "fish_color_valid_path",
+    "fish_pager_color_background",
+    "fish_pager_color_completion",
+    "fish_pager_color_description",
+    "fish_pager_color_prefix",
+    "fish_pager_color_progress",
+    "fish_pager_color_secondary_background",
+    "fish_pager_color_secondary_completion",
+    "fish_pager_color_secondary_description",
+    "fish_pager_color_secondary_prefix",
+    "fish_pager_color_selected_background",
+    "fish_pager_color_selected_completion",
+    "fish_pager_color_selected_description",
+    "fish_pager_color_selected_prefix",
+];
+
+/// The color names `set_color` knows, from `set_color --print-colors`
+const NAMED_COLORS: &[&str] = &[
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "brblack",
+    "brred",
+    "brgreen",
+    "bryellow",
+    "brblue",
+    "brmagenta",
+    "brcyan",
+    "brwhite",
+    "normal",
+];
+
+/// `set_color` options that take no value, long and short
+const FLAGS: &[&str] = &[
+    "--bold",
+    "--dim",
+    "--italics",
+    "--reverse",
+    "--underline",
+    "-o",
+    "-d",
+    "-i",
+    "-r",
+    "-u",
+];
+
+const UNDERLINE_STYLES: &[&str] = &["single", "double", "curly", "dotted", "dashed"];
+
+/// One word of a color spec: a named or RGB color, or a `set_color` option
+pub(crate) fn spec_word(word: &str) -> Result<(), &'static str> {
+    if let Some(background) = word
+        .strip_prefix("--background=")
+        .or_else(|| word.strip_prefix("--underline-color="))
+    {
+        return color(background);
+    }
+    if let Some(style) = word.strip_prefix("--underline=") {
+        if UNDERLINE_STYLES.contains(&style) {
+            return Ok(());
+        }
+        return Err("underline styles are `single`, `double`, `curly`, `dotted` or `dashed`");
+    }
+    if word.starts_with('-') {
+        if FLAGS.contains(&word) {
+            return Ok(());
+        }
+        return Err(
+            "options are `--bold`, `--dim`, `--italics`, `--reverse`, `--underline` and `--background=<color>`",
+        );
+    }
+    color(word)
+}
+
+/// A color name, or an RGB color as three or six hex digits with an
+/// optional `#`
+fn color(color: &str) -> Result<(), &'static str> {
+    if NAMED_COLORS.contains(&color) {
+        return Ok(());
+    }
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(());
+    }
+    Err("colors are names such as `blue` or `brblack`, or hex RGB such as `5f87af`")
+}
+
+/// Reads the `<variable> <spec>...` lines of a fish `.theme` file, as
+/// `fish_config theme save` writes them, skipping comments and blank lines
+pub(crate) fn theme(text: &str) -> Vec<(String, Vec<String>)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let variable = words.next()?.to_string();
+            let spec = words
+                .map(|word| word.trim_matches('\'').to_string())
+                .collect();
+            Some((variable, spec))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_words() {
+        assert!(spec_word("blue").is_ok());
+        assert!(spec_word("brblack").is_ok());
+        assert!(spec_word("5f87af").is_ok());
+        assert!(spec_word("#FFF").is_ok());
+        assert!(spec_word("--bold").is_ok());
+        assert!(spec_word("-o").is_ok());
+        assert!(spec_word("--background=brblack").is_ok());
+        assert!(spec_word("--underline=curly").is_ok());
+        assert!(spec_word("bleu").is_err());
+        assert!(spec_word("12345").is_err());
+        assert!(spec_word("--blink").is_err());
+        assert!(spec_word("--background=").is_err());
+        assert!(spec_word("--underline=wavy").is_err());
+        assert!(COLOR_VARIABLES.is_sorted());
+    }
+
+    #[test]
+    fn test_theme() {
+        let text = "# name: 'Example'\n\
+                    # preferred_background: 282a36\n\
+                    \n\
+                    fish_color_normal f8f8f2\n\
+                    fish_color_comment 6272a4 --italics\n";
+        assert_eq!(
+            theme(text),
+            [
+                ("fish_color_normal".to_string(), vec!["f8f8f2".to_string()]),
+                (
+                    "fish_color_comment".to_string(),
+                    vec!["6272a4".to_string(), "--italics".to_string()]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,3750 @@
+//! Typed configuration model
+//!
+//! [`FishConfig`] is the inspectable representation of a tilde-fish EDN
+//! document. It is built from [`Edn`] with [`FishConfig::parse`] or
+//! [`TryFrom`] and rendered to fish source with [`FishConfig::to_fish`].
+
+use crate::color;
+use crate::diagnostic::{ConfigError, Diagnostic, DiagnosticKind, Segment, location, suggest};
+use crate::layers;
+use crate::names;
+use crate::preset;
+use crate::quote::{escape, unescape};
+use crate::syntax::{self, SyntaxErrorKind};
+use crate::target::Target;
+use clojure_reader::edn::Edn;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// A complete fish shell configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FishConfig {
+    /// Header text from `:preambles {:tilde/all ...}`
+    pub preamble: Option<String>,
+    /// The `:fish-greeting` setting
+    pub greeting: Option<Greeting>,
+    /// `:snippet/<name>` entries, in key order
+    pub snippets: Vec<Snippet>,
+    /// `:abbrs`; `Some(vec![])` when the section is present but empty
+    pub abbrs: Option<Vec<Abbr>>,
+    /// `:aliases`
+    pub aliases: Option<Vec<Alias>>,
+    /// `:env`, then the variables erased by `:unset`
+    pub env: Option<Vec<EnvVar>>,
+    /// `:paths`
+    pub paths: Option<Vec<PathEntry>>,
+    /// `:path-vars`, by variable name
+    pub path_vars: Option<Vec<PathVar>>,
+    /// `:functions`
+    pub functions: Option<Vec<FishFunction>>,
+    /// Whether `:autoload` lists `:functions`, which are then written to
+    /// their own files instead of `config.fish`, except event handlers
+    pub autoload_functions: bool,
+    /// `:completions`
+    pub completions: Option<Vec<Completion>>,
+    /// Whether `:autoload` lists `:completions`
+    pub autoload_completions: bool,
+    /// `:fish` raw commands
+    pub fish: Option<Vec<String>>,
+    /// `:colors`, with the `:theme` file's colors first
+    pub colors: Option<Vec<Color>>,
+    /// `:prompt`
+    pub prompt: Option<Prompt>,
+    /// `:key-bindings`
+    pub key_bindings: Option<KeyBindings>,
+    /// `:conditional` blocks, then entries with their own `:when`, grouped
+    /// by condition
+    pub conditionals: Vec<Conditional>,
+    /// `:scope` overrides, by section name; see [`FishConfig::scope`]
+    pub scopes: BTreeMap<String, Scope>,
+}
+
+/// The shells a section is written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Only interactive shells, inside `if status is-interactive`
+    Interactive,
+    /// Only login shells, inside `if status is-login`
+    Login,
+    /// Every shell, including those running scripts
+    Always,
+}
+
+impl Scope {
+    /// The scope of a section with no `:scope` override: interactive for
+    /// the greeting, abbreviations, colors, prompt and key bindings, which scripts
+    /// never use
+    pub fn default_for(section: &str) -> Scope {
+        if INTERACTIVE_SECTIONS.contains(&section) {
+            Scope::Interactive
+        } else {
+            Scope::Always
+        }
+    }
+}
+
+/// The fish greeting shown at interactive startup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Greeting {
+    /// `set fish_greeting '<message>'`
+    Message(String),
+    /// `set fish_greeting` with no value, which disables the greeting
+    Disabled,
+}
+
+/// A named block of fish code emitted verbatim under a comment header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub name: String,
+    pub content: String,
+}
+
+/// An `abbr -a` abbreviation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Abbr {
+    pub name: String,
+    pub expansion: AbbrExpansion,
+    /// `:position`; [`AbbrPosition::Command`] unless given
+    pub position: AbbrPosition,
+    /// `:regex`, a pattern matched instead of the name
+    pub regex: Option<String>,
+    /// `:set-cursor`, the marker in the expansion where the cursor is left;
+    /// `%` for `true`
+    pub set_cursor: Option<String>,
+}
+
+/// What an abbreviation expands to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbbrExpansion {
+    /// A fixed string, given as the value or `:expansion`
+    Text(String),
+    /// The output of a function, from `:function`
+    Function(String),
+}
+
+/// Where an abbreviation is expanded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AbbrPosition {
+    /// Only as a command name
+    #[default]
+    Command,
+    /// Anywhere on the command line
+    Anywhere,
+}
+
+/// An `alias` definition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    pub name: String,
+    pub command: String,
+}
+
+/// A variable set, or erased, with `set`
+///
+/// Everything but the name and value comes from the options of a
+/// map-valued entry; plain values are exported globals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVar {
+    pub name: String,
+    /// The elements of the list, one for a string; `None` for `nil`, which
+    /// erases the variable
+    pub value: Option<Vec<String>>,
+    /// `:scope`; global unless given, and any scope when erasing
+    pub scope: Option<VariableScope>,
+    /// `:export`, `true` unless given
+    pub export: bool,
+    /// `:path`, so the list is joined with colons instead of spaces when
+    /// exported
+    pub path: bool,
+}
+
+impl EnvVar {
+    /// An exported global variable holding `value`
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        EnvVar {
+            name: name.into(),
+            value: Some(vec![value.into()]),
+            scope: None,
+            export: true,
+            path: false,
+        }
+    }
+}
+
+/// Where a variable is set, from an `:env` entry's `:scope`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableScope {
+    /// `-g`, for the rest of the session
+    Global,
+    /// `-U`, shared by every fish session and kept across restarts
+    Universal,
+    /// `-l`, only while `config.fish` runs
+    Local,
+}
+
+/// A directory added to `PATH` with `fish_add_path`, or to a `:path-vars`
+/// list
+///
+/// Everything but the path comes from the options of a map-valued entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathEntry {
+    pub path: String,
+    /// `:append`, adding the directory after the existing ones instead of
+    /// before them
+    pub append: bool,
+    /// `:move`, moving the directory to the front (or back) if it is
+    /// already listed instead of leaving it where it is
+    pub move_existing: bool,
+    /// `:if-exists`, only adding the directory if it exists, which
+    /// `fish_add_path` always does
+    pub if_exists: bool,
+    /// `:global`, setting `PATH` for this session instead of the universal
+    /// `fish_user_paths`; `:paths` only
+    pub global: bool,
+}
+
+/// A colon-separated list of directories other than `PATH`, such as
+/// `MANPATH`, from `:path-vars`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathVar {
+    pub name: String,
+    pub entries: Vec<PathEntry>,
+}
+
+/// A fish function definition
+///
+/// Everything but the name and body comes from the options of a map-valued
+/// entry, and is empty for a plain string body.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FishFunction {
+    pub name: String,
+    pub body: String,
+    /// `--description`
+    pub description: Option<String>,
+    /// `--argument-names`, the variables the arguments are assigned to
+    pub argument_names: Vec<String>,
+    /// `--wraps`, the command whose completions the function inherits
+    pub wraps: Option<String>,
+    /// `--on-event`, one flag per event
+    pub on_event: Vec<String>,
+    /// `--on-variable`
+    pub on_variable: Vec<String>,
+    /// `--on-signal`, by name or number
+    pub on_signal: Vec<String>,
+    /// `--on-job-exit`, a PID or `caller`
+    pub on_job_exit: Vec<String>,
+    /// `--inherit-variable`
+    pub inherit_variable: Vec<String>,
+    /// `--no-scope-shadowing`
+    pub no_scope_shadowing: bool,
+}
+
+impl FishFunction {
+    /// Whether the function handles events, which only works once it is
+    /// defined, so it can't wait to be autoloaded
+    pub fn handles_events(&self) -> bool {
+        !(self.on_event.is_empty()
+            && self.on_variable.is_empty()
+            && self.on_signal.is_empty()
+            && self.on_job_exit.is_empty())
+    }
+}
+
+/// The `complete` lines for one command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub command: String,
+    pub options: Vec<CompletionOption>,
+}
+
+/// One `complete -c <command>` line, from an option spec map
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionOption {
+    /// `:short`, the option character without its `-`
+    pub short: Option<String>,
+    /// `:long`, the option name without its `--`
+    pub long: Option<String>,
+    /// `:description`
+    pub description: Option<String>,
+    /// `:arguments`, a fish expression whose words are the candidates; a
+    /// vector of candidates is stored with each one escaped
+    pub arguments: Option<String>,
+    /// `:condition`, a command that must succeed for the option to be offered
+    pub condition: Option<String>,
+    /// `:no-files`, so files aren't offered alongside the arguments
+    pub no_files: bool,
+    /// `:require-parameter`, so the option must be followed by an argument
+    pub require_parameter: bool,
+}
+
+/// Sections that only apply where a condition holds, written inside an
+/// `if` block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conditional {
+    pub when: Condition,
+    pub config: FishConfig,
+}
+
+/// A `:when` condition; every part that is given must hold
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Condition {
+    /// `:os`, any of these systems
+    pub os: Vec<Os>,
+    /// `:hostname`, any of these host names
+    pub hostname: Vec<String>,
+    /// `:hostname-regex`, which must match the whole host name
+    pub hostname_regex: Option<String>,
+    /// `:env`, tests on environment variables
+    pub env: Vec<EnvCondition>,
+}
+
+/// An operating system, as named in `:os`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    Linux,
+    MacOs,
+    FreeBsd,
+    OpenBsd,
+    NetBsd,
+}
+
+impl Os {
+    pub const ALL: [Os; 5] = [Os::Linux, Os::MacOs, Os::FreeBsd, Os::OpenBsd, Os::NetBsd];
+
+    /// The `:os` keyword, which is also Rust's name for the system
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Os::Linux => "linux",
+            Os::MacOs => "macos",
+            Os::FreeBsd => "freebsd",
+            Os::OpenBsd => "openbsd",
+            Os::NetBsd => "netbsd",
+        }
+    }
+
+    /// The name `uname` prints for the system
+    pub fn uname(self) -> &'static str {
+        match self {
+            Os::Linux => "Linux",
+            Os::MacOs => "Darwin",
+            Os::FreeBsd => "FreeBSD",
+            Os::OpenBsd => "OpenBSD",
+            Os::NetBsd => "NetBSD",
+        }
+    }
+
+    pub fn from_keyword(keyword: &str) -> Option<Os> {
+        Os::ALL.into_iter().find(|os| os.keyword() == keyword)
+    }
+}
+
+/// A test on one environment variable in `:when {:env {...}}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvCondition {
+    pub name: String,
+    pub test: EnvTest,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvTest {
+    /// `true`: the variable is set
+    Set,
+    /// `false` or `nil`: the variable is not set
+    Unset,
+    /// A string the value must equal
+    Equals(String),
+}
+
+/// A color variable and the `set_color` arguments it holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Color {
+    /// A variable such as `fish_color_command`
+    pub variable: String,
+    /// Colors and options, such as `["blue", "--bold"]`
+    pub spec: Vec<String>,
+}
+
+/// The `:key-bindings` map: the built-in bindings to start from and the
+/// user's own, set in `fish_user_key_bindings`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyBindings {
+    /// `:mode`; fish's default, the emacs bindings, unless given
+    pub mode: Option<KeyMode>,
+    /// `:bindings`
+    pub bindings: Vec<KeyBinding>,
+}
+
+/// The built-in key bindings selected by `fish_key_bindings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMode {
+    /// `fish_default_key_bindings`
+    Emacs,
+    /// `fish_vi_key_bindings`
+    Vi,
+    /// The vi bindings with the emacs ones available in every mode
+    Hybrid,
+}
+
+/// One `bind` command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    /// `:keys`, the key sequence as fish writes it, such as `\cf`
+    pub keys: String,
+    pub action: KeyAction,
+    /// `:mode`, the bind mode the binding applies in (`-M`)
+    pub mode: Option<String>,
+    /// `:sets-mode`, the bind mode to switch to afterwards (`-m`)
+    pub sets_mode: Option<String>,
+}
+
+/// What a key binding does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyAction {
+    /// `:command`, input functions such as `forward-char`, run in order
+    Functions(Vec<String>),
+    /// `:fish`, a command line run as fish code
+    Fish(String),
+}
+
+/// The `fish_prompt` and `fish_right_prompt` functions built from the
+/// `:prompt` map's segments
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Prompt {
+    /// `:left`, written as `fish_prompt` unless empty
+    pub left: Vec<PromptSegment>,
+    /// `:right`, written as `fish_right_prompt` unless empty
+    pub right: Vec<PromptSegment>,
+    /// Written between segments unless a segment has its own, `" "` by
+    /// default
+    pub separator: String,
+}
+
+/// One part of a prompt, left out when it has nothing to show
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptSegment {
+    pub kind: SegmentKind,
+    /// `set_color` arguments, such as `["blue", "--bold"]`
+    pub color: Vec<String>,
+    pub prefix: String,
+    pub suffix: String,
+    /// Written after this segment instead of the prompt's separator
+    pub separator: Option<String>,
+}
+
+impl PromptSegment {
+    /// A segment with no color, prefix, suffix or separator of its own
+    pub fn new(kind: SegmentKind) -> Self {
+        PromptSegment {
+            kind,
+            color: Vec::new(),
+            prefix: String::new(),
+            suffix: String::new(),
+            separator: None,
+        }
+    }
+}
+
+/// What a prompt segment shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// `:cwd`, the working directory as `prompt_pwd` shortens it
+    Cwd,
+    /// `:git-branch`, from `fish_git_prompt`
+    GitBranch,
+    /// `:git-dirty`, the symbol shown when the work tree has changes
+    GitDirty(String),
+    /// `:status`, the last command's exit status unless it is 0
+    Status,
+    /// `:duration`, how long the last command took, if at least a second
+    Duration,
+    /// `:jobs`, the number of background jobs, if any
+    Jobs,
+    /// `:venv`, the name of the active Python virtual environment
+    Venv,
+    /// `:hostname`, from `prompt_hostname`
+    Hostname,
+    /// `:time`, as hours and minutes
+    Time,
+    /// `{:text ...}`, written as is
+    Text(String),
+}
+
+/// Top-level keys understood by the generator, besides `:snippet/<name>`
+pub(crate) const SECTIONS: &[&str] = &[
+    "preambles",
+    "fish-greeting",
+    "abbrs",
+    "aliases",
+    "env",
+    "unset",
+    "paths",
+    "path-vars",
+    "functions",
+    "completions",
+    "conditional",
+    "fish",
+    "colors",
+    "prompt",
+    "key-bindings",
+    "autoload",
+    "scope",
+    "include",
+    "profiles",
+];
+
+/// Top-level keys that a `:conditional` block cannot contain
+pub(crate) const TOP_LEVEL_ONLY: &[&str] =
+    &["preambles", "autoload", "scope", "include", "profiles"];
+
+/// Top-level keys that a profile cannot contain
+const NOT_IN_PROFILES: &[&str] = &["include", "profiles"];
+
+/// Sections `:scope` can move, besides `:snippet/<name>` keys
+const SCOPED_SECTIONS: &[&str] = &[
+    "fish-greeting",
+    "abbrs",
+    "aliases",
+    "env",
+    "paths",
+    "path-vars",
+    "functions",
+    "completions",
+    "fish",
+    "colors",
+    "prompt",
+    "key-bindings",
+];
+
+/// Sections only written for interactive shells unless `:scope` says
+/// otherwise
+const INTERACTIVE_SECTIONS: &[&str] =
+    &["fish-greeting", "abbrs", "colors", "prompt", "key-bindings"];
+
+const CONDITION_KEYS: &[&str] = &["os", "hostname", "hostname-regex", "env"];
+
+/// Sections that `:autoload` can move out of `config.fish`
+const AUTOLOAD_SECTIONS: &[&str] = &["functions", "completions"];
+
+const ENV_KEYS: &[&str] = &["value", "scope", "export", "path"];
+
+const PATH_KEYS: &[&str] = &["path", "append", "move", "if-exists", "global"];
+
+const PREAMBLE_KEYS: &[&str] = &["tilde/all"];
+
+const ABBR_KEYS: &[&str] = &["expansion", "function", "position", "regex", "set-cursor"];
+
+const FUNCTION_KEYS: &[&str] = &[
+    "body",
+    "description",
+    "argument-names",
+    "wraps",
+    "on-event",
+    "on-variable",
+    "on-signal",
+    "on-job-exit",
+    "inherit-variable",
+    "no-scope-shadowing",
+];
+
+const COMPLETION_KEYS: &[&str] = &[
+    "short",
+    "long",
+    "description",
+    "arguments",
+    "condition",
+    "no-files",
+    "require-parameter",
+];
+
+const PROMPT_KEYS: &[&str] = &["left", "right", "separator", "show-git", "style"];
+
+const SEGMENT_KEYS: &[&str] = &[
+    "segment",
+    "text",
+    "color",
+    "prefix",
+    "suffix",
+    "separator",
+    "symbol",
+];
+
+const PROMPT_SEGMENTS: &[&str] = &[
+    "cwd",
+    "git-branch",
+    "git-dirty",
+    "status",
+    "duration",
+    "jobs",
+    "venv",
+    "hostname",
+    "time",
+];
+
+const KEY_BINDINGS_KEYS: &[&str] = &["mode", "bindings"];
+
+const BINDING_KEYS: &[&str] = &["keys", "command", "fish", "mode", "sets-mode"];
+
+/// Settings that control how a configuration is read
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Report unrecognised keys at the top level and inside `:prompt` and
+    /// `:preambles` instead of ignoring them
+    pub strict: bool,
+    /// Decide `:when` conditions for this machine while reading, keeping
+    /// the sections and entries that apply and dropping the rest, instead
+    /// of writing them as `if` blocks
+    pub target: Option<Target>,
+    /// The entry of `:profiles` to lay over the rest of the configuration
+    pub profile: Option<String>,
+    /// Whether the files named by `:include` have already been merged into
+    /// the configuration, as the command line does; otherwise `:include` is
+    /// reported, since reading a configuration doesn't read other files
+    pub includes_read: bool,
+    /// The `:colors :theme` files the caller has read, by the name the
+    /// configuration gives, each with its text or the error reading it; a
+    /// theme that isn't here is reported, like `:include`
+    pub themes: BTreeMap<String, Result<String, String>>,
+}
+
+impl FishConfig {
+    /// Reads a configuration, skipping every value that doesn't fit its
+    /// section and reporting it as a [`Diagnostic`]
+    ///
+    /// A top-level value that isn't a map, or a vector of maps, yields an
+    /// empty configuration.
+    pub fn parse(edn: &Edn<'_>) -> (Self, Vec<Diagnostic>) {
+        Self::parse_with(edn, &Options::default())
+    }
+
+    /// Like [`FishConfig::parse`], with explicit [`Options`]
+    ///
+    /// A vector of maps is read as layers, merged as [`layers::merge`]
+    /// describes, and diagnostics point into the layer that gave each value.
+    /// The profile named by [`Options::profile`] is merged last.
+    pub fn parse_with(edn: &Edn<'_>, options: &Options) -> (Self, Vec<Diagnostic>) {
+        let (prefixes, layers): (Vec<_>, Vec<_>) = layers::split(edn).into_iter().unzip();
+        let merged = layers::merge(layers);
+        let (config, diagnostics) = Self::read_profiles(&merged.config, options);
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|mut diagnostic| {
+                if let Some((layer, path)) = merged.origin(&diagnostic.path) {
+                    diagnostic.path = [prefixes[layer].clone(), path].concat();
+                }
+                diagnostic
+            })
+            .collect();
+        (config, diagnostics)
+    }
+
+    /// Reads a configuration with the selected profile laid over it, and
+    /// checks every other profile laid over it the same way
+    fn read_profiles(edn: &Edn<'_>, options: &Options) -> (Self, Vec<Diagnostic>) {
+        let mut reader = Reader {
+            options,
+            diagnostics: Vec::new(),
+        };
+        let mut selected = None;
+        let mut checked = Vec::new();
+        for (name, profile) in reader.profiles(edn) {
+            let merged = layers::merge(vec![edn.clone(), Edn::Map(profile)]);
+            let mut profile_reader = Reader {
+                options,
+                diagnostics: Vec::new(),
+            };
+            let config = profile_reader.config(&merged.config);
+            let is_selected = options.profile.as_deref() == key_name(&name);
+            for mut diagnostic in profile_reader.diagnostics {
+                match merged.origin(&diagnostic.path) {
+                    Some((1, path)) => {
+                        let profile_path =
+                            [Segment::Key("profiles".to_string()), key_segment(&name)];
+                        diagnostic.path = [&profile_path[..], &path].concat();
+                    }
+                    // The base was read once already, unless this is the
+                    // profile that replaces it
+                    _ if !is_selected => continue,
+                    _ => {}
+                }
+                if is_selected {
+                    reader.diagnostics.push(diagnostic);
+                } else {
+                    checked.push(diagnostic);
+                }
+            }
+            if is_selected {
+                selected = Some(config);
+            }
+        }
+        let config = selected.unwrap_or_else(|| reader.config(edn));
+        reader.diagnostics.extend(checked);
+        (config, reader.diagnostics)
+    }
+}
+
+impl FishConfig {
+    /// Where `section` is written: its `:scope` override, or
+    /// [`Scope::default_for`] it
+    pub fn scope(&self, section: &str) -> Scope {
+        scope(&self.scopes, section)
+    }
+
+    /// Adds the sections of `other` after this configuration's own, with
+    /// its greeting, prompt and key bindings replacing these
+    fn absorb(&mut self, other: FishConfig) {
+        fn append<T>(section: &mut Option<Vec<T>>, other: Option<Vec<T>>) {
+            if let Some(other) = other {
+                section.get_or_insert_default().extend(other);
+            }
+        }
+        if other.greeting.is_some() {
+            self.greeting = other.greeting;
+        }
+        self.snippets.extend(other.snippets);
+        append(&mut self.abbrs, other.abbrs);
+        append(&mut self.aliases, other.aliases);
+        append(&mut self.env, other.env);
+        append(&mut self.paths, other.paths);
+        append(&mut self.path_vars, other.path_vars);
+        append(&mut self.functions, other.functions);
+        append(&mut self.completions, other.completions);
+        append(&mut self.fish, other.fish);
+        append(&mut self.colors, other.colors);
+        if other.prompt.is_some() {
+            self.prompt = other.prompt;
+        }
+        if other.key_bindings.is_some() {
+            self.key_bindings = other.key_bindings;
+        }
+        self.conditionals.extend(other.conditionals);
+    }
+}
+
+impl TryFrom<&Edn<'_>> for FishConfig {
+    type Error = ConfigError;
+
+    /// Reads a configuration, failing if any value had to be skipped
+    fn try_from(edn: &Edn<'_>) -> Result<Self, Self::Error> {
+        let (config, diagnostics) = Self::parse(edn);
+        if diagnostics.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError { diagnostics })
+        }
+    }
+}
+
+/// Returns the name of a map key given as either a keyword or a string
+pub(crate) fn key_name<'e>(key: &Edn<'e>) -> Option<&'e str> {
+    match key {
+        Edn::Key(name) | Edn::Str(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// Returns a human-readable name for the type of an EDN value
+pub(crate) fn type_name(edn: &Edn<'_>) -> &'static str {
+    match edn {
+        Edn::Vector(_) => "vector",
+        Edn::Set(_) => "set",
+        Edn::Map(_) => "map",
+        Edn::List(_) => "list",
+        Edn::Key(_) => "keyword",
+        Edn::Symbol(_) => "symbol",
+        Edn::Str(_) => "string",
+        Edn::Int(_) => "integer",
+        Edn::Tagged(_, _) => "tagged value",
+        Edn::Rational(_) => "rational",
+        Edn::Char(_) => "character",
+        Edn::Bool(_) => "boolean",
+        Edn::Nil => "nil",
+        _ => "value",
+    }
+}
+
+/// Finds the group of entries for `when`, adding one if it is new
+fn guard(conditionals: &mut Vec<Conditional>, when: Condition) -> &mut FishConfig {
+    let index = match conditionals
+        .iter()
+        .position(|conditional| conditional.when == when)
+    {
+        Some(index) => index,
+        None => {
+            conditionals.push(Conditional {
+                when,
+                config: FishConfig::default(),
+            });
+            conditionals.len() - 1
+        }
+    };
+    &mut conditionals[index].config
+}
+
+/// Looks up the scope of `section` among `:scope` overrides
+pub(crate) fn scope(scopes: &BTreeMap<String, Scope>, section: &str) -> Scope {
+    scopes
+        .get(section)
+        .copied()
+        .unwrap_or_else(|| Scope::default_for(section))
+}
+
+/// Anchors a `:hostname-regex` so it has to match the whole name
+pub(crate) fn whole_match(regex: &str) -> String {
+    format!("^(?:{regex})$")
+}
+
+/// Whether two directories are the same apart from trailing slashes, which
+/// `fish_add_path` ignores
+pub(crate) fn same_directory(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+/// Describes a map key for use in a diagnostic path
+pub(crate) fn key_segment(key: &Edn<'_>) -> Segment {
+    match key_name(key) {
+        Some(name) => Segment::Key(name.to_string()),
+        None => Segment::Key(key.to_string()),
+    }
+}
+
+fn path(segments: &[&str]) -> Vec<Segment> {
+    segments
+        .iter()
+        .map(|segment| Segment::Key(segment.to_string()))
+        .collect()
+}
+
+/// Joins numbered `:fish` entries into the script they are emitted as
+fn script(entries: &[(usize, String)]) -> String {
+    let code: Vec<&str> = entries
+        .iter()
+        .map(|(_, command)| command.as_str())
+        .collect();
+    code.join("\n")
+}
+
+/// Finds the entry holding `line` of the joined script, returning its
+/// position and the line it starts on; lines past the end belong to the
+/// last entry
+fn locate(entries: &[(usize, String)], line: usize) -> (usize, usize) {
+    let mut first_line = 1;
+    for (at, (_, command)) in entries.iter().enumerate() {
+        let lines = command.matches('\n').count() + 1;
+        if line < first_line + lines || at == entries.len() - 1 {
+            return (at, first_line);
+        }
+        first_line += lines;
+    }
+    (0, 1)
+}
+
+/// Converts EDN into the typed model, recording every rejected value
+struct Reader<'o> {
+    options: &'o Options,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Reader<'_> {
+    /// Reports a value of the wrong type, or a map with a `:when` where
+    /// only a plain value can go
+    fn mismatch(&mut self, path: Vec<Segment>, expected: &'static str, value: &Edn<'_>) {
+        if let Edn::Map(entries) = value
+            && entries.contains_key(&Edn::Key("when"))
+            && expected != "vector"
+        {
+            let mut when_path = path;
+            when_path.push(Segment::Key("when".to_string()));
+            self.unsupported_when(when_path);
+            return;
+        }
+        self.diagnostics
+            .push(Diagnostic::type_mismatch(path, expected, type_name(value)));
+    }
+
+    fn config(&mut self, edn: &Edn<'_>) -> FishConfig {
+        let Edn::Map(config_map) = edn else {
+            self.mismatch(vec![], "map", edn);
+            return FishConfig::default();
+        };
+
+        self.check_keys(&[], config_map, SECTIONS, |key| key.starts_with("snippet/"));
+
+        let preamble = self
+            .map_section(config_map, "preambles")
+            .and_then(|preambles| {
+                self.check_keys(&path(&["preambles"]), preambles, PREAMBLE_KEYS, |_| false);
+                let preamble = preambles.get(&Edn::Key("tilde/all"))?;
+                self.string(path(&["preambles", "tilde/all"]), preamble)
+            });
+
+        let greeting = config_map
+            .get(&Edn::Key("fish-greeting"))
+            .and_then(|value| match value {
+                Edn::Str(message) => Some(Greeting::Message(unescape(message))),
+                Edn::Nil | Edn::Bool(false) => Some(Greeting::Disabled),
+                _ => {
+                    self.mismatch(path(&["fish-greeting"]), "string or nil", value);
+                    None
+                }
+            });
+
+        let mut snippets = Vec::new();
+        for (key, value) in config_map {
+            if let Edn::Key(key_str) = key
+                && let Some(name) = key_str.strip_prefix("snippet/")
+                && let Some(content) = self.string(path(&[key_str]), value)
+                && self.code(path(&[key_str]), &content)
+            {
+                snippets.push(Snippet {
+                    name: name.to_string(),
+                    content,
+                });
+            }
+        }
+
+        // Entries with a `:when` of their own, grouped by condition
+        let mut conditionals = Vec::new();
+
+        let (abbrs, guarded) = self.named_map(config_map, "abbrs", names::abbr, Self::abbr);
+        for (when, abbr) in guarded {
+            guard(&mut conditionals, when)
+                .abbrs
+                .get_or_insert_default()
+                .push(abbr);
+        }
+
+        let (aliases, guarded) =
+            self.string_map(config_map, "aliases", names::function, |name, command| {
+                Alias { name, command }
+            });
+        for (when, alias) in guarded {
+            guard(&mut conditionals, when)
+                .aliases
+                .get_or_insert_default()
+                .push(alias);
+        }
+
+        let (mut env, guarded) = self.named_map(config_map, "env", names::variable, Self::env_var);
+        if let Some(unset) = self.unset(config_map, env.iter().flatten())
+            && !unset.is_empty()
+        {
+            env.get_or_insert_default().extend(unset);
+        }
+        for (when, var) in guarded {
+            guard(&mut conditionals, when)
+                .env
+                .get_or_insert_default()
+                .push(var);
+        }
+
+        let (paths, guarded) = match config_map.get(&Edn::Key("paths")) {
+            Some(paths) => self.path_entries(path(&["paths"]), paths, true),
+            None => (None, Vec::new()),
+        };
+        for (when, entry) in guarded {
+            guard(&mut conditionals, when)
+                .paths
+                .get_or_insert_default()
+                .push(entry);
+        }
+
+        let (path_vars, guarded) = self.named_map(
+            config_map,
+            "path-vars",
+            names::path_variable,
+            |reader, var_path, name, value| {
+                let entries = reader.path_entries(var_path, value, false).0?;
+                Some(PathVar { name, entries })
+            },
+        );
+        for (when, var) in guarded {
+            guard(&mut conditionals, when)
+                .path_vars
+                .get_or_insert_default()
+                .push(var);
+        }
+
+        let (functions, guarded) =
+            self.named_map(config_map, "functions", names::function, Self::function);
+        for (when, function) in guarded {
+            guard(&mut conditionals, when)
+                .functions
+                .get_or_insert_default()
+                .push(function);
+        }
+
+        // An alias is a function too, so a function of the same name would
+        // silently replace it
+        let aliases = aliases.map(|aliases| {
+            aliases
+                .into_iter()
+                .filter(|alias| {
+                    let defined = functions
+                        .iter()
+                        .flatten()
+                        .any(|function: &FishFunction| function.name == alias.name);
+                    if defined {
+                        self.diagnostics.push(Diagnostic {
+                            path: path(&["aliases", &alias.name]),
+                            kind: DiagnosticKind::Conflict {
+                                with: format!("functions.{}", alias.name),
+                            },
+                        });
+                    }
+                    !defined
+                })
+                .collect()
+        });
+
+        let (completions, guarded) =
+            self.named_map(config_map, "completions", names::command, Self::completion);
+        for (when, completion) in guarded {
+            guard(&mut conditionals, when)
+                .completions
+                .get_or_insert_default()
+                .push(completion);
+        }
+
+        // `:fish []` still writes its heading, but not an emptied vector
+        let fish = self.string_vector(config_map, "fish").and_then(|commands| {
+            let given = commands.len();
+            let commands = self.commands(commands);
+            (given == 0 || !commands.is_empty()).then_some(commands)
+        });
+
+        let autoload = self.autoload(config_map);
+
+        // Included files are read by the command line; only the paths are
+        // checked here
+        if self.string_vector(config_map, "include").is_some() && !self.options.includes_read {
+            self.invalid(
+                path(&["include"]),
+                "included files are only read by the `tilde-fish` command; merge them first",
+            );
+        }
+
+        let scopes = self.scopes(config_map);
+
+        let colors = self
+            .map_section(config_map, "colors")
+            .map(|colors| self.colors(colors));
+
+        let prompt = self
+            .map_section(config_map, "prompt")
+            .map(|prompt_config| self.prompt(prompt_config));
+
+        let key_bindings = self
+            .map_section(config_map, "key-bindings")
+            .map(|key_bindings| self.key_bindings(key_bindings))
+            .map(|mut key_bindings| {
+                // `fish_user_key_bindings` is where the bindings go
+                if !key_bindings.bindings.is_empty()
+                    && functions
+                        .iter()
+                        .flatten()
+                        .any(|function| function.name == "fish_user_key_bindings")
+                {
+                    self.diagnostics.push(Diagnostic {
+                        path: path(&["key-bindings", "bindings"]),
+                        kind: DiagnosticKind::Conflict {
+                            with: "functions.fish_user_key_bindings".to_string(),
+                        },
+                    });
+                    key_bindings.bindings.clear();
+                }
+                key_bindings
+            });
+
+        let mut config = FishConfig {
+            preamble,
+            greeting,
+            snippets,
+            abbrs,
+            aliases,
+            env,
+            paths,
+            path_vars,
+            functions,
+            autoload_functions: autoload.contains(&"functions"),
+            completions,
+            autoload_completions: autoload.contains(&"completions"),
+            fish,
+            colors,
+            prompt,
+            key_bindings,
+            conditionals,
+            scopes,
+        };
+        for (when, block) in self.conditional_blocks(config_map) {
+            match &self.options.target {
+                Some(target) if target.satisfies(&when) => config.absorb(block),
+                Some(_) => {}
+                None => config.conditionals.push(Conditional {
+                    when,
+                    config: block,
+                }),
+            }
+        }
+        config
+    }
+
+    /// Reads the `:conditional` vector of blocks, each a map of `:when` and
+    /// the sections that apply where it holds
+    fn conditional_blocks(
+        &mut self,
+        config_map: &BTreeMap<Edn<'_>, Edn<'_>>,
+    ) -> Vec<(Condition, FishConfig)> {
+        let items = match config_map.get(&Edn::Key("conditional")) {
+            None => return Vec::new(),
+            Some(Edn::Vector(items)) => items,
+            Some(value) => {
+                self.mismatch(path(&["conditional"]), "vector", value);
+                return Vec::new();
+            }
+        };
+        let mut blocks = Vec::new();
+        for (index, block) in items.iter().enumerate() {
+            let block_path = vec![
+                Segment::Key("conditional".to_string()),
+                Segment::Index(index),
+            ];
+            let Edn::Map(block_map) = block else {
+                self.mismatch(block_path, "map", block);
+                continue;
+            };
+            let mut sections = block_map.clone();
+            let Some(when) = sections.remove(&Edn::Key("when")) else {
+                self.invalid(block_path, "a conditional block needs `:when`");
+                continue;
+            };
+            let mut when_path = block_path.clone();
+            when_path.push(Segment::Key("when".to_string()));
+            let when = self.condition(when_path, &when);
+            for key in TOP_LEVEL_ONLY {
+                if sections.remove(&Edn::Key(key)).is_some() {
+                    let mut key_path = block_path.clone();
+                    key_path.push(Segment::Key(key.to_string()));
+                    self.invalid(key_path, "only allowed at the top level");
+                }
+            }
+
+            // The block is a configuration of its own, with paths under it
+            let mut reader = Reader {
+                options: self.options,
+                diagnostics: Vec::new(),
+            };
+            let config = reader.config(&Edn::Map(sections));
+            for mut diagnostic in reader.diagnostics {
+                diagnostic.path.splice(0..0, block_path.iter().cloned());
+                self.diagnostics.push(diagnostic);
+            }
+            if let Some(when) = when {
+                blocks.push((when, config));
+            }
+        }
+        blocks
+    }
+
+    /// Reads a `:when` map, or `None` if any part of it is rejected, since
+    /// applying its sections unconditionally would be wrong
+    fn condition(&mut self, when_path: Vec<Segment>, value: &Edn<'_>) -> Option<Condition> {
+        let Edn::Map(tests) = value else {
+            self.mismatch(when_path, "map", value);
+            return None;
+        };
+        self.check_keys(&when_path, tests, CONDITION_KEYS, |_| false);
+        let test_path = |key: &str| {
+            let mut test_path = when_path.clone();
+            test_path.push(Segment::Key(key.to_string()));
+            test_path
+        };
+        let diagnostics = self.diagnostics.len();
+
+        let mut os = Vec::new();
+        let os_items: Vec<(Vec<Segment>, &Edn<'_>)> = match tests.get(&Edn::Key("os")) {
+            None => Vec::new(),
+            Some(Edn::Vector(items)) => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    let mut item_path = test_path("os");
+                    item_path.push(Segment::Index(index));
+                    (item_path, item)
+                })
+                .collect(),
+            Some(item) => vec![(test_path("os"), item)],
+        };
+        for (item_path, item) in os_items {
+            match item {
+                Edn::Key(keyword) => match Os::from_keyword(keyword) {
+                    Some(system) => os.push(system),
+                    None => self.invalid(
+                        item_path,
+                        "`:os` must be `:linux`, `:macos`, `:freebsd`, `:openbsd` or `:netbsd`",
+                    ),
+                },
+                _ => self.mismatch(item_path, "keyword or vector of them", item),
+            }
+        }
+
+        let hostname = self.words(tests, &test_path("hostname"), names::hostname);
+        let hostname_regex = tests.get(&Edn::Key("hostname-regex")).and_then(|regex| {
+            let regex = self.string(test_path("hostname-regex"), regex)?;
+            if regex::Regex::new(&whole_match(&regex)).is_err() {
+                self.invalid(
+                    test_path("hostname-regex"),
+                    "not a valid regular expression",
+                );
+                return None;
+            }
+            Some(regex)
+        });
+
+        let mut env = Vec::new();
+        match tests.get(&Edn::Key("env")) {
+            None => {}
+            Some(Edn::Map(vars)) => {
+                for (key, value) in vars {
+                    let mut var_path = test_path("env");
+                    var_path.push(key_segment(key));
+                    let Some(name) = key_name(key) else {
+                        self.mismatch(var_path, "keyword or string key", key);
+                        continue;
+                    };
+                    if let Err(reason) = names::variable(name) {
+                        self.diagnostics.push(Diagnostic {
+                            path: var_path,
+                            kind: DiagnosticKind::InvalidName { reason },
+                        });
+                        continue;
+                    }
+                    let test = match value {
+                        Edn::Bool(true) => EnvTest::Set,
+                        Edn::Bool(false) | Edn::Nil => EnvTest::Unset,
+                        Edn::Str(value) => EnvTest::Equals(unescape(value)),
+                        _ => {
+                            self.mismatch(var_path, "string, boolean or nil", value);
+                            continue;
+                        }
+                    };
+                    env.push(EnvCondition {
+                        name: name.to_string(),
+                        test,
+                    });
+                }
+            }
+            Some(value) => self.mismatch(test_path("env"), "map", value),
+        }
+
+        if self.diagnostics.len() > diagnostics {
+            return None;
+        }
+        let condition = Condition {
+            os,
+            hostname,
+            hostname_regex,
+            env,
+        };
+        if condition == Condition::default() {
+            self.invalid(
+                when_path,
+                "a condition needs `:os`, `:hostname`, `:hostname-regex` or `:env`",
+            );
+            return None;
+        }
+        Some(condition)
+    }
+
+    /// Reads the `:scope` map of section keywords to scopes
+    fn scopes(&mut self, config_map: &BTreeMap<Edn<'_>, Edn<'_>>) -> BTreeMap<String, Scope> {
+        let mut scopes = BTreeMap::new();
+        let Some(entries) = self.map_section(config_map, "scope") else {
+            return scopes;
+        };
+        for (key, value) in entries {
+            let entry_path = vec![Segment::Key("scope".to_string()), key_segment(key)];
+            let section = match key {
+                Edn::Key(section)
+                    if SCOPED_SECTIONS.contains(section) || section.starts_with("snippet/") =>
+                {
+                    section
+                }
+                _ => {
+                    self.diagnostics
+                        .push(Diagnostic::unknown_key(entry_path, SCOPED_SECTIONS));
+                    continue;
+                }
+            };
+            let scope = match value {
+                Edn::Key("interactive") => Scope::Interactive,
+                Edn::Key("login") => Scope::Login,
+                Edn::Key("always") => Scope::Always,
+                Edn::Key(_) => {
+                    self.invalid(
+                        entry_path,
+                        "`:scope` must be `:interactive`, `:login` or `:always`",
+                    );
+                    continue;
+                }
+                _ => {
+                    self.mismatch(entry_path, "keyword", value);
+                    continue;
+                }
+            };
+            scopes.insert(section.to_string(), scope);
+        }
+        scopes
+    }
+
+    /// Reads the `:profiles` map of names to configurations to lay over the
+    /// rest, reporting a selected profile that isn't there
+    fn profiles<'e>(&mut self, edn: &Edn<'e>) -> Vec<(Edn<'e>, BTreeMap<Edn<'e>, Edn<'e>>)> {
+        let entries = match edn {
+            Edn::Map(config_map) => self.map_section(config_map, "profiles"),
+            _ => None,
+        };
+        let mut profiles = Vec::new();
+        for (key, value) in entries.into_iter().flatten() {
+            let profile_path = vec![Segment::Key("profiles".to_string()), key_segment(key)];
+            if key_name(key).is_none() {
+                self.mismatch(profile_path, "keyword or string key", key);
+                continue;
+            }
+            let Edn::Map(profile) = value else {
+                self.mismatch(profile_path, "map", value);
+                continue;
+            };
+            let mut profile = profile.clone();
+            for key in NOT_IN_PROFILES {
+                if profile.remove(&Edn::Key(key)).is_some() {
+                    let mut key_path = profile_path.clone();
+                    key_path.push(Segment::Key(key.to_string()));
+                    self.invalid(key_path, "only allowed at the top level");
+                }
+            }
+            profiles.push((key.clone(), profile));
+        }
+
+        if let Some(selected) = &self.options.profile
+            && !profiles
+                .iter()
+                .any(|(name, _)| key_name(name) == Some(selected))
+        {
+            let names: Vec<&str> = profiles
+                .iter()
+                .filter_map(|(name, _)| key_name(name))
+                .collect();
+            self.diagnostics.push(Diagnostic {
+                path: vec![
+                    Segment::Key("profiles".to_string()),
+                    Segment::Key(selected.clone()),
+                ],
+                kind: DiagnosticKind::Unknown {
+                    what: "profile",
+                    suggestion: suggest(selected, &names).map(str::to_string),
+                },
+            });
+        }
+        profiles
+    }
+
+    /// Reads the `:autoload` vector of section keywords
+    fn autoload<'e>(&mut self, config_map: &BTreeMap<Edn<'e>, Edn<'e>>) -> Vec<&'e str> {
+        let items = match config_map.get(&Edn::Key("autoload")) {
+            None => return Vec::new(),
+            Some(Edn::Vector(items)) => items,
+            Some(value) => {
+                self.mismatch(path(&["autoload"]), "vector", value);
+                return Vec::new();
+            }
+        };
+        let mut sections = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            let item_path = vec![Segment::Key("autoload".to_string()), Segment::Index(index)];
+            match item {
+                Edn::Key(section) if AUTOLOAD_SECTIONS.contains(section) => sections.push(*section),
+                Edn::Key(_) => self.invalid(
+                    item_path,
+                    "only `:functions` and `:completions` can be autoloaded",
+                ),
+                _ => self.mismatch(item_path, "keyword", item),
+            }
+        }
+        sections
+    }
+
+    fn prompt(&mut self, prompt_config: &BTreeMap<Edn<'_>, Edn<'_>>) -> Prompt {
+        self.check_keys(&path(&["prompt"]), prompt_config, PROMPT_KEYS, |_| false);
+
+        let preset = prompt_config
+            .get(&Edn::Key("style"))
+            .and_then(|style| self.prompt_style(style));
+        let show_git =
+            prompt_config
+                .get(&Edn::Key("show-git"))
+                .and_then(|show_git| match show_git {
+                    Edn::Bool(show_git) => Some(*show_git),
+                    _ => {
+                        self.mismatch(path(&["prompt", "show-git"]), "boolean", show_git);
+                        None
+                    }
+                });
+        let separator = prompt_config
+            .get(&Edn::Key("separator"))
+            .and_then(|separator| self.string(path(&["prompt", "separator"]), separator));
+
+        // The style's segments and separator, unless given here
+        let preset = preset.unwrap_or_else(|| Prompt {
+            separator: " ".to_string(),
+            ..Prompt::default()
+        });
+        let mut left = self
+            .prompt_segments(prompt_config, "left")
+            .unwrap_or(preset.left);
+        let mut right = self
+            .prompt_segments(prompt_config, "right")
+            .unwrap_or(preset.right);
+        let separator = separator.unwrap_or(preset.separator);
+        if show_git == Some(false) {
+            for segments in [&mut left, &mut right] {
+                segments.retain(|segment| {
+                    !matches!(
+                        segment.kind,
+                        SegmentKind::GitBranch | SegmentKind::GitDirty(_)
+                    )
+                });
+            }
+        }
+        Prompt {
+            left,
+            right,
+            separator,
+        }
+    }
+
+    /// Looks up the preset named by `:style`
+    fn prompt_style(&mut self, style: &Edn<'_>) -> Option<Prompt> {
+        let style_path = path(&["prompt", "style"]);
+        let Some(name) = key_name(style) else {
+            self.mismatch(style_path, "keyword or string", style);
+            return None;
+        };
+        let preset = preset::prompt(name);
+        if preset.is_none() {
+            self.diagnostics.push(Diagnostic {
+                path: style_path,
+                kind: DiagnosticKind::Unknown {
+                    what: "prompt style",
+                    suggestion: suggest(name, preset::STYLES).map(str::to_string),
+                },
+            });
+        }
+        preset
+    }
+
+    /// Reads the `:left` or `:right` vector of prompt segments, if given
+    fn prompt_segments(
+        &mut self,
+        prompt_config: &BTreeMap<Edn<'_>, Edn<'_>>,
+        side: &str,
+    ) -> Option<Vec<PromptSegment>> {
+        let side_path = path(&["prompt", side]);
+        let value = prompt_config.get(&Edn::Key(side))?;
+        let Edn::Vector(items) = value else {
+            self.mismatch(side_path, "vector", value);
+            return None;
+        };
+        let segments = items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let mut segment_path = side_path.clone();
+                segment_path.push(Segment::Index(index));
+                self.prompt_segment(segment_path, item)
+            })
+            .collect();
+        Some(segments)
+    }
+
+    /// Reads a segment, given as its keyword or as a map of options
+    fn prompt_segment(
+        &mut self,
+        segment_path: Vec<Segment>,
+        value: &Edn<'_>,
+    ) -> Option<PromptSegment> {
+        let options = match value {
+            Edn::Key(_) => {
+                let kind = self.segment_kind(segment_path, value)?;
+                return Some(PromptSegment::new(kind));
+            }
+            Edn::Map(options) => options,
+            _ => {
+                self.mismatch(segment_path, "keyword or map", value);
+                return None;
+            }
+        };
+        self.check_keys(&segment_path, options, SEGMENT_KEYS, |_| false);
+        let option_path = |key: &str| {
+            let mut option_path = segment_path.clone();
+            option_path.push(Segment::Key(key.to_string()));
+            option_path
+        };
+        let diagnostics = self.diagnostics.len();
+
+        let symbol = options
+            .get(&Edn::Key("symbol"))
+            .and_then(|symbol| self.string(option_path("symbol"), symbol));
+        let kind = match (
+            options.get(&Edn::Key("segment")),
+            options.get(&Edn::Key("text")),
+        ) {
+            (Some(kind), None) => self.segment_kind(option_path("segment"), kind),
+            (None, Some(text)) => self
+                .string(option_path("text"), text)
+                .map(SegmentKind::Text),
+            (Some(_), Some(_)) => {
+                self.invalid(
+                    segment_path.clone(),
+                    "a segment takes either `:segment` or `:text`, not both",
+                );
+                None
+            }
+            (None, None) => {
+                self.invalid(
+                    segment_path.clone(),
+                    "a segment needs `:segment` or `:text`",
+                );
+                None
+            }
+        };
+        let kind = match (kind, symbol) {
+            (Some(SegmentKind::GitDirty(_)), Some(symbol)) => Some(SegmentKind::GitDirty(symbol)),
+            (Some(_), Some(_)) => {
+                self.invalid(option_path("symbol"), "only `:git-dirty` takes a `:symbol`");
+                None
+            }
+            (kind, _) => kind,
+        };
+        let color = options.get(&Edn::Key("color")).and_then(|color| {
+            let spec = self.color_spec(option_path("color"), color)?;
+            self.check_spec(option_path("color"), &spec).then_some(spec)
+        });
+        let mut text = |key: &str| {
+            options
+                .get(&Edn::Key(key))
+                .and_then(|value| self.string(option_path(key), value))
+        };
+        let prefix = text("prefix");
+        let suffix = text("suffix");
+        let separator = text("separator");
+
+        if self.diagnostics.len() > diagnostics {
+            return None;
+        }
+        Some(PromptSegment {
+            kind: kind?,
+            color: color.unwrap_or_default(),
+            prefix: prefix.unwrap_or_default(),
+            suffix: suffix.unwrap_or_default(),
+            separator,
+        })
+    }
+
+    /// Reads a segment keyword
+    fn segment_kind(&mut self, kind_path: Vec<Segment>, value: &Edn<'_>) -> Option<SegmentKind> {
+        let Edn::Key(name) = value else {
+            self.mismatch(kind_path, "keyword", value);
+            return None;
+        };
+        let kind = match *name {
+            "cwd" => SegmentKind::Cwd,
+            "git-branch" => SegmentKind::GitBranch,
+            "git-dirty" => SegmentKind::GitDirty("*".to_string()),
+            "status" => SegmentKind::Status,
+            "duration" => SegmentKind::Duration,
+            "jobs" => SegmentKind::Jobs,
+            "venv" => SegmentKind::Venv,
+            "hostname" => SegmentKind::Hostname,
+            "time" => SegmentKind::Time,
+            _ => {
+                self.diagnostics.push(Diagnostic {
+                    path: kind_path,
+                    kind: DiagnosticKind::Unknown {
+                        what: "prompt segment",
+                        suggestion: suggest(name, PROMPT_SEGMENTS).map(str::to_string),
+                    },
+                });
+                return None;
+            }
+        };
+        Some(kind)
+    }
+
+    /// Reads the `:colors` map of color variables to specs, starting from
+    /// the colors of its `:theme` file
+    fn colors(&mut self, colors_map: &BTreeMap<Edn<'_>, Edn<'_>>) -> Vec<Color> {
+        let mut colors = Vec::new();
+        if let Some(theme) = colors_map.get(&Edn::Key("theme"))
+            && let Some(file) = self.string(path(&["colors", "theme"]), theme)
+        {
+            // Theme files are read by the caller, relative to the file
+            // naming them
+            let options = self.options;
+            match options.themes.get(&file) {
+                Some(Ok(text)) => {
+                    for (variable, spec) in color::theme(text) {
+                        let variable_path = path(&["colors", "theme", &variable]);
+                        if let Some(color) = self.color(variable_path, variable, spec) {
+                            colors.push(color);
+                        }
+                    }
+                }
+                Some(Err(error)) => self.diagnostics.push(Diagnostic {
+                    path: path(&["colors", "theme"]),
+                    kind: DiagnosticKind::Unreadable {
+                        error: error.clone(),
+                    },
+                }),
+                None => self.invalid(
+                    path(&["colors", "theme"]),
+                    "theme files are only read by the `tilde-fish` command; pass it in `Options::themes`",
+                ),
+            }
+        }
+
+        let themed = colors.len();
+        let mut seen: Vec<(&str, String)> = Vec::new();
+        for (key, value) in colors_map {
+            let variable_path = vec![Segment::Key("colors".to_string()), key_segment(key)];
+            let Some(variable) = key_name(key) else {
+                self.mismatch(variable_path, "keyword or string key", key);
+                continue;
+            };
+            if variable == "theme" && matches!(key, Edn::Key(_)) {
+                continue;
+            }
+            if *key == Edn::Key("when") {
+                self.unsupported_when(variable_path);
+                continue;
+            }
+            if let Some((_, first)) = seen.iter().find(|(seen, _)| *seen == variable) {
+                self.diagnostics.push(Diagnostic {
+                    path: variable_path,
+                    kind: DiagnosticKind::Conflict {
+                        with: first.clone(),
+                    },
+                });
+                continue;
+            }
+            seen.push((variable, key.to_string()));
+            let Some(spec) = self.color_spec(variable_path.clone(), value) else {
+                continue;
+            };
+            let Some(color) = self.color(variable_path, variable.to_string(), spec) else {
+                continue;
+            };
+            // Colors given here override the theme's
+            match colors[..themed]
+                .iter_mut()
+                .find(|existing| existing.variable == color.variable)
+            {
+                Some(existing) => existing.spec = color.spec,
+                None => colors.push(color),
+            }
+        }
+        colors
+    }
+
+    /// Checks a color variable and its spec
+    fn color(
+        &mut self,
+        variable_path: Vec<Segment>,
+        variable: String,
+        spec: Vec<String>,
+    ) -> Option<Color> {
+        if !color::COLOR_VARIABLES.contains(&variable.as_str()) {
+            self.diagnostics.push(Diagnostic::unknown_key(
+                variable_path,
+                color::COLOR_VARIABLES,
+            ));
+            return None;
+        }
+        if !self.check_spec(variable_path, &spec) {
+            return None;
+        }
+        Some(Color { variable, spec })
+    }
+
+    /// Checks each word of a color spec, reporting the first that
+    /// `set_color` would reject
+    fn check_spec(&mut self, spec_path: Vec<Segment>, spec: &[String]) -> bool {
+        match spec.iter().find_map(|word| color::spec_word(word).err()) {
+            Some(reason) => {
+                self.invalid(spec_path, reason);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Reads a color spec given as a string of words or a vector of them
+    fn color_spec(&mut self, spec_path: Vec<Segment>, value: &Edn<'_>) -> Option<Vec<String>> {
+        match value {
+            Edn::Str(spec) => Some(
+                unescape(spec)
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect(),
+            ),
+            Edn::Vector(items) => {
+                let mut spec = Vec::new();
+                for (index, item) in items.iter().enumerate() {
+                    let mut item_path = spec_path.clone();
+                    item_path.push(Segment::Index(index));
+                    spec.extend(self.string(item_path, item));
+                }
+                (spec.len() == items.len()).then_some(spec)
+            }
+            _ => {
+                self.mismatch(spec_path, "string or vector", value);
+                None
+            }
+        }
+    }
+
+    fn key_bindings(&mut self, key_bindings: &BTreeMap<Edn<'_>, Edn<'_>>) -> KeyBindings {
+        self.check_keys(
+            &path(&["key-bindings"]),
+            key_bindings,
+            KEY_BINDINGS_KEYS,
+            |_| false,
+        );
+
+        let mode = match key_bindings.get(&Edn::Key("mode")) {
+            None => None,
+            Some(Edn::Key("emacs")) => Some(KeyMode::Emacs),
+            Some(Edn::Key("vi")) => Some(KeyMode::Vi),
+            Some(Edn::Key("hybrid")) => Some(KeyMode::Hybrid),
+            Some(Edn::Key(_)) => {
+                self.invalid(
+                    path(&["key-bindings", "mode"]),
+                    "`:mode` must be `:emacs`, `:vi` or `:hybrid`",
+                );
+                None
+            }
+            Some(mode) => {
+                self.mismatch(path(&["key-bindings", "mode"]), "keyword", mode);
+                None
+            }
+        };
+
+        let items = match key_bindings.get(&Edn::Key("bindings")) {
+            None => &Vec::new(),
+            Some(Edn::Vector(items)) => items,
+            Some(value) => {
+                self.mismatch(path(&["key-bindings", "bindings"]), "vector", value);
+                &Vec::new()
+            }
+        };
+        let mut bindings: Vec<(usize, KeyBinding)> = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            let mut binding_path = path(&["key-bindings", "bindings"]);
+            binding_path.push(Segment::Index(index));
+            let Some(binding) = self.key_binding(binding_path.clone(), item) else {
+                continue;
+            };
+            // A second binding for the same keys replaces the first
+            if let Some((first, _)) = bindings.iter().find(|(_, existing)| {
+                existing.keys == binding.keys && existing.mode == binding.mode
+            }) {
+                let mut first_path = path(&["key-bindings", "bindings"]);
+                first_path.push(Segment::Index(*first));
+                self.diagnostics.push(Diagnostic {
+                    path: binding_path,
+                    kind: DiagnosticKind::Conflict {
+                        with: location(&first_path),
+                    },
+                });
+                continue;
+            }
+            bindings.push((index, binding));
+        }
+
+        KeyBindings {
+            mode,
+            bindings: bindings.into_iter().map(|(_, binding)| binding).collect(),
+        }
+    }
+
+    /// Reads one binding map, leaving it out if any of its options is
+    /// rejected
+    fn key_binding(&mut self, binding_path: Vec<Segment>, value: &Edn<'_>) -> Option<KeyBinding> {
+        let Edn::Map(options) = value else {
+            self.mismatch(binding_path, "map", value);
+            return None;
+        };
+        self.check_keys(&binding_path, options, BINDING_KEYS, |_| false);
+        let option_path = |key: &str| {
+            let mut option_path = binding_path.clone();
+            option_path.push(Segment::Key(key.to_string()));
+            option_path
+        };
+        let diagnostics = self.diagnostics.len();
+
+        let keys = match options.get(&Edn::Key("keys")) {
+            Some(keys) => {
+                self.string(option_path("keys"), keys)
+                    .filter(|keys| match names::keys(keys) {
+                        Ok(()) => true,
+                        Err(reason) => {
+                            self.invalid(option_path("keys"), reason);
+                            false
+                        }
+                    })
+            }
+            None => {
+                self.invalid(binding_path.clone(), "a binding needs `:keys`");
+                None
+            }
+        };
+
+        let functions = options.get(&Edn::Key("command")).map(|_| {
+            let functions = self.words(options, &option_path("command"), |_| Ok(()));
+            let command_path = option_path("command");
+            for (index, function) in functions.iter().enumerate() {
+                if names::BIND_FUNCTIONS.contains(&function.as_str()) {
+                    continue;
+                }
+                let mut function_path = command_path.clone();
+                if matches!(options.get(&Edn::Key("command")), Some(Edn::Vector(_))) {
+                    function_path.push(Segment::Index(index));
+                }
+                self.diagnostics.push(Diagnostic {
+                    path: function_path,
+                    kind: DiagnosticKind::Unknown {
+                        what: "bind function",
+                        suggestion: suggest(function, names::BIND_FUNCTIONS).map(str::to_string),
+                    },
+                });
+            }
+            functions
+        });
+        let fish = options.get(&Edn::Key("fish")).and_then(|fish| {
+            self.string(option_path("fish"), fish)
+                .filter(|fish| self.code(option_path("fish"), fish))
+        });
+        let mut mode = |key: &str| {
+            let value = options.get(&Edn::Key(key))?;
+            let Edn::Key(mode) = value else {
+                self.mismatch(option_path(key), "keyword", value);
+                return None;
+            };
+            match names::bind_mode(mode) {
+                Ok(()) => Some(mode.to_string()),
+                Err(reason) => {
+                    self.invalid(option_path(key), reason);
+                    None
+                }
+            }
+        };
+        let mode_name = mode("mode");
+        let sets_mode = mode("sets-mode");
+
+        if self.diagnostics.len() > diagnostics {
+            return None;
+        }
+        let action = match (functions, fish) {
+            (Some(functions), None) if !functions.is_empty() => KeyAction::Functions(functions),
+            (None, Some(fish)) => KeyAction::Fish(fish),
+            (Some(_), Some(_)) => {
+                self.invalid(
+                    binding_path,
+                    "a binding takes either `:command` or `:fish`, not both",
+                );
+                return None;
+            }
+            _ => {
+                self.invalid(binding_path, "a binding needs `:command` or `:fish`");
+                return None;
+            }
+        };
+        Some(KeyBinding {
+            keys: keys?,
+            action,
+            mode: mode_name,
+            sets_mode,
+        })
+    }
+
+    /// Reads an abbreviation, given as its expansion or as a map of options
+    fn abbr(&mut self, abbr_path: Vec<Segment>, name: String, value: &Edn<'_>) -> Option<Abbr> {
+        let options = match value {
+            Edn::Str(expansion) => {
+                return Some(Abbr {
+                    name,
+                    expansion: AbbrExpansion::Text(unescape(expansion)),
+                    position: AbbrPosition::default(),
+                    regex: None,
+                    set_cursor: None,
+                });
+            }
+            Edn::Map(options) => options,
+            _ => {
+                self.mismatch(abbr_path, "string or map", value);
+                return None;
+            }
+        };
+        self.check_keys(&abbr_path, options, ABBR_KEYS, |_| false);
+        let option_path = |key: &str| {
+            let mut option_path = abbr_path.clone();
+            option_path.push(Segment::Key(key.to_string()));
+            option_path
+        };
+        let mut valid = true;
+
+        let text = options
+            .get(&Edn::Key("expansion"))
+            .map(|text| self.string(option_path("expansion"), text));
+        let function = options
+            .get(&Edn::Key("function"))
+            .map(|function| self.string(option_path("function"), function));
+        if let Some(Some(function)) = &function
+            && let Err(reason) = names::function(function)
+        {
+            self.diagnostics.push(Diagnostic {
+                path: option_path("function"),
+                kind: DiagnosticKind::InvalidName { reason },
+            });
+            valid = false;
+        }
+        let expansion = match (text, function) {
+            (Some(Some(text)), None) => AbbrExpansion::Text(text),
+            (None, Some(Some(function))) => AbbrExpansion::Function(function),
+            (Some(_), Some(_)) => {
+                self.invalid(
+                    abbr_path,
+                    "an abbreviation takes either `:expansion` or `:function`, not both",
+                );
+                return None;
+            }
+            (None, None) => {
+                self.invalid(
+                    abbr_path,
+                    "an abbreviation needs `:expansion` or `:function`",
+                );
+                return None;
+            }
+            // Already reported as a type mismatch
+            _ => return None,
+        };
+
+        let position = match options.get(&Edn::Key("position")) {
+            None | Some(Edn::Key("command")) => AbbrPosition::Command,
+            Some(Edn::Key("anywhere")) => AbbrPosition::Anywhere,
+            Some(Edn::Key(_)) => {
+                self.invalid(
+                    option_path("position"),
+                    "`:position` must be `:command` or `:anywhere`",
+                );
+                valid = false;
+                AbbrPosition::Command
+            }
+            Some(position) => {
+                self.mismatch(option_path("position"), "keyword", position);
+                valid = false;
+                AbbrPosition::Command
+            }
+        };
+
+        let regex = match options.get(&Edn::Key("regex")) {
+            None => None,
+            Some(regex) => match self.string(option_path("regex"), regex) {
+                Some(regex) if regex.is_empty() => {
+                    self.invalid(option_path("regex"), "`:regex` cannot be empty");
+                    valid = false;
+                    None
+                }
+                Some(regex) => Some(regex),
+                None => {
+                    valid = false;
+                    None
+                }
+            },
+        };
+
+        let set_cursor = match options.get(&Edn::Key("set-cursor")) {
+            None | Some(Edn::Bool(false)) => None,
+            Some(Edn::Bool(true)) => Some("%".to_string()),
+            Some(Edn::Str(marker)) if !marker.is_empty() => Some(unescape(marker)),
+            Some(marker) => {
+                self.mismatch(
+                    option_path("set-cursor"),
+                    "boolean or non-empty string",
+                    marker,
+                );
+                valid = false;
+                None
+            }
+        };
+        if let (Some(marker), AbbrExpansion::Text(text)) = (&set_cursor, &expansion)
+            && !text.contains(marker.as_str())
+        {
+            self.invalid(
+                option_path("set-cursor"),
+                "the expansion doesn't contain the cursor marker",
+            );
+            valid = false;
+        }
+
+        valid.then_some(Abbr {
+            name,
+            expansion,
+            position,
+            regex,
+            set_cursor,
+        })
+    }
+
+    /// Reads a function, given as its body or as a map of options
+    fn function(
+        &mut self,
+        function_path: Vec<Segment>,
+        name: String,
+        value: &Edn<'_>,
+    ) -> Option<FishFunction> {
+        let options = match value {
+            Edn::Str(_) => {
+                let body = self.string(function_path.clone(), value)?;
+                return self.code(function_path, &body).then_some(FishFunction {
+                    name,
+                    body,
+                    ..FishFunction::default()
+                });
+            }
+            Edn::Map(options) => options,
+            _ => {
+                self.mismatch(function_path, "string or map", value);
+                return None;
+            }
+        };
+        self.check_keys(&function_path, options, FUNCTION_KEYS, |_| false);
+        let option_path = |key: &str| {
+            let mut option_path = function_path.clone();
+            option_path.push(Segment::Key(key.to_string()));
+            option_path
+        };
+        let diagnostics = self.diagnostics.len();
+
+        let body = match options.get(&Edn::Key("body")) {
+            Some(body) => self
+                .string(option_path("body"), body)
+                .filter(|body| self.code(option_path("body"), body)),
+            None => {
+                self.invalid(function_path.clone(), "a function needs a `:body`");
+                None
+            }
+        };
+        let mut string = |key: &str| {
+            let value = options.get(&Edn::Key(key))?;
+            self.string(option_path(key), value)
+        };
+        let description = string("description");
+        let wraps = string("wraps");
+
+        let argument_names = self.words(options, &option_path("argument-names"), names::variable);
+        let on_event = self.words(options, &option_path("on-event"), names::event);
+        let on_variable = self.words(options, &option_path("on-variable"), names::variable);
+        let on_signal = self.words(options, &option_path("on-signal"), names::signal);
+        let on_job_exit = self.words(options, &option_path("on-job-exit"), names::job);
+        let inherit_variable =
+            self.words(options, &option_path("inherit-variable"), names::variable);
+        let no_scope_shadowing = self.flag(options, &option_path("no-scope-shadowing"));
+
+        // Leave out functions with any rejected option, since a handler or
+        // argument that silently went missing would change their behavior
+        if self.diagnostics.len() > diagnostics {
+            return None;
+        }
+        Some(FishFunction {
+            name,
+            body: body?,
+            description,
+            argument_names,
+            wraps,
+            on_event,
+            on_variable,
+            on_signal,
+            on_job_exit,
+            inherit_variable,
+            no_scope_shadowing,
+        })
+    }
+
+    /// Reads the option specs for one command's completions
+    fn completion(
+        &mut self,
+        completion_path: Vec<Segment>,
+        command: String,
+        value: &Edn<'_>,
+    ) -> Option<Completion> {
+        let Edn::Vector(specs) = value else {
+            self.mismatch(completion_path, "vector", value);
+            return None;
+        };
+        let mut options = Vec::new();
+        for (index, spec) in specs.iter().enumerate() {
+            let mut spec_path = completion_path.clone();
+            spec_path.push(Segment::Index(index));
+            if let Some(option) = self.completion_option(spec_path, spec) {
+                options.push(option);
+            }
+        }
+        Some(Completion { command, options })
+    }
+
+    /// Reads one completion spec map, leaving it out if any of its options
+    /// is rejected
+    fn completion_option(
+        &mut self,
+        spec_path: Vec<Segment>,
+        spec: &Edn<'_>,
+    ) -> Option<CompletionOption> {
+        let Edn::Map(options) = spec else {
+            self.mismatch(spec_path, "map", spec);
+            return None;
+        };
+        self.check_keys(&spec_path, options, COMPLETION_KEYS, |_| false);
+        let option_path = |key: &str| {
+            let mut option_path = spec_path.clone();
+            option_path.push(Segment::Key(key.to_string()));
+            option_path
+        };
+        let diagnostics = self.diagnostics.len();
+
+        let mut string = |key: &str, check: fn(&str) -> Result<(), &'static str>| {
+            let value = options.get(&Edn::Key(key))?;
+            let value = self.string(option_path(key), value)?;
+            match check(&value) {
+                Ok(()) => Some(value),
+                Err(reason) => {
+                    self.invalid(option_path(key), reason);
+                    None
+                }
+            }
+        };
+        let short = string("short", names::short_option);
+        let long = string("long", names::long_option);
+        let description = string("description", |_| Ok(()));
+        let condition = string("condition", |_| Ok(()))
+            .filter(|condition| self.code(option_path("condition"), condition));
+        let arguments = match options.get(&Edn::Key("arguments")) {
+            Some(Edn::Vector(_)) => Some(
+                self.words(options, &option_path("arguments"), |_| Ok(()))
+                    .iter()
+                    .map(|candidate| escape(candidate))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Some(arguments) => self
+                .string(option_path("arguments"), arguments)
+                .filter(|arguments| self.code(option_path("arguments"), arguments)),
+            None => None,
+        };
+        let no_files = self.flag(options, &option_path("no-files"));
+        let require_parameter = self.flag(options, &option_path("require-parameter"));
+
+        if self.diagnostics.len() > diagnostics {
+            return None;
+        }
+        if short.is_none() && long.is_none() && arguments.is_none() && !no_files {
+            // `complete -c <command>` alone lists completions instead
+            self.invalid(
+                spec_path,
+                "a completion needs `:short`, `:long`, `:arguments` or `:no-files`",
+            );
+            return None;
+        }
+        Some(CompletionOption {
+            short,
+            long,
+            description,
+            arguments,
+            condition,
+            no_files,
+            require_parameter,
+        })
+    }
+
+    /// Reads a vector of directories, each a string or a map of options,
+    /// reporting a directory listed twice
+    ///
+    /// `fish_add_path` is whether they are for `:paths` rather than
+    /// `:path-vars`. Like [`Reader::named_map`], it returns `:paths` entries
+    /// with a `:when` of their own separately.
+    fn path_entries(
+        &mut self,
+        list_path: Vec<Segment>,
+        value: &Edn<'_>,
+        fish_add_path: bool,
+    ) -> (Option<Vec<PathEntry>>, Vec<(Condition, PathEntry)>) {
+        let Edn::Vector(items) = value else {
+            self.mismatch(list_path, "vector", value);
+            return (None, Vec::new());
+        };
+        let mut entries: Vec<(usize, Option<Condition>, PathEntry)> = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            let mut entry_path = list_path.clone();
+            entry_path.push(Segment::Index(index));
+            // Only `:paths` entries can have a `:when`; a `:path-vars` list
+            // is set as a whole
+            let Some((when, entry_path, item)) = (if fish_add_path {
+                self.unwrap_when(entry_path, item)
+            } else {
+                Some((None, entry_path, Cow::Borrowed(item)))
+            }) else {
+                continue;
+            };
+            let Some(entry) = self.path_entry(entry_path.clone(), &item, fish_add_path) else {
+                continue;
+            };
+            if let Some((first, _, _)) = entries
+                .iter()
+                .find(|(_, _, existing)| same_directory(&existing.path, &entry.path))
+            {
+                let mut first_path = list_path.clone();
+                first_path.push(Segment::Index(*first));
+                self.diagnostics.push(Diagnostic {
+                    path: entry_path,
+                    kind: DiagnosticKind::Conflict {
+                        with: location(&first_path),
+                    },
+                });
+                continue;
+            }
+            entries.push((index, when, entry));
+        }
+
+        let mut items = Vec::new();
+        let mut guarded = Vec::new();
+        let mut conditional = false;
+        for (_, when, entry) in entries {
+            match (when, &self.options.target) {
+                (None, _) => items.push(entry),
+                (Some(when), Some(target)) => {
+                    if target.satisfies(&when) {
+                        items.push(entry);
+                    } else {
+                        conditional = true;
+                    }
+                }
+                (Some(when), None) => guarded.push((when, entry)),
+            }
+        }
+        if items.is_empty() && (conditional || !guarded.is_empty()) {
+            return (None, guarded);
+        }
+        (Some(items), guarded)
+    }
+
+    /// Reads one directory, given as its path or as a map of options,
+    /// leaving it out if any option is rejected
+    fn path_entry(
+        &mut self,
+        entry_path: Vec<Segment>,
+        value: &Edn<'_>,
+        fish_add_path: bool,
+    ) -> Option<PathEntry> {
+        let options = match value {
+            Edn::Str(_) => {
+                let path = self.string(entry_path.clone(), value)?;
+                return self.directory(entry_path, path);
+            }
+            Edn::Map(options) => options,
+            _ => {
+                self.mismatch(entry_path, "string or map", value);
+                return None;
+            }
+        };
+        let option_path = |key: &str| {
+            let mut option_path = entry_path.clone();
+            option_path.push(Segment::Key(key.to_string()));
+            option_path
+        };
+        let diagnostics = self.diagnostics.len();
+        self.check_keys(&entry_path, options, PATH_KEYS, |_| false);
+
+        let path = match options.get(&Edn::Key("path")) {
+            Some(path) => self
+                .string(option_path("path"), path)
+                .and_then(|path| self.directory(option_path("path"), path)),
+            None => {
+                self.invalid(entry_path.clone(), "a path entry needs a `:path`");
+                None
+            }
+        };
+        let append = self.flag(options, &option_path("append"));
+        let move_existing = self.flag(options, &option_path("move"));
+        let if_exists = self.flag(options, &option_path("if-exists"));
+        let global = self.flag(options, &option_path("global"));
+        if fish_add_path && options.get(&Edn::Key("if-exists")) == Some(&Edn::Bool(false)) {
+            self.invalid(
+                option_path("if-exists"),
+                "`fish_add_path` always skips directories that don't exist",
+            );
+        }
+        if !fish_add_path && options.contains_key(&Edn::Key("global")) {
+            self.invalid(
+                option_path("global"),
+                "`:path-vars` are always exported globally",
+            );
+        }
+
+        if self.diagnostics.len() > diagnostics {
+            return None;
+        }
+        Some(PathEntry {
+            append,
+            move_existing,
+            if_exists,
+            global,
+            ..path?
+        })
+    }
+
+    /// Checks that a directory is usable, since an empty one would add the
+    /// current directory to the list
+    fn directory(&mut self, path: Vec<Segment>, directory: String) -> Option<PathEntry> {
+        if directory.is_empty() {
+            self.invalid(path, "directories cannot be empty");
+            return None;
+        }
+        Some(PathEntry {
+            path: directory,
+            ..PathEntry::default()
+        })
+    }
+
+    /// Reads a variable, given as its value or as a map of options
+    fn env_var(&mut self, var_path: Vec<Segment>, name: String, value: &Edn<'_>) -> Option<EnvVar> {
+        let options = match value {
+            Edn::Map(options) => options,
+            _ => {
+                let value = self.env_value(var_path, value)?;
+                return Some(EnvVar {
+                    name,
+                    value,
+                    scope: None,
+                    export: true,
+                    path: false,
+                });
+            }
+        };
+        self.check_keys(&var_path, options, ENV_KEYS, |_| false);
+        let option_path = |key: &str| {
+            let mut option_path = var_path.clone();
+            option_path.push(Segment::Key(key.to_string()));
+            option_path
+        };
+        let diagnostics = self.diagnostics.len();
+
+        let value = match options.get(&Edn::Key("value")) {
+            Some(value) => self.env_value(option_path("value"), value),
+            None => {
+                self.invalid(
+                    var_path.clone(),
+                    "a variable needs a `:value`, or `nil` to erase it",
+                );
+                None
+            }
+        };
+        let scope = match options.get(&Edn::Key("scope")) {
+            None => None,
+            Some(Edn::Key("global")) => Some(VariableScope::Global),
+            Some(Edn::Key("universal")) => Some(VariableScope::Universal),
+            Some(Edn::Key("local")) => Some(VariableScope::Local),
+            Some(Edn::Key(_)) => {
+                self.invalid(
+                    option_path("scope"),
+                    "`:scope` must be `:global`, `:universal` or `:local`",
+                );
+                None
+            }
+            Some(scope) => {
+                self.mismatch(option_path("scope"), "keyword", scope);
+                None
+            }
+        };
+        let export = match options.get(&Edn::Key("export")) {
+            None => true,
+            Some(_) => self.flag(options, &option_path("export")),
+        };
+        let path = self.flag(options, &option_path("path"));
+
+        if self.diagnostics.len() > diagnostics {
+            return None;
+        }
+        Some(EnvVar {
+            name,
+            value: value?,
+            scope,
+            export,
+            path,
+        })
+    }
+
+    /// Reads the value of a variable: a string, a vector of strings for a
+    /// list, or `nil` to erase it, as `Some(None)`
+    fn env_value(
+        &mut self,
+        value_path: Vec<Segment>,
+        value: &Edn<'_>,
+    ) -> Option<Option<Vec<String>>> {
+        match value {
+            Edn::Nil => Some(None),
+            Edn::Str(_) => Some(Some(vec![self.string(value_path, value)?])),
+            Edn::Vector(items) => {
+                let diagnostics = self.diagnostics.len();
+                let mut values = Vec::new();
+                for (index, item) in items.iter().enumerate() {
+                    let mut item_path = value_path.clone();
+                    item_path.push(Segment::Index(index));
+                    if let Some(item) = self.string(item_path, item) {
+                        values.push(item);
+                    }
+                }
+                // A list missing an element would be wrong, not just shorter
+                (self.diagnostics.len() == diagnostics).then_some(Some(values))
+            }
+            _ => {
+                self.mismatch(value_path, "string, vector or nil", value);
+                None
+            }
+        }
+    }
+
+    /// Reads the `:unset` vector of variable names, each erased with
+    /// `set -e`, reporting those that `:env` also sets
+    fn unset<'v>(
+        &mut self,
+        config_map: &BTreeMap<Edn<'_>, Edn<'_>>,
+        env: impl Iterator<Item = &'v EnvVar> + Clone,
+    ) -> Option<Vec<EnvVar>> {
+        let items = match config_map.get(&Edn::Key("unset"))? {
+            Edn::Vector(items) => items,
+            value => {
+                self.mismatch(path(&["unset"]), "vector", value);
+                return None;
+            }
+        };
+        let mut unset: Vec<EnvVar> = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            let item_path = vec![Segment::Key("unset".to_string()), Segment::Index(index)];
+            let name = match item {
+                Edn::Key(name) => name.to_string(),
+                Edn::Str(name) => unescape(name),
+                _ => {
+                    self.mismatch(item_path, "keyword or string", item);
+                    continue;
+                }
+            };
+            if let Err(reason) = names::variable(&name) {
+                self.diagnostics.push(Diagnostic {
+                    path: item_path,
+                    kind: DiagnosticKind::InvalidName { reason },
+                });
+                continue;
+            }
+            if env.clone().any(|var| var.name == name) {
+                self.diagnostics.push(Diagnostic {
+                    path: item_path,
+                    kind: DiagnosticKind::Conflict {
+                        with: format!("env.{name}"),
+                    },
+                });
+                continue;
+            }
+            if unset.iter().all(|var| var.name != name) {
+                unset.push(EnvVar {
+                    name,
+                    value: None,
+                    scope: None,
+                    export: true,
+                    path: false,
+                });
+            }
+        }
+        Some(unset)
+    }
+
+    /// Reads an optional boolean option, `false` if absent
+    fn flag(&mut self, options: &BTreeMap<Edn<'_>, Edn<'_>>, option_path: &[Segment]) -> bool {
+        let Some(Segment::Key(key)) = option_path.last() else {
+            return false;
+        };
+        match options.get(&Edn::Key(key)) {
+            None => false,
+            Some(Edn::Bool(value)) => *value,
+            Some(value) => {
+                self.mismatch(option_path.to_vec(), "boolean", value);
+                false
+            }
+        }
+    }
+
+    /// Reads an option given as one word or a vector of them, as a string
+    /// or integer, checking each with `check`
+    fn words(
+        &mut self,
+        options: &BTreeMap<Edn<'_>, Edn<'_>>,
+        option_path: &[Segment],
+        check: fn(&str) -> Result<(), &'static str>,
+    ) -> Vec<String> {
+        let Some(Segment::Key(key)) = option_path.last() else {
+            return Vec::new();
+        };
+        let items: Vec<(Vec<Segment>, &Edn<'_>)> = match options.get(&Edn::Key(key)) {
+            None => return Vec::new(),
+            Some(Edn::Vector(items)) => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    let mut item_path = option_path.to_vec();
+                    item_path.push(Segment::Index(index));
+                    (item_path, item)
+                })
+                .collect(),
+            Some(item) => vec![(option_path.to_vec(), item)],
+        };
+        let mut words = Vec::new();
+        for (item_path, item) in items {
+            let word = match item {
+                Edn::Str(word) => unescape(word),
+                Edn::Int(number) => number.to_string(),
+                _ => {
+                    self.mismatch(item_path, "string, integer or vector of them", item);
+                    continue;
+                }
+            };
+            match check(&word) {
+                Ok(()) => words.push(word),
+                Err(reason) => self.invalid(item_path, reason),
+            }
+        }
+        words
+    }
+
+    fn invalid(&mut self, path: Vec<Segment>, reason: &'static str) {
+        self.diagnostics.push(Diagnostic {
+            path,
+            kind: DiagnosticKind::InvalidValue { reason },
+        });
+    }
+
+    /// In strict mode, reports every key of `map` that is neither in `known`
+    /// nor accepted by `also_known`
+    ///
+    /// A `:when` that isn't known is reported in any mode, since ignoring it
+    /// would write the value for every machine.
+    fn check_keys(
+        &mut self,
+        parent: &[Segment],
+        map: &BTreeMap<Edn<'_>, Edn<'_>>,
+        known: &[&str],
+        also_known: impl Fn(&str) -> bool,
+    ) {
+        for key in map.keys() {
+            if let Edn::Key(name) = key
+                && (known.contains(name) || also_known(name))
+            {
+                continue;
+            }
+            let mut key_path = parent.to_vec();
+            key_path.push(key_segment(key));
+            if *key == Edn::Key("when") {
+                self.unsupported_when(key_path);
+            } else if self.options.strict {
+                self.diagnostics
+                    .push(Diagnostic::unknown_key(key_path, known));
+            }
+        }
+    }
+
+    /// Reports a `:when` given where conditions aren't supported
+    fn unsupported_when(&mut self, path: Vec<Segment>) {
+        self.diagnostics.push(Diagnostic {
+            path,
+            kind: DiagnosticKind::UnsupportedCondition,
+        });
+    }
+
+    /// Checks a piece of fish code, reporting it if it wouldn't parse
+    fn code(&mut self, path: Vec<Segment>, source: &str) -> bool {
+        match syntax::check(source) {
+            Ok(()) => true,
+            Err(error) => {
+                self.diagnostics.push(Diagnostic {
+                    path,
+                    kind: DiagnosticKind::Syntax(error),
+                });
+                false
+            }
+        }
+    }
+
+    /// Checks the `:fish` commands, which are emitted one per line and may
+    /// build blocks across several entries, so they are parsed together,
+    /// returning the ones to keep
+    ///
+    /// An entry containing an error is left out and reported, with the line
+    /// counted from the start of that entry, and the rest are parsed again.
+    /// When the entry opened a block, the block's body and `end` are left out
+    /// with it, so the body never runs without its guard; if no `end` closes
+    /// it, everything after the entry is.
+    fn commands(&mut self, commands: Vec<String>) -> Vec<String> {
+        let mut entries: Vec<(usize, String)> = commands.into_iter().enumerate().collect();
+        loop {
+            let Err(mut error) = syntax::check(&script(&entries)) else {
+                return entries.into_iter().map(|(_, command)| command).collect();
+            };
+            let (position, first_line) = locate(&entries, error.line);
+            error.line -= first_line - 1;
+            let unclosed = matches!(error.kind, SyntaxErrorKind::UnclosedBlock(_));
+            let (index, command) = entries.remove(position);
+            self.diagnostics.push(Diagnostic {
+                path: vec![Segment::Key("fish".to_string()), Segment::Index(index)],
+                kind: DiagnosticKind::Syntax(error),
+            });
+
+            // Without its opening entry, the `end` of the block no longer
+            // matches anything
+            let rest = &entries[position..];
+            let body_end = match syntax::check(&script(rest)) {
+                Err(error) if error.kind == SyntaxErrorKind::UnexpectedEnd => {
+                    position + locate(rest, error.line).0 + 1
+                }
+                _ if unclosed || syntax::opens_block(&command) => entries.len(),
+                _ => position,
+            };
+            let body: Vec<(usize, String)> = entries.drain(position..body_end).collect();
+            for (index, _) in body {
+                self.invalid(
+                    vec![Segment::Key("fish".to_string()), Segment::Index(index)],
+                    "part of a block whose opening entry has an error",
+                );
+            }
+        }
+    }
+
+    fn string(&mut self, path: Vec<Segment>, value: &Edn<'_>) -> Option<String> {
+        match value {
+            Edn::Str(value) => Some(unescape(value)),
+            _ => {
+                self.mismatch(path, "string", value);
+                None
+            }
+        }
+    }
+
+    /// Looks up a section whose value must be a map
+    fn map_section<'c, 'e>(
+        &mut self,
+        config_map: &'c BTreeMap<Edn<'e>, Edn<'e>>,
+        section: &'e str,
+    ) -> Option<&'c BTreeMap<Edn<'e>, Edn<'e>>> {
+        match config_map.get(&Edn::Key(section))? {
+            Edn::Map(entries) => Some(entries),
+            value => {
+                self.mismatch(path(&[section]), "map", value);
+                None
+            }
+        }
+    }
+
+    /// Collects a section whose value is a map of names to strings
+    fn string_map<T>(
+        &mut self,
+        config_map: &BTreeMap<Edn<'_>, Edn<'_>>,
+        section: &str,
+        check_name: fn(&str) -> Result<(), &'static str>,
+        entry: impl Fn(String, String) -> T,
+    ) -> (Option<Vec<T>>, Vec<(Condition, T)>) {
+        self.named_map(
+            config_map,
+            section,
+            check_name,
+            |reader, path, name, value| {
+                let value = reader.string(path, value)?;
+                Some(entry(name, value))
+            },
+        )
+    }
+
+    /// Collects a section whose value is a map of names to entries read by
+    /// `entry`, skipping names rejected by `check_name` and names already
+    /// given by another key (`:ll` and `"ll"`)
+    ///
+    /// Entries with a `:when` of their own are returned separately, unless
+    /// [`Options::target`] decides them here.
+    fn named_map<T>(
+        &mut self,
+        config_map: &BTreeMap<Edn<'_>, Edn<'_>>,
+        section: &str,
+        check_name: fn(&str) -> Result<(), &'static str>,
+        mut entry: impl FnMut(&mut Self, Vec<Segment>, String, &Edn<'_>) -> Option<T>,
+    ) -> (Option<Vec<T>>, Vec<(Condition, T)>) {
+        let Some(entries) = self.map_section(config_map, section) else {
+            return (None, Vec::new());
+        };
+        let mut items = Vec::new();
+        let mut guarded = Vec::new();
+        let mut conditional = false;
+        let mut seen: Vec<(&str, String)> = Vec::new();
+        for (key, value) in entries {
+            let entry_path = vec![Segment::Key(section.to_string()), key_segment(key)];
+            // A section can't be conditional on its own; `"when"` still names
+            // an entry
+            if *key == Edn::Key("when") {
+                self.unsupported_when(entry_path);
+                continue;
+            }
+            let Some(name) = key_name(key) else {
+                self.mismatch(entry_path, "keyword or string key", key);
+                continue;
+            };
+            if let Err(reason) = check_name(name) {
+                self.diagnostics.push(Diagnostic {
+                    path: entry_path,
+                    kind: DiagnosticKind::InvalidName { reason },
+                });
+                continue;
+            }
+            if let Some((_, first)) = seen.iter().find(|(seen, _)| *seen == name) {
+                self.diagnostics.push(Diagnostic {
+                    path: entry_path,
+                    kind: DiagnosticKind::Conflict {
+                        with: first.clone(),
+                    },
+                });
+                continue;
+            }
+            seen.push((name, key.to_string()));
+            let Some((when, entry_path, value)) = self.unwrap_when(entry_path, value) else {
+                continue;
+            };
+            let Some(item) = entry(self, entry_path, name.to_string(), &value) else {
+                continue;
+            };
+            match (when, &self.options.target) {
+                (None, _) => items.push(item),
+                (Some(when), Some(target)) => {
+                    if target.satisfies(&when) {
+                        items.push(item);
+                    } else {
+                        conditional = true;
+                    }
+                }
+                (Some(when), None) => guarded.push((when, item)),
+            }
+        }
+        // A section whose entries all have conditions is left out rather
+        // than written empty
+        if items.is_empty() && (conditional || !guarded.is_empty()) {
+            return (None, guarded);
+        }
+        (Some(items), guarded)
+    }
+
+    /// Separates the `:when` of an entry given as a map from the rest of
+    /// it, which is the `:value` if that is all there is
+    ///
+    /// Returns `None` if the condition was rejected.
+    fn unwrap_when<'v, 'e>(
+        &mut self,
+        entry_path: Vec<Segment>,
+        value: &'v Edn<'e>,
+    ) -> Option<(Option<Condition>, Vec<Segment>, Cow<'v, Edn<'e>>)> {
+        let Edn::Map(options) = value else {
+            return Some((None, entry_path, Cow::Borrowed(value)));
+        };
+        let Some(when) = options.get(&Edn::Key("when")) else {
+            return Some((None, entry_path, Cow::Borrowed(value)));
+        };
+        let mut when_path = entry_path.clone();
+        when_path.push(Segment::Key("when".to_string()));
+        let when = self.condition(when_path, when)?;
+
+        let mut rest = options.clone();
+        rest.remove(&Edn::Key("when"));
+        if rest.len() == 1
+            && let Some(value) = rest.remove(&Edn::Key("value"))
+        {
+            let mut value_path = entry_path;
+            value_path.push(Segment::Key("value".to_string()));
+            return Some((Some(when), value_path, Cow::Owned(value)));
+        }
+        Some((Some(when), entry_path, Cow::Owned(Edn::Map(rest))))
+    }
+
+    /// Collects a section whose value is a vector of strings
+    fn string_vector(
+        &mut self,
+        config_map: &BTreeMap<Edn<'_>, Edn<'_>>,
+        section: &str,
+    ) -> Option<Vec<String>> {
+        let items = match config_map.get(&Edn::Key(section))? {
+            Edn::Vector(items) => items,
+            value => {
+                self.mismatch(path(&[section]), "vector", value);
+                return None;
+            }
+        };
+        let mut strings = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            let item_path = vec![Segment::Key(section.to_string()), Segment::Index(index)];
+            if let Some(item) = self.string(item_path, item) {
+                strings.push(item);
+            }
+        }
+        Some(strings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clojure_reader::edn;
+
+    #[test]
+    fn test_try_from_reads_sections() {
+        let parsed = edn::read_string(
+            r#"{:fish-greeting nil
+                :aliases {:ll "ls -la" "grep" "grep --color=auto"}
+                :paths ["/usr/local/bin"]
+                :functions {:mkcd "mkdir -p $argv[1]\nand cd $argv[1]"}
+                :prompt {:left [:cwd]}}"#,
+        )
+        .unwrap();
+        let config = FishConfig::try_from(&parsed).unwrap();
+
+        assert_eq!(config.greeting, Some(Greeting::Disabled));
+        assert_eq!(
+            config.aliases,
+            Some(vec![
+                Alias {
+                    name: "ll".to_string(),
+                    command: "ls -la".to_string(),
+                },
+                Alias {
+                    name: "grep".to_string(),
+                    command: "grep --color=auto".to_string(),
+                },
+            ])
+        );
+        assert_eq!(
+            config.paths,
+            Some(vec![PathEntry {
+                path: "/usr/local/bin".to_string(),
+                ..PathEntry::default()
+            }])
+        );
+        assert_eq!(
+            config.functions.unwrap()[0].body,
+            "mkdir -p $argv[1]\nand cd $argv[1]"
+        );
+        assert_eq!(
+            config.prompt,
+            Some(Prompt {
+                left: vec![PromptSegment::new(SegmentKind::Cwd)],
+                right: vec![],
+                separator: " ".to_string(),
+            })
+        );
+        assert_eq!(config.env, None);
+    }
+
+    #[test]
+    fn test_try_from_rejects_non_map() {
+        let result = FishConfig::try_from(&Edn::Vector(vec![]));
+        assert_eq!(
+            result,
+            Err(ConfigError {
+                diagnostics: vec![Diagnostic::type_mismatch(vec![], "map", "vector")],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_reports_rejected_values() {
+        let parsed = edn::read_string(
+            r#"{:aliases {:ok "ls" :bad 42}
+                :env ["EDITOR"]
+                :paths ["/bin" :usr]
+                :fish-greeting 7
+                :snippet/broken true
+                :prompt {:show-git "yes"}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(config.aliases.map(|aliases| aliases.len()), Some(1));
+        assert_eq!(config.env, None);
+        assert_eq!(config.paths.map(|paths| paths.len()), Some(1));
+        assert_eq!(config.greeting, None);
+        assert!(config.snippets.is_empty());
+
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "fish-greeting: expected string or nil, found integer",
+                "snippet/broken: expected string, found boolean",
+                "aliases.bad: expected string, found integer",
+                "env: expected map, found vector",
+                "paths[1]: expected string or map, found keyword",
+                "prompt.show-git: expected boolean, found string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strict_reports_unknown_keys() {
+        let parsed = edn::read_string(
+            r#"{:alias {:ll "ls -la"}
+                :path ["/bin"]
+                :snippet/ok "echo ok"
+                :completely-unrelated 1
+                :preambles {:tilde/al "x"}
+                :prompt {:separator "x" :show-gti true}}"#,
+        )
+        .unwrap();
+
+        let (_, lenient) = FishConfig::parse(&parsed);
+        assert!(lenient.is_empty());
+
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        let (_, diagnostics) = FishConfig::parse_with(&parsed, &options);
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "alias: unknown key, did you mean `aliases`?",
+                "completely-unrelated: unknown key",
+                "path: unknown key, did you mean `paths`?",
+                "preambles.tilde/al: unknown key, did you mean `tilde/all`?",
+                "prompt.show-gti: unknown key, did you mean `show-git`?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_names() {
+        let parsed = edn::read_string(
+            r#"{:aliases {"my alias" "ls" :ok "ls"}
+                :env {"1FOO" "bar" :FOO "bar"}
+                :abbrs {"g s" "git status"}
+                :functions {:if "echo" "-h" "echo"}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(config.aliases.map(|aliases| aliases.len()), Some(1));
+        assert_eq!(config.env.map(|env| env.len()), Some(1));
+        assert_eq!(config.abbrs, Some(vec![]));
+        assert_eq!(config.functions, Some(vec![]));
+
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "abbrs.g s: invalid name, abbreviation names cannot contain whitespace or control characters",
+                "aliases.my alias: invalid name, function names cannot contain `/`, whitespace or control characters",
+                "env.1FOO: invalid name, variable names cannot start with a digit",
+                "functions.if: invalid name, function names cannot be a reserved word",
+                "functions.-h: invalid name, function names cannot start with `-`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_broken_code() {
+        let parsed = edn::read_string(
+            r#"{:snippet/ok "echo ok"
+                :snippet/broken "if true\necho yes"
+                :functions {:good "echo hi" :bad "echo 'unterminated"}
+                :fish ["set -g a 1" "if not type -q starship" "    echo none" "end" "end"]}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(config.snippets.len(), 1);
+        assert_eq!(config.functions.map(|functions| functions.len()), Some(1));
+        assert_eq!(config.fish.map(|fish| fish.len()), Some(4));
+
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "snippet/broken: `if` block is missing `end` at line 1, column 1",
+                "functions.bad: unterminated single quote at line 1, column 6",
+                "fish[4]: `end` without a matching block at line 1, column 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_conflicting_definitions() {
+        let parsed = edn::read_string(
+            r#"{:aliases {:ll "ls -la" "ll" "ls -l" :gs "git status"}
+                :functions {:gs "git status -sb"}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(
+            config.aliases,
+            Some(vec![Alias {
+                name: "ll".to_string(),
+                command: "ls -la".to_string(),
+            }])
+        );
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "aliases.ll: conflicts with `:ll`",
+                "aliases.gs: conflicts with `functions.gs`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_abbr_options() {
+        let parsed = edn::read_string(
+            r#"{:abbrs {:gs "git status"
+                        :gco {:expansion "git checkout %" :position :anywhere :set-cursor true}
+                        :dotdot {:regex "^\\.\\.+$" :function "multicd"}
+                        :both {:expansion "x" :function "y"}
+                        :none {:position :anywhere}
+                        :where {:expansion "x" :position :everywhere}
+                        :cursor {:expansion "no marker" :set-cursor "@"}
+                        :func {:function "a b"}
+                        :number 42}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        let abbrs = config.abbrs.unwrap();
+        assert_eq!(
+            abbrs
+                .iter()
+                .map(|abbr| abbr.name.as_str())
+                .collect::<Vec<_>>(),
+            ["dotdot", "gco", "gs"]
+        );
+        assert_eq!(
+            abbrs[1],
+            Abbr {
+                name: "gco".to_string(),
+                expansion: AbbrExpansion::Text("git checkout %".to_string()),
+                position: AbbrPosition::Anywhere,
+                regex: None,
+                set_cursor: Some("%".to_string()),
+            }
+        );
+        assert_eq!(
+            abbrs[0].expansion,
+            AbbrExpansion::Function("multicd".to_string())
+        );
+        assert_eq!(abbrs[0].regex.as_deref(), Some(r"^\.\.+$"));
+
+        let mut messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        messages.sort();
+        assert_eq!(
+            messages,
+            [
+                "abbrs.both: invalid value, an abbreviation takes either `:expansion` or `:function`, not both",
+                "abbrs.cursor.set-cursor: invalid value, the expansion doesn't contain the cursor marker",
+                "abbrs.func.function: invalid name, function names cannot contain `/`, whitespace or control characters",
+                "abbrs.none: invalid value, an abbreviation needs `:expansion` or `:function`",
+                "abbrs.number: expected string or map, found integer",
+                "abbrs.where.position: invalid value, `:position` must be `:command` or `:anywhere`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_function_options() {
+        let parsed = edn::read_string(
+            r#"{:functions {:greet {:body "echo hi $name" :argument-names "name" :description "Greet"}
+                            :nobody {:description "x"}
+                            :args {:body "echo" :argument-names ["ok" "not-ok"]}
+                            :job {:body "echo" :on-job-exit "%self"}
+                            :shadow {:body "echo" :no-scope-shadowing "yes"}
+                            :broken {:body "if true"}}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(
+            config.functions,
+            Some(vec![FishFunction {
+                name: "greet".to_string(),
+                body: "echo hi $name".to_string(),
+                description: Some("Greet".to_string()),
+                argument_names: vec!["name".to_string()],
+                ..FishFunction::default()
+            }])
+        );
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "functions.args.argument-names[1]: invalid value, variable names may only contain letters, digits and underscores",
+                "functions.broken.body: `if` block is missing `end` at line 1, column 1",
+                "functions.job.on-job-exit: invalid value, jobs are given by PID or as `caller`",
+                "functions.nobody: invalid value, a function needs a `:body`",
+                "functions.shadow.no-scope-shadowing: expected boolean, found string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_completions() {
+        let parsed = edn::read_string(
+            r#"{:completions {:tool [{:short "v" :long "verbose"}
+                                     {:short "-h"}
+                                     {:long "out" :require-parameter "yes"}
+                                     {:description "nothing to complete"}
+                                     {:condition "if true" :long "x"}
+                                     "--help"]
+                              "bin/tool" []}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(
+            config.completions,
+            Some(vec![Completion {
+                command: "tool".to_string(),
+                options: vec![CompletionOption {
+                    short: Some("v".to_string()),
+                    long: Some("verbose".to_string()),
+                    ..CompletionOption::default()
+                }],
+            }])
+        );
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "completions.tool[1].short: invalid value, short options are a single character, without the `-`",
+                "completions.tool[2].require-parameter: expected boolean, found string",
+                "completions.tool[3]: invalid value, a completion needs `:short`, `:long`, `:arguments` or `:no-files`",
+                "completions.tool[4].condition: `if` block is missing `end` at line 1, column 1",
+                "completions.tool[5]: expected map, found string",
+                "completions.bin/tool: invalid name, command names cannot contain `/`, whitespace or control characters",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_env_options() {
+        let parsed = edn::read_string(
+            r#"{:env {:EDITOR "nvim"
+                      :PAGER nil
+                      :LESS ["-R" "-F"]
+                      :fish_color_cwd {:value ["green" "--bold"] :scope :universal :export false}
+                      :XDG_DATA_DIRS {:value ["/usr/local/share"] :path true}
+                      :BAD ["ok" 1]
+                      :WHERE {:value "x" :scope :session}
+                      :NONE {:scope :local}}
+                :unset [:GREP_OPTIONS "EDITOR" :GREP_OPTIONS :1X]}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        let env = config.env.unwrap();
+        let names: Vec<&str> = env.iter().map(|var| var.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "EDITOR",
+                "LESS",
+                "PAGER",
+                "XDG_DATA_DIRS",
+                "fish_color_cwd",
+                "GREP_OPTIONS"
+            ]
+        );
+        assert_eq!(env[0], EnvVar::new("EDITOR", "nvim"));
+        assert_eq!(env[1].value, Some(vec!["-R".to_string(), "-F".to_string()]));
+        assert_eq!(env[2].value, None);
+        assert!(env[3].path);
+        assert_eq!(
+            env[4],
+            EnvVar {
+                name: "fish_color_cwd".to_string(),
+                value: Some(vec!["green".to_string(), "--bold".to_string()]),
+                scope: Some(VariableScope::Universal),
+                export: false,
+                path: false,
+            }
+        );
+        assert_eq!(env[5].value, None);
+
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "env.BAD[1]: expected string, found integer",
+                "env.NONE: invalid value, a variable needs a `:value`, or `nil` to erase it",
+                "env.WHERE.scope: invalid value, `:scope` must be `:global`, `:universal` or `:local`",
+                "unset[1]: conflicts with `env.EDITOR`",
+                "unset[3]: invalid name, variable names cannot start with a digit",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_key_bindings() {
+        let parsed = edn::read_string(
+            r#"{:key-bindings {:mode :vi
+                              :bindings [{:keys "\\cf" :command "forward-char"}
+                                         {:mode :insert :keys "\\cf" :command ["accept-autosuggestion" "repaint"]}
+                                         {:keys "\\cf" :fish "echo"}
+                                         {:keys "\\e." :command "history-token-serch-backward"}
+                                         {:keys "\\cg" :command "forward-char" :fish "echo"}
+                                         {:keys "a b" :fish "if true"}
+                                         {:keys "jk" :command "cancel" :sets-mode "default"}
+                                         {:command "undo"}]}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(
+            config.key_bindings,
+            Some(KeyBindings {
+                mode: Some(KeyMode::Vi),
+                bindings: vec![
+                    KeyBinding {
+                        keys: r"\cf".to_string(),
+                        action: KeyAction::Functions(vec!["forward-char".to_string()]),
+                        mode: None,
+                        sets_mode: None,
+                    },
+                    KeyBinding {
+                        keys: r"\cf".to_string(),
+                        action: KeyAction::Functions(vec![
+                            "accept-autosuggestion".to_string(),
+                            "repaint".to_string(),
+                        ]),
+                        mode: Some("insert".to_string()),
+                        sets_mode: None,
+                    },
+                ],
+            })
+        );
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "key-bindings.bindings[2]: conflicts with `key-bindings.bindings[0]`",
+                "key-bindings.bindings[3].command: unknown bind function, did you mean `history-token-search-backward`?",
+                "key-bindings.bindings[4]: invalid value, a binding takes either `:command` or `:fish`, not both",
+                "key-bindings.bindings[5].keys: invalid value, key sequences are written with escapes such as `\\cf` instead of quotes, whitespace or special characters",
+                "key-bindings.bindings[5].fish: `if` block is missing `end` at line 1, column 1",
+                "key-bindings.bindings[6].sets-mode: expected keyword, found string",
+                "key-bindings.bindings[7]: invalid value, a binding needs `:keys`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_prompt_segments() {
+        let parsed = edn::read_string(
+            r#"{:prompt {:left [:cwd
+                               {:segment :git-dirty :symbol "!" :color ["red" "--bold"] :separator ""}
+                               {:text "> "}
+                               :git-brnach
+                               {:segment :cwd :text "x"}
+                               {:segment :time :symbol "!"}
+                               {:segment :venv :color "purple"}
+                               {:color "red"}
+                               "status"]
+                        :right [:git-branch :time]
+                        :separator " | "
+                        :show-git false
+                        :style :two-lines}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(
+            config.prompt,
+            Some(Prompt {
+                left: vec![
+                    PromptSegment::new(SegmentKind::Cwd),
+                    PromptSegment::new(SegmentKind::Text("> ".to_string())),
+                ],
+                right: vec![PromptSegment::new(SegmentKind::Time)],
+                separator: " | ".to_string(),
+            })
+        );
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "prompt.style: unknown prompt style, did you mean `two-line`?",
+                "prompt.left[3]: unknown prompt segment, did you mean `git-branch`?",
+                "prompt.left[4]: invalid value, a segment takes either `:segment` or `:text`, not both",
+                "prompt.left[5].symbol: invalid value, only `:git-dirty` takes a `:symbol`",
+                "prompt.left[6].color: invalid value, colors are names such as `blue` or `brblack`, or hex RGB such as `5f87af`",
+                "prompt.left[7]: invalid value, a segment needs `:segment` or `:text`",
+                "prompt.left[8]: expected keyword or map, found string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_colors() {
+        let parsed = edn::read_string(
+            r#"{:colors {:theme "test.theme"
+                         :fish_color_command "blue --bold"
+                         :fish_pager_color_progress ["brwhite" "--background=cyan"]
+                         :fish_color_error "bleu"
+                         :fish_color_cwdd "green"
+                         "fish_color_command" "red"}}"#,
+        )
+        .unwrap();
+        let theme = "# name: 'Test'\n\
+                     fish_color_normal normal\n\
+                     fish_color_command 5f87af --bold\n\
+                     fish_color_comand blue\n";
+        let options = Options {
+            themes: BTreeMap::from([("test.theme".to_string(), Ok(theme.to_string()))]),
+            ..Options::default()
+        };
+        let (config, diagnostics) = FishConfig::parse_with(&parsed, &options);
+
+        let color = |variable: &str, spec: &[&str]| Color {
+            variable: variable.to_string(),
+            spec: spec.iter().map(|word| word.to_string()).collect(),
+        };
+        assert_eq!(
+            config.colors,
+            Some(vec![
+                color("fish_color_normal", &["normal"]),
+                color("fish_color_command", &["blue", "--bold"]),
+                color(
+                    "fish_pager_color_progress",
+                    &["brwhite", "--background=cyan"]
+                ),
+            ])
+        );
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "colors.theme.fish_color_comand: unknown key, did you mean `fish_color_command`?",
+                "colors.fish_color_cwdd: unknown key, did you mean `fish_color_cwd`?",
+                "colors.fish_color_error: invalid value, colors are names such as `blue` or `brblack`, or hex RGB such as `5f87af`",
+                "colors.fish_color_command: conflicts with `:fish_color_command`",
+            ]
+        );
+
+        let parsed = edn::read_string(r#"{:colors {:theme "missing.theme"}}"#).unwrap();
+        let (_, diagnostics) = FishConfig::parse(&parsed);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "colors.theme: invalid value, theme files are only read by the `tilde-fish` command; pass it in `Options::themes`"
+        );
+        let options = Options {
+            themes: BTreeMap::from([(
+                "missing.theme".to_string(),
+                Err("missing.theme: No such file or directory".to_string()),
+            )]),
+            ..Options::default()
+        };
+        let (_, diagnostics) = FishConfig::parse_with(&parsed, &options);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "colors.theme: couldn't be read, missing.theme: No such file or directory"
+        );
+    }
+
+    #[test]
+    fn test_parse_path_entries() {
+        let parsed = edn::read_string(
+            r#"{:paths ["~/bin"
+                        {:path "/opt/homebrew/bin" :global true :move true}
+                        "~/bin/"
+                        {:append true}
+                        {:path "/opt/tools" :if-exists false}]
+                :path-vars {:MANPATH [{:path "~/.local/share/man" :append true :if-exists true}
+                                      {:path "/opt/man" :global true}
+                                      ""]
+                            :PATH ["/bin"]
+                            :CDPATH "~/src"}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(
+            config.paths,
+            Some(vec![
+                PathEntry {
+                    path: "~/bin".to_string(),
+                    ..PathEntry::default()
+                },
+                PathEntry {
+                    path: "/opt/homebrew/bin".to_string(),
+                    move_existing: true,
+                    global: true,
+                    ..PathEntry::default()
+                },
+            ])
+        );
+        assert_eq!(
+            config.path_vars,
+            Some(vec![PathVar {
+                name: "MANPATH".to_string(),
+                entries: vec![PathEntry {
+                    path: "~/.local/share/man".to_string(),
+                    append: true,
+                    if_exists: true,
+                    ..PathEntry::default()
+                }],
+            }])
+        );
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "paths[2]: conflicts with `paths[0]`",
+                "paths[3]: invalid value, a path entry needs a `:path`",
+                "paths[4].if-exists: invalid value, `fish_add_path` always skips directories that don't exist",
+                "path-vars.CDPATH: expected vector, found string",
+                "path-vars.MANPATH[1].global: invalid value, `:path-vars` are always exported globally",
+                "path-vars.MANPATH[2]: invalid value, directories cannot be empty",
+                "path-vars.PATH: invalid name, `PATH` is managed with `:paths`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_conditions() {
+        let parsed = edn::read_string(
+            r#"{:env {:EDITOR "nvim"
+                      :BROWSER {:value "open" :when {:os :macos}}
+                      :PAGER {:value "less" :when {:os :windows}}}
+                :abbrs {:o {:expansion "open" :when {:os :macos}}}
+                :conditional [{:when {:hostname ["a" "b"] :env {:CI true :TERM "dumb" :SSH nil}}
+                               :aliases {:ll 42}}
+                              {:fish ["true"]}]}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(config.abbrs, None);
+        assert_eq!(config.env.map(|env| env.len()), Some(1));
+        let macos = Condition {
+            os: vec![Os::MacOs],
+            ..Condition::default()
+        };
+        assert_eq!(config.conditionals.len(), 2);
+        assert_eq!(config.conditionals[0].when, macos);
+        assert_eq!(
+            config.conditionals[0].config.abbrs.as_ref().map(Vec::len),
+            Some(1)
+        );
+        assert_eq!(
+            config.conditionals[0].config.env.as_ref().map(Vec::len),
+            Some(1)
+        );
+        assert_eq!(
+            config.conditionals[1].when,
+            Condition {
+                hostname: vec!["a".to_string(), "b".to_string()],
+                env: vec![
+                    EnvCondition {
+                        name: "CI".to_string(),
+                        test: EnvTest::Set,
+                    },
+                    EnvCondition {
+                        name: "SSH".to_string(),
+                        test: EnvTest::Unset,
+                    },
+                    EnvCondition {
+                        name: "TERM".to_string(),
+                        test: EnvTest::Equals("dumb".to_string()),
+                    },
+                ],
+                ..Condition::default()
+            }
+        );
+
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "env.PAGER.when.os: invalid value, `:os` must be `:linux`, `:macos`, `:freebsd`, `:openbsd` or `:netbsd`",
+                "conditional[0].aliases.ll: expected string, found integer",
+                "conditional[1]: invalid value, a conditional block needs `:when`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_conditions() {
+        let parsed = edn::read_string(
+            r#"{:env {:BROWSER {:value "open" :when {:os :macos}}
+                      :OPENER {:value "xdg-open" :when {:os :linux}}}
+                :conditional [{:when {:hostname-regex "build-\\d+"} :paths ["/opt/ci/bin"]}
+                              {:when {:hostname "laptop"} :paths ["~/bin"]}]}"#,
+        )
+        .unwrap();
+        let options = Options {
+            target: Some(Target {
+                os: Some(Os::Linux),
+                hostname: "build-12".to_string(),
+                env: BTreeMap::new(),
+            }),
+            ..Options::default()
+        };
+        let (config, diagnostics) = FishConfig::parse_with(&parsed, &options);
+
+        assert!(diagnostics.is_empty());
+        assert!(config.conditionals.is_empty());
+        assert_eq!(config.env, Some(vec![EnvVar::new("OPENER", "xdg-open")]));
+        assert_eq!(
+            config.paths,
+            Some(vec![PathEntry {
+                path: "/opt/ci/bin".to_string(),
+                ..PathEntry::default()
+            }])
+        );
+    }
+
+    #[test]
+    fn test_parse_path_conditions() {
+        let parsed = edn::read_string(
+            r#"{:paths ["/a" {:path "/b" :when {:os :linux}} {:value "/c" :when {:os :macos}}]}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+        let entry = |path: &str| PathEntry {
+            path: path.to_string(),
+            ..PathEntry::default()
+        };
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.paths.map(|paths| paths.len()), Some(1));
+        let paths: Vec<_> = config
+            .conditionals
+            .iter()
+            .map(|conditional| (&conditional.when.os, conditional.config.paths.as_ref()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                (&vec![Os::Linux], Some(&vec![entry("/b")])),
+                (&vec![Os::MacOs], Some(&vec![entry("/c")])),
+            ]
+        );
+
+        let options = Options {
+            target: Some(Target {
+                os: Some(Os::MacOs),
+                hostname: "laptop".to_string(),
+                env: BTreeMap::new(),
+            }),
+            ..Options::default()
+        };
+        let (config, _) = FishConfig::parse_with(&parsed, &options);
+        assert_eq!(config.paths, Some(vec![entry("/a"), entry("/c")]));
+        assert!(config.conditionals.is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_conditions_are_left_out() {
+        let parsed = edn::read_string(
+            r#"{:when {:os :linux}
+                :env {:when {:os :linux} "when" "ok"}
+                :path-vars {:MANPATH [{:path "/m" :when {:os :linux}}]}
+                :fish [{:value "exec startx" :when {:os :linux}}]
+                :snippet/x {:value "echo x" :when {:os :linux}}
+                :colors {:fish_color_command {:value "blue" :when {:os :linux}}}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        let guarded = &config.conditionals[0].config;
+        assert_eq!(guarded.env, Some(vec![EnvVar::new("when", "ok")]));
+        assert_eq!(guarded.fish, Some(vec![]));
+        assert!(guarded.snippets.is_empty());
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        let unsupported = "`:when` isn't supported here; use a `:conditional` block";
+        assert_eq!(
+            messages,
+            [
+                format!("snippet/x.when: {unsupported}"),
+                format!("env.when: {unsupported}"),
+                format!("path-vars.MANPATH[0].when: {unsupported}"),
+                format!("fish[0].when: {unsupported}"),
+                format!("colors.fish_color_command.when: {unsupported}"),
+            ]
+        );
+        assert!(diagnostics.iter().all(Diagnostic::is_fatal));
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        let parsed = edn::read_string(
+            r#"{:scope {:env :login :snippet/tools :interactive :abbr :always :fish :sometimes}}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(config.scope("env"), Scope::Login);
+        assert_eq!(config.scope("snippet/tools"), Scope::Interactive);
+        assert_eq!(config.scope("abbrs"), Scope::Interactive);
+        assert_eq!(config.scope("fish"), Scope::Always);
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "scope.abbr: unknown key, did you mean `abbrs`?",
+                "scope.fish: invalid value, `:scope` must be `:interactive`, `:login` or `:always`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_autoload() {
+        let parsed = edn::read_string(r#"{:autoload [:functions :aliases "env"]}"#).unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert!(config.autoload_functions);
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "autoload[1]: invalid value, only `:functions` and `:completions` can be autoloaded",
+                "autoload[2]: expected keyword, found string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_profiles() {
+        let parsed = edn::read_string(
+            r#"{:env {:EDITOR "vim"}
+                :profiles {:work {:env {:EDITOR "code --wait"} :include ["work.edn"]}
+                           :home []
+                           42 {}}
+                :conditional [{:when {:os :linux} :profiles {}}]}"#,
+        )
+        .unwrap();
+        let options = Options {
+            profile: Some("work".to_string()),
+            ..Options::default()
+        };
+        let (config, diagnostics) = FishConfig::parse_with(&parsed, &options);
+
+        assert_eq!(
+            config.env.unwrap()[0].value,
+            Some(vec!["code --wait".to_string()])
+        );
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "profiles.home: expected map, found vector",
+                "profiles.work.include: invalid value, only allowed at the top level",
+                "profiles.42: expected keyword or string key, found integer",
+                "conditional[0].profiles: invalid value, only allowed at the top level",
+            ]
+        );
+        assert_eq!(
+            FishConfig::parse(&parsed).0.env.unwrap()[0].value,
+            Some(vec!["vim".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_fish_commands_may_span_entries() {
+        let parsed = edn::read_string(
+            r#"{:fish ["if not type -q starship" "    function fish_prompt" "        echo '> '" "    end" "end"]}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.fish.map(|fish| fish.len()), Some(5));
+    }
+
+    #[test]
+    fn test_broken_fish_commands_are_left_out() {
+        let parsed = edn::read_string(
+            r#"{:fish ["set -g a 1" "echo 'oops" "set -g b 2" "if true" "    echo x" "end" "echo (date))"]}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(
+            config.fish.unwrap(),
+            ["set -g a 1", "set -g b 2", "if true", "    echo x", "end"]
+        );
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "fish[1]: unterminated single quote at line 1, column 6",
+                "fish[6]: unexpected `)` at line 1, column 12",
+            ]
+        );
+
+        let parsed = edn::read_string(r#"{:fish ["end"]}"#).unwrap();
+        assert_eq!(FishConfig::parse(&parsed).0.fish, None);
+    }
+
+    #[test]
+    fn test_broken_fish_blocks_leave_out_their_body() {
+        let parsed =
+            edn::read_string(r#"{:fish ["if test (tty) = /dev/tty1" "    exec startx"]}"#).unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(config.fish, None);
+        let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "fish[0]: `if` block is missing `end` at line 1, column 1",
+                "fish[1]: invalid value, part of a block whose opening entry has an error",
+            ]
+        );
+
+        let parsed = edn::read_string(
+            r#"{:fish ["set -g a 1" "if test tty)" "    exec startx" "end" "set -g b 2"]}"#,
+        )
+        .unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+
+        assert_eq!(config.fish.unwrap(), ["set -g a 1", "set -g b 2"]);
+        let locations: Vec<String> = diagnostics.iter().map(Diagnostic::location).collect();
+        assert_eq!(locations, ["fish[1]", "fish[2]", "fish[3]"]);
+    }
+
+    #[test]
+    fn test_snippets_in_key_order() {
+        let parsed =
+            edn::read_string(r#"{:snippet/b "echo b" :snippet/a "echo a" :other "x"}"#).unwrap();
+        let config = FishConfig::try_from(&parsed).unwrap();
+
+        let names: Vec<_> = config.snippets.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+}
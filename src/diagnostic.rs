@@ -0,0 +1,244 @@
+//! Diagnostics for configuration entries that could not be used
+
+use crate::syntax::SyntaxError;
+use std::fmt;
+
+/// One step in the path from the top-level config map to a value
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Segment {
+    /// A map entry, named by its keyword or string key
+    Key(String),
+    /// A vector element
+    Index(usize),
+}
+
+/// A problem found with a single configuration value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Location of the offending value; empty for the top-level value
+    pub path: Vec<Segment>,
+    pub kind: DiagnosticKind,
+}
+
+/// What was wrong with a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The value had a different EDN type than the section expects
+    TypeMismatch {
+        expected: &'static str,
+        actual: &'static str,
+    },
+    /// A key that isn't part of the configuration format (strict mode only),
+    /// or a `:colors` variable fish doesn't read
+    UnknownKey { suggestion: Option<String> },
+    /// An alias, abbreviation, function or variable name fish would reject
+    InvalidName { reason: &'static str },
+    /// A snippet, function body or `:fish` command that fish couldn't parse
+    Syntax(SyntaxError),
+    /// A value, or combination of values, that the section doesn't allow
+    InvalidValue { reason: &'static str },
+    /// A `:when` where conditions aren't supported; leaving it out would
+    /// apply the value on every machine
+    UnsupportedCondition,
+    /// A file the value names that couldn't be read, with the error
+    Unreadable { error: String },
+    /// A second definition of a name already defined at another location
+    Conflict { with: String },
+    /// A value naming something fish doesn't have, such as a bind function
+    Unknown {
+        what: &'static str,
+        suggestion: Option<String>,
+    },
+}
+
+impl Diagnostic {
+    pub fn type_mismatch(path: Vec<Segment>, expected: &'static str, actual: &'static str) -> Self {
+        Diagnostic {
+            path,
+            kind: DiagnosticKind::TypeMismatch { expected, actual },
+        }
+    }
+
+    /// Reports the key at the end of `path`, suggesting the closest of `known`
+    pub fn unknown_key(path: Vec<Segment>, known: &[&str]) -> Self {
+        let suggestion = match path.last() {
+            Some(Segment::Key(key)) => suggest(key, known).map(str::to_string),
+            _ => None,
+        };
+        Diagnostic {
+            path,
+            kind: DiagnosticKind::UnknownKey { suggestion },
+        }
+    }
+
+    /// The top-level section the value belongs to, if any
+    pub fn section(&self) -> Option<&str> {
+        match self.path.first() {
+            Some(Segment::Key(section)) => Some(section),
+            _ => None,
+        }
+    }
+
+    /// Whether generating without the value could write a configuration
+    /// meant for another machine, which fails even without `--strict`: a
+    /// `:when` that would be ignored, or a selected profile that isn't there
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self.kind,
+            DiagnosticKind::UnsupportedCondition
+                | DiagnosticKind::Unknown {
+                    what: "profile",
+                    ..
+                }
+        )
+    }
+
+    /// The path rendered as `section.key[index]`, or `<root>` for the top level
+    pub fn location(&self) -> String {
+        location(&self.path)
+    }
+}
+
+/// Renders a path as `section.key[index]`, or `<root>` for the top level
+pub(crate) fn location(path: &[Segment]) -> String {
+    if path.is_empty() {
+        return "<root>".to_string();
+    }
+    let mut location = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) => {
+                if !location.is_empty() {
+                    location.push('.');
+                }
+                location.push_str(key);
+            }
+            Segment::Index(index) => location.push_str(&format!("[{index}]")),
+        }
+    }
+    location
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticKind::TypeMismatch { expected, actual } => {
+                write!(f, "expected {expected}, found {actual}")
+            }
+            DiagnosticKind::UnknownKey {
+                suggestion: Some(suggestion),
+            } => write!(f, "unknown key, did you mean `{suggestion}`?"),
+            DiagnosticKind::UnknownKey { suggestion: None } => write!(f, "unknown key"),
+            DiagnosticKind::InvalidName { reason } => write!(f, "invalid name, {reason}"),
+            DiagnosticKind::InvalidValue { reason } => write!(f, "invalid value, {reason}"),
+            DiagnosticKind::Syntax(error) => write!(f, "{error}"),
+            DiagnosticKind::UnsupportedCondition => {
+                write!(
+                    f,
+                    "`:when` isn't supported here; use a `:conditional` block"
+                )
+            }
+            DiagnosticKind::Unreadable { error } => write!(f, "couldn't be read, {error}"),
+            DiagnosticKind::Conflict { with } => write!(f, "conflicts with `{with}`"),
+            DiagnosticKind::Unknown {
+                what,
+                suggestion: Some(suggestion),
+            } => write!(f, "unknown {what}, did you mean `{suggestion}`?"),
+            DiagnosticKind::Unknown {
+                what,
+                suggestion: None,
+            } => write!(f, "unknown {what}"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location(), self.kind)
+    }
+}
+
+/// Returns the candidate closest to `name` by edit distance, if any is close
+/// enough to plausibly be what was meant
+pub(crate) fn suggest<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let threshold = (name.chars().count() / 3).max(2);
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings, counted in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Error returned when a configuration contains values that had to be rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration")?;
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n  {diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_formatting() {
+        let diagnostic = Diagnostic::type_mismatch(
+            vec![Segment::Key("paths".to_string()), Segment::Index(2)],
+            "string",
+            "integer",
+        );
+        assert_eq!(diagnostic.section(), Some("paths"));
+        assert_eq!(
+            diagnostic.to_string(),
+            "paths[2]: expected string, found integer"
+        );
+
+        let root = Diagnostic::type_mismatch(vec![], "map", "vector");
+        assert_eq!(root.section(), None);
+        assert_eq!(root.to_string(), "<root>: expected map, found vector");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("path", "paths"), 1);
+        assert_eq!(edit_distance("alias", "aliases"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let known = ["aliases", "abbrs", "paths", "prompt"];
+        assert_eq!(suggest("alias", &known), Some("aliases"));
+        assert_eq!(suggest("path", &known), Some("paths"));
+        assert_eq!(suggest("abbr", &known), Some("abbrs"));
+        assert_eq!(suggest("greeting", &known), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Unified diffs between two versions of a generated file
+
+/// Lines of unchanged text shown around each change
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Returns the changes from `old` to `new` in unified diff format, or an
+/// empty string if they are the same
+pub fn unified(old_name: &str, new_name: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let ops = edit_script(&old, &new);
+    if ops.iter().all(|op| *op == Op::Equal) {
+        return String::new();
+    }
+
+    let mut output = format!("--- {old_name}\n+++ {new_name}\n");
+    // Line positions in `old` and `new` before each op
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_line, mut new_line) = (0, 0);
+    for op in &ops {
+        positions.push((old_line, new_line));
+        match op {
+            Op::Equal => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Op::Delete => old_line += 1,
+            Op::Insert => new_line += 1,
+        }
+    }
+    positions.push((old_line, new_line));
+
+    let mut start = 0;
+    while let Some(first_change) = ops[start..].iter().position(|op| *op != Op::Equal) {
+        let hunk_start = (start + first_change).saturating_sub(CONTEXT);
+        // Extend the hunk while the next change is close enough to share context
+        let mut hunk_end = start + first_change;
+        let mut equal_run = 0;
+        for (index, op) in ops.iter().enumerate().skip(hunk_end) {
+            if *op == Op::Equal {
+                equal_run += 1;
+                if equal_run > 2 * CONTEXT {
+                    break;
+                }
+            } else {
+                equal_run = 0;
+                hunk_end = index + 1;
+            }
+        }
+        let hunk_end = (hunk_end + CONTEXT).min(ops.len());
+
+        let (old_start, new_start) = positions[hunk_start];
+        let (old_end, new_end) = positions[hunk_end];
+        output.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        ));
+        for (index, op) in ops.iter().enumerate().take(hunk_end).skip(hunk_start) {
+            let (old_line, new_line) = positions[index];
+            match op {
+                Op::Equal => output.push_str(&format!(" {}\n", old[old_line])),
+                Op::Delete => output.push_str(&format!("-{}\n", old[old_line])),
+                Op::Insert => output.push_str(&format!("+{}\n", new[new_line])),
+            }
+        }
+        start = hunk_end;
+    }
+    output
+}
+
+/// Formats a hunk range, which names the line before it when it is empty
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+/// The shortest sequence of line deletions and insertions turning `old` into
+/// `new`, found from their longest common subsequence
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Op> {
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push(Op::Equal);
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            // Deletions first, so a changed line reads as `-old` then `+new`
+            ops.push(Op::Delete);
+            i += 1;
+        } else {
+            ops.push(Op::Insert);
+            j += 1;
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical() {
+        assert_eq!(unified("a", "b", "x\ny\n", "x\ny\n"), "");
+    }
+
+    #[test]
+    fn test_single_change() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n";
+        assert_eq!(
+            unified("old", "new", old, new),
+            "--- old\n+++ new\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn test_separate_hunks() {
+        let old: String = (1..=20).map(|n| format!("{n}\n")).collect();
+        let new: String = (1..=20)
+            .filter(|n| *n != 19)
+            .map(|n| {
+                if n == 2 {
+                    "two\n".to_string()
+                } else {
+                    format!("{n}\n")
+                }
+            })
+            .collect();
+        let diff = unified("old", "new", &old, &new);
+        assert!(diff.contains("@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n"));
+        assert!(diff.contains("@@ -16,5 +16,4 @@\n 16\n 17\n 18\n-19\n 20\n"));
+    }
+
+    #[test]
+    fn test_empty_sides() {
+        assert_eq!(
+            unified("a", "b", "", "x\n"),
+            "--- a\n+++ b\n@@ -0,0 +1 @@\n+x\n"
+        );
+        assert_eq!(
+            unified("a", "b", "x\n", ""),
+            "--- a\n+++ b\n@@ -1 +0,0 @@\n-x\n"
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Descriptions of the configuration sections, for `tilde-fish explain`
+
+/// A top-level key with a one-line summary and a longer description
+pub struct Topic {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub details: &'static str,
+}
+
+/// Every section, in the order they are written to `config.fish`
+pub const TOPICS: &[Topic] = &[
+    Topic {
+        name: "preambles",
+        summary: "Header text written at the top of the file",
+        details: r##"A map whose `:tilde/all` string is written first, as is. tilde sets
+it to a "generated file" notice.
+
+    :preambles {:tilde/all "# Generated by tilde; DO NOT MODIFY\n"}"##,
+    },
+    Topic {
+        name: "fish-greeting",
+        summary: "The message shown when an interactive shell starts",
+        details: r##"A string to show, or nil (or false) to disable the greeting.
+
+    :fish-greeting "Welcome back"    ; set fish_greeting 'Welcome back'
+    :fish-greeting nil               ; set fish_greeting"##,
+    },
+    Topic {
+        name: "snippet/<name>",
+        summary: "A block of fish code written under a `# <name>` comment",
+        details: r##"Any number of `:snippet/<name>` keys, written in key order after the
+greeting. The code must parse as fish.
+
+    :snippet/git-helpers "function gst\n    git status --short\nend""##,
+    },
+    Topic {
+        name: "abbrs",
+        summary: "Abbreviations, expanded as they are typed",
+        details: r##"A map of abbreviation names to expansions. Names cannot contain
+whitespace.
+
+    :abbrs {:gs "git status"}    ; abbr -a -- gs 'git status'
+
+An expansion may also be a map with `:expansion` or `:function`, and
+optionally `:position` (`:command` or `:anywhere`), `:regex` and
+`:set-cursor` (`true` for `%`, or a marker string).
+
+    :abbrs {:gco {:expansion "git checkout %" :position :anywhere :set-cursor true}
+            :dotdot {:regex "^\\.\\.+$" :function "multicd"}}"##,
+    },
+    Topic {
+        name: "aliases",
+        summary: "Commands defined with `alias`",
+        details: r##"A map of alias names to commands. Names follow the function name
+rules, and an alias cannot share its name with a function.
+
+    :aliases {:ll "ls -la"}    ; alias ll 'ls -la'"##,
+    },
+    Topic {
+        name: "env",
+        summary: "Exported environment variables",
+        details: r##"A map of variable names to values, set with `set -gx`. Names may only
+contain letters, digits and underscores. A vector value sets a list, and
+`nil` erases the variable. A value may also be a map of `:value` and the
+options `:scope` (`:global`, `:universal` or `:local`), `:export` (`true`
+unless given) and `:path` (join the list with colons when exported).
+
+    :env {:EDITOR "nvim"}    ; set -gx EDITOR 'nvim'
+    :env {:PAGER nil}        ; set -e PAGER
+    :env {:XDG_DATA_DIRS {:value ["/usr/local/share" "/usr/share"] :path true}}
+        ; set -gx --path XDG_DATA_DIRS '/usr/local/share' '/usr/share'"##,
+    },
+    Topic {
+        name: "unset",
+        summary: "Variables to erase",
+        details: r##"A vector of variable names, each erased with `set -e`. A name can't also
+be set in `:env`.
+
+    :unset [:GREP_OPTIONS]    ; set -e GREP_OPTIONS"##,
+    },
+    Topic {
+        name: "paths",
+        summary: "Directories added to PATH",
+        details: r##"A vector of directories, each added with `fish_add_path`. A leading
+`~` is expanded by fish. An entry may be a map of `:path` and the options
+`:append` (add after the existing directories), `:move` (move the directory
+if it is already in PATH) and `:global` (set PATH for the session instead of
+the universal `fish_user_paths`). Directories that don't exist are skipped,
+and a directory listed twice is reported.
+
+    :paths ["~/.local/bin"]    ; fish_add_path ~/.local/bin
+    :paths [{:path "/opt/homebrew/bin" :global true :move true}]
+                               ; fish_add_path --global --move /opt/homebrew/bin"##,
+    },
+    Topic {
+        name: "path-vars",
+        summary: "Directory lists such as MANPATH and CDPATH",
+        details: r##"A map of variable names to vectors of directories, for colon-separated
+lists other than PATH, such as MANPATH, CDPATH, INFOPATH and
+LD_LIBRARY_PATH. Each directory is added with `set -gx --prepend` unless it
+is already listed. Entries take `:append`, `:move` and `:if-exists` (only
+add the directory if it exists) like `:paths`. MANPATH and INFOPATH start
+from an empty element when unset, so the system directories stay
+searched, and CDPATH starts from `.`.
+
+    :path-vars {:MANPATH ["~/.local/share/man"]}
+        ; set -q MANPATH; or set -gx MANPATH ''
+        ; contains -- ~/.local/share/man $MANPATH; or set -gx --prepend MANPATH ~/.local/share/man"##,
+    },
+    Topic {
+        name: "functions",
+        summary: "Function definitions",
+        details: r##"A map of function names to bodies. The body is indented inside a
+`function <name>` ... `end` block and must parse as fish.
+
+    :functions {:mkcd "mkdir -p $argv[1]\nand cd $argv[1]"}
+
+A function may also be a map with `:body` and the options `:description`,
+`:argument-names`, `:wraps`, `:on-event`, `:on-variable`, `:on-signal`,
+`:on-job-exit`, `:inherit-variable` and `:no-scope-shadowing`.
+
+    :functions {:on-exit {:body "echo bye" :on-event "fish_exit"}}"##,
+    },
+    Topic {
+        name: "completions",
+        summary: "Tab completions for commands",
+        details: r##"A map of command names to vectors of option specs, each written as a
+`complete -c <command>` line. A spec may have `:short` (one character),
+`:long` (without `--`), `:description`, `:arguments` (a fish expression,
+or a vector of candidates), `:condition` (a command that must succeed),
+and the booleans `:no-files` and `:require-parameter`.
+
+    :completions {:deploy [{:no-files true}
+                           {:short "h" :long "help" :description "Show help"}
+                           {:long "env" :require-parameter true :arguments ["staging" "prod"]}]}"##,
+    },
+    Topic {
+        name: "conditional",
+        summary: "Sections that only apply on some machines",
+        details: r##"A vector of blocks, each a map of `:when` and any sections, written
+inside an `if` block that tests the condition when fish starts.
+
+    :conditional [{:when {:os :linux :hostname-regex "build-.*"}
+                   :env {:JOBS "32"}}]
+
+`:when` may have `:os` (`:linux`, `:macos`, `:freebsd`, `:openbsd` or
+`:netbsd`, or a vector of them), `:hostname` (a name or vector of names),
+`:hostname-regex` (matching the whole name) and `:env` (a map of variables
+to a value they must equal, `true` for set or `nil` for unset). Every part
+that is given must hold.
+
+An entry of `:abbrs`, `:aliases`, `:env`, `:paths`, `:path-vars`,
+`:functions` or `:completions` may have its own `:when`; a plain value is
+then written as `:value`. A `:when` at the top of a file makes the whole
+file a block, except `:preambles`, `:autoload`, `:scope`, `:include` and
+`:profiles`. A `:when` anywhere else is an error, even without `--strict`.
+
+    :env {:BROWSER {:value "open" :when {:os :macos}}}
+
+`gen --resolve` decides the conditions for the current machine instead,
+and `--os` and `--hostname` for another one."##,
+    },
+    Topic {
+        name: "fish",
+        summary: "Raw fish commands",
+        details: r##"A vector of lines written as they are. A block may span several
+entries, but together they must parse as fish; an entry with an error
+is left out and reported. When that entry opens a block, the block's
+body is left out with it.
+
+    :fish ["if status is-interactive" "    starship init fish | source" "end"]"##,
+    },
+    Topic {
+        name: "colors",
+        summary: "Syntax highlighting and pager colors",
+        details: r##"A map of fish color variables, such as `fish_color_command` or
+`fish_pager_color_prefix`, to color specs, each set with `set -g`. A spec
+is a string or a vector of words: named colors (`blue`, `brblack`), hex RGB
+(`5f87af`) and the `set_color` options `--bold`, `--dim`, `--italics`,
+`--reverse`, `--underline` and `--background=<color>`. Unknown variables
+and colors are reported.
+
+`:theme` loads a `.theme` file, as saved by `fish_config theme save`, first;
+variables given in the map override it. A relative path is read from the
+directory of the file naming it, as with `:include`.
+
+    :colors {:theme "~/.config/fish/themes/dracula.theme"
+             :fish_color_command "5f87af --bold"}
+        ; set -g fish_color_command 5f87af --bold"##,
+    },
+    Topic {
+        name: "prompt",
+        summary: "The prompt functions, built from segments",
+        details: r##"A map with `:left` and `:right` vectors of segments, written as the
+`fish_prompt` and `fish_right_prompt` functions. Segments are `:cwd`,
+`:git-branch`, `:git-dirty`, `:status`, `:duration`, `:jobs`, `:venv`,
+`:hostname` and `:time`, or `{:text "..."}`. A segment with nothing to
+show is left out.
+
+A segment may be a map of `:segment` and the options `:color` (a
+`set_color` spec), `:prefix`, `:suffix`, `:separator` (written after it
+instead of the prompt's `:separator`, `" "` by default) and, for
+`:git-dirty`, `:symbol`. `:show-git false` leaves out the git segments.
+
+    :prompt {:left [{:segment :cwd :color "blue"} :git-branch {:text "❯ "}]
+             :right [:duration]}
+
+`:style` starts from a bundled prompt, `:minimal`, `:robbyrussell`,
+`:two-line` or `:powerline`, whose `:left`, `:right` and `:separator` are
+replaced by any given alongside it.
+
+    :prompt {:style :robbyrussell}"##,
+    },
+    Topic {
+        name: "key-bindings",
+        summary: "Key binding mode and bind commands",
+        details: r##"A map with an optional `:mode` (`:emacs`, `:vi` or `:hybrid`, vi with the
+emacs bindings in every mode), set as `fish_key_bindings`, and a
+`:bindings` vector written as `bind` commands in `fish_user_key_bindings`.
+Each binding has `:keys`, written with escapes such as `\cf`, and either
+`:command`, a bind function such as `forward-char` or a vector of them,
+or `:fish`, a command line to run. `:mode` and `:sets-mode` give the bind
+mode the binding applies in and the one it switches to.
+
+    :key-bindings {:mode :vi
+                   :bindings [{:mode :insert :keys "\\cf" :command "accept-autosuggestion"}]}
+        ; set -g fish_key_bindings fish_vi_key_bindings
+        ; function fish_user_key_bindings
+        ;     bind -M insert \cf accept-autosuggestion
+        ; end"##,
+    },
+    Topic {
+        name: "autoload",
+        summary: "Sections written to their own files instead of config.fish",
+        details: r##"A vector of section keywords. With `:functions`, each function is
+written to `fish/functions/<name>.fish`, where fish loads it the first
+time it is called, instead of being defined at startup. Functions with
+`:on-event`, `:on-variable`, `:on-signal` or `:on-job-exit` stay in
+config.fish, since fish only runs handlers it has defined. With
+`:completions`, each command's completions are written to
+`fish/completions/<command>.fish`, loaded the first time it is completed.
+
+    :autoload [:functions :completions]"##,
+    },
+    Topic {
+        name: "scope",
+        summary: "Which shells each section is written for",
+        details: r##"A map of section keywords to `:interactive`, `:login` or `:always`.
+Interactive sections are wrapped in `if status is-interactive`, login
+ones in `if status is-login`, so shells running scripts skip them.
+
+The greeting, abbreviations, colors, prompt and key bindings are interactive by
+default, and every other section, including `:snippet/<name>` keys, is
+`:always`.
+
+    :scope {:env :login :abbrs :always}"##,
+    },
+    Topic {
+        name: "include",
+        summary: "Other EDN files merged in before this one",
+        details: r##"A vector of paths, relative to the including file. Each file is read
+and merged in order, the same way as several files given to `gen`, and
+the including file's own entries are merged last, so they win. A file
+included more than once is only read the first time, and a file that
+ends up including itself is an error. Problems are reported in the file
+that defined the entry. Only the `tilde-fish` command reads the files; the
+library reports `:include` unless its caller has merged them.
+
+    :include ["git.edn" "rust.edn"]"##,
+    },
+    Topic {
+        name: "profiles",
+        summary: "Named overlays, one of which is selected when generating",
+        details: r##"A map of profile names to configurations. The one selected with
+`--profile NAME`, or the `TILDE_FISH_PROFILE` environment variable, is
+merged over the rest of the configuration the same way as a later file:
+maps merge, vectors concatenate unless marked `^:replace`, and `nil`
+removes an entry. Without a selection, the profiles are only checked;
+selecting one that isn't defined is an error.
+
+    :profiles {:work {:env {:EDITOR "code --wait"} :paths ["~/work/bin"]}
+               :server {:prompt {:style :minimal}}}"##,
+    },
+];
+
+/// Looks up a section by name, with or without its leading `:`
+pub fn topic(name: &str) -> Option<&'static Topic> {
+    let name = name.strip_prefix(':').unwrap_or(name);
+    let name = if name.starts_with("snippet/") {
+        "snippet/<name>"
+    } else {
+        name
+    };
+    TOPICS.iter().find(|topic| topic.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SECTIONS;
+
+    #[test]
+    fn test_every_section_is_explained() {
+        for section in SECTIONS {
+            assert!(topic(section).is_some(), "`{section}` has no topic");
+        }
+        assert_eq!(TOPICS.len(), SECTIONS.len() + 1);
+    }
+
+    #[test]
+    fn test_topic_lookup() {
+        assert_eq!(topic(":aliases").map(|topic| topic.name), Some("aliases"));
+        assert_eq!(
+            topic("snippet/git").map(|topic| topic.name),
+            Some("snippet/<name>")
+        );
+        assert!(topic("alias").is_none());
+    }
+}
@@ -0,0 +1,599 @@
+//! Conversion of an existing `config.fish` into an EDN configuration
+//!
+//! Commands with a section of their own (`alias`, `abbr -a`, `set` with a
+//! scope, `set -e`, `fish_add_path`, `set fish_greeting` and `function`
+//! definitions) are moved into it, as long as all their arguments are
+//! literal words. Everything else, including anything using variables,
+//! command substitutions or wildcards, is kept as written in `:fish`.
+//! Comments outside of functions are dropped.
+
+use crate::config::same_directory;
+use crate::names;
+use crate::syntax;
+
+/// What was found in the file, in the order of first appearance, with
+/// values already written as EDN
+#[derive(Default)]
+struct Imported {
+    greeting: Option<Option<String>>,
+    abbrs: Vec<(String, String)>,
+    aliases: Vec<(String, String)>,
+    env: Vec<(String, String)>,
+    /// Directories and their entries
+    paths: Vec<(String, String)>,
+    functions: Vec<(String, String)>,
+    fish: Vec<String>,
+}
+
+/// Sets `name` to `value`, keeping the position of an earlier definition,
+/// since fish uses the last one
+fn define(entries: &mut Vec<(String, String)>, name: String, value: String) {
+    match entries.iter_mut().find(|(existing, _)| *existing == name) {
+        Some(entry) => entry.1 = value,
+        None => entries.push((name, value)),
+    }
+}
+
+/// Converts the contents of a `config.fish` to an EDN configuration that
+/// generates equivalent fish code
+pub fn from_fish(source: &str) -> String {
+    let mut imported = Imported::default();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        // Take whole statements, so blocks stay together
+        let mut statement = vec![line];
+        while syntax::check(&statement.join("\n")).is_err() {
+            match lines.next() {
+                Some(line) => statement.push(line),
+                None => break,
+            }
+        }
+        if !imported.statement(&statement) {
+            imported
+                .fish
+                .extend(statement.iter().map(|line| line.to_string()));
+        }
+    }
+    imported.to_edn()
+}
+
+impl Imported {
+    /// Moves a statement into its section, returning `false` if it has none
+    fn statement(&mut self, statement: &[&str]) -> bool {
+        if let [first, body @ .., last] = statement
+            && last.trim() == "end"
+            && let Some(words) = words(first, false)
+            && let [function, name, options @ ..] = &words[..]
+            && function == "function"
+            && !name.starts_with('-')
+            && let Some(mut value) = function_options(options)
+        {
+            let body = edn_string(&dedent(body));
+            let value = if value.is_empty() {
+                body
+            } else {
+                value.insert(0, format!(":body {body}"));
+                format!("{{{}}}", value.join(" "))
+            };
+            define(&mut self.functions, name.clone(), value);
+            return true;
+        }
+
+        let [line] = statement else {
+            return false;
+        };
+        let Some(words) = words(line, line.trim_start().starts_with("fish_add_path")) else {
+            return false;
+        };
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        match words[..] {
+            ["alias", definition] if definition.contains('=') => {
+                let (name, command) = definition.split_once('=').unwrap_or_default();
+                define(&mut self.aliases, name.to_string(), edn_string(command));
+            }
+            ["alias", name, command] => {
+                define(&mut self.aliases, name.to_string(), edn_string(command));
+            }
+            ["abbr", "-a" | "--add", ref rest @ ..] => {
+                let rest = rest.strip_prefix(&["--"]).unwrap_or(rest);
+                let [name, expansion @ ..] = rest else {
+                    return false;
+                };
+                if name.starts_with('-') || expansion.is_empty() {
+                    return false;
+                }
+                define(
+                    &mut self.abbrs,
+                    name.to_string(),
+                    edn_string(&expansion.join(" ")),
+                );
+            }
+            ["set", "fish_greeting"] | ["set", "-g", "fish_greeting"] => {
+                self.greeting = Some(None);
+            }
+            ["set", "fish_greeting", message] | ["set", "-g", "fish_greeting", message] => {
+                self.greeting = Some(Some(message.to_string()));
+            }
+            ["set", ref arguments @ ..] => {
+                let Some((name, value)) = variable(arguments) else {
+                    return false;
+                };
+                define(&mut self.env, name, value);
+            }
+            ["fish_add_path", ref options @ ..] => {
+                let Some((path, entry)) = path_entry(options) else {
+                    return false;
+                };
+                // Adding a directory again only has an effect with `--move`
+                if !self
+                    .paths
+                    .iter()
+                    .any(|(existing, _)| same_directory(existing, &path))
+                {
+                    self.paths.push((path, entry));
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn to_edn(&self) -> String {
+        let mut sections = Vec::new();
+        if let Some(greeting) = &self.greeting {
+            sections.push(format!(
+                ":fish-greeting {}",
+                greeting.as_deref().map_or("nil".to_string(), edn_string)
+            ));
+        }
+        for (section, entries) in [
+            ("abbrs", &self.abbrs),
+            ("aliases", &self.aliases),
+            ("env", &self.env),
+            ("functions", &self.functions),
+        ] {
+            if !entries.is_empty() {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(name, value)| format!("{} {value}", edn_key(name)))
+                    .collect();
+                sections.push(format!(
+                    ":{section} {}",
+                    collection('{', '}', section, &entries)
+                ));
+            }
+        }
+        if !self.paths.is_empty() {
+            let entries: Vec<String> = self.paths.iter().map(|(_, entry)| entry.clone()).collect();
+            sections.push(format!(
+                ":paths {}",
+                collection('[', ']', "paths", &entries)
+            ));
+        }
+        if !self.fish.is_empty() {
+            let items: Vec<String> = self.fish.iter().map(|item| edn_string(item)).collect();
+            sections.push(format!(":fish {}", collection('[', ']', "fish", &items)));
+        }
+        format!("{{{}}}\n", sections.join("\n "))
+    }
+}
+
+/// Converts the options of a `function` line to EDN map entries, or `None`
+/// if any of them has no equivalent
+fn function_options(options: &[String]) -> Option<Vec<String>> {
+    let mut entries = Vec::new();
+    let mut lists: Vec<(&str, Vec<&str>)> = Vec::new();
+    let mut options = options.iter().map(String::as_str).peekable();
+    while let Some(option) = options.next() {
+        let (flag, inline) = match option.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (option, None),
+        };
+        let key = match flag {
+            "-d" | "--description" => "description",
+            "-a" | "--argument-names" => "argument-names",
+            "-w" | "--wraps" => "wraps",
+            "-e" | "--on-event" => "on-event",
+            "-v" | "--on-variable" => "on-variable",
+            "-s" | "--on-signal" => "on-signal",
+            "-j" | "--on-job-exit" => "on-job-exit",
+            "-V" | "--inherit-variable" => "inherit-variable",
+            "-S" | "--no-scope-shadowing" if inline.is_none() => {
+                entries.push(":no-scope-shadowing true".to_string());
+                continue;
+            }
+            _ => return None,
+        };
+        let mut values = match inline {
+            Some(value) => vec![value],
+            None => vec![options.next()?],
+        };
+        if key == "argument-names" && inline.is_none() {
+            while let Some(name) = options.next_if(|name| !name.starts_with('-')) {
+                values.push(name);
+            }
+        }
+        match key {
+            "description" | "wraps" => {
+                entries.push(format!(":{key} {}", edn_string(values[0])));
+            }
+            _ => match lists.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, existing)) => existing.extend(values),
+                None => lists.push((key, values)),
+            },
+        }
+    }
+    for (key, values) in lists {
+        let values: Vec<String> = values.into_iter().map(edn_string).collect();
+        entries.push(format!(":{key} [{}]", values.join(" ")));
+    }
+    Some(entries)
+}
+
+/// Converts the arguments of `set` to an `:env` entry, or `None` unless
+/// they are options with an equivalent, including a scope, and a variable
+///
+/// Without `-x` or `-u`, `set` leaves a variable exported if it already
+/// was; the entry unexports it, which only differs for variables inherited
+/// from the environment.
+fn variable(arguments: &[&str]) -> Option<(String, String)> {
+    let mut scope = None;
+    let (mut export, mut path, mut erase) = (false, false, false);
+    let mut arguments = arguments.iter().peekable();
+    while let Some(option) = arguments.next_if(|argument| argument.starts_with('-')) {
+        let flags: Vec<char> = match *option {
+            "--global" => vec!['g'],
+            "--universal" => vec!['U'],
+            "--local" => vec!['l'],
+            "--export" => vec!['x'],
+            "--unexport" => vec!['u'],
+            "--erase" => vec!['e'],
+            "--path" => {
+                path = true;
+                continue;
+            }
+            option if !option.starts_with("--") => option[1..].chars().collect(),
+            _ => return None,
+        };
+        for flag in flags {
+            match flag {
+                'g' => scope = Some("global"),
+                'U' => scope = Some("universal"),
+                'l' => scope = Some("local"),
+                'x' => export = true,
+                'u' => export = false,
+                'e' => erase = true,
+                _ => return None,
+            }
+        }
+    }
+    let name = arguments.next()?;
+    names::variable(name).ok()?;
+    let values: Vec<&str> = arguments.copied().collect();
+    if erase {
+        if !values.is_empty() || path {
+            return None;
+        }
+        let value = match scope {
+            Some(scope) => format!("{{:value nil :scope :{scope}}}"),
+            None => "nil".to_string(),
+        };
+        return Some((name.to_string(), value));
+    }
+
+    let scope = scope?;
+    let value = match &values[..] {
+        [value] => edn_string(value),
+        values => {
+            let values: Vec<String> = values.iter().map(|value| edn_string(value)).collect();
+            format!("[{}]", values.join(" "))
+        }
+    };
+    let mut options = Vec::new();
+    if scope != "global" {
+        options.push(format!(":scope :{scope}"));
+    }
+    if !export {
+        options.push(":export false".to_string());
+    }
+    if path {
+        options.push(":path true".to_string());
+    }
+    if options.is_empty() {
+        return Some((name.to_string(), value));
+    }
+    Some((
+        name.to_string(),
+        format!("{{:value {value} {}}}", options.join(" ")),
+    ))
+}
+
+/// Converts the arguments of `fish_add_path` to a `:paths` entry, or `None`
+/// unless they are options with an equivalent and a single directory
+fn path_entry(arguments: &[&str]) -> Option<(String, String)> {
+    let mut path = None;
+    let mut options = Vec::new();
+    for argument in arguments {
+        match *argument {
+            // The defaults
+            "-U" | "--universal" | "-p" | "--prepend" => {}
+            "-g" | "--global" => options.push(":global true"),
+            "-a" | "--append" => options.push(":append true"),
+            "-m" | "--move" => options.push(":move true"),
+            option if option.starts_with('-') => return None,
+            directory if path.is_none() => path = Some(directory.to_string()),
+            _ => return None,
+        }
+    }
+    let path = path?;
+    let entry = if options.is_empty() {
+        edn_string(&path)
+    } else {
+        format!("{{:path {} {}}}", edn_string(&path), options.join(" "))
+    };
+    Some((path, entry))
+}
+
+/// Lays out a map or vector with one entry per line, aligned after the
+/// opening delimiter
+fn collection(open: char, close: char, section: &str, entries: &[String]) -> String {
+    let indent = " ".repeat(section.len() + 4);
+    format!("{open}{}{close}", entries.join(&format!("\n{indent}")))
+}
+
+/// Removes the indentation shared by every non-blank line
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_string()
+}
+
+/// Splits a command line into words if they are all literal, undoing quotes
+/// and escapes; `None` if fish would expand or redirect anything
+///
+/// With `tilde`, an argument may start with an unquoted `~`, which the
+/// generated `fish_add_path` keeps unquoted too.
+fn words(line: &str, tilde: bool) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Some(words);
+        };
+        if first == '#' {
+            return Some(words);
+        }
+        if first == '~' && (!tilde || words.is_empty()) {
+            return None;
+        }
+        let mut word = String::new();
+        while let Some(c) = chars.next_if(|c| *c != ' ' && *c != '\t') {
+            match c {
+                '\'' => loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        '\\' if chars.peek().is_some_and(|c| *c == '\\' || *c == '\'') => {
+                            word.push(chars.next()?);
+                        }
+                        c => word.push(c),
+                    }
+                },
+                '"' => loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '$' => return None,
+                        '\\' if chars.peek().is_some_and(|c| "\\\"$\n".contains(*c)) => {
+                            word.push(chars.next()?);
+                        }
+                        c => word.push(c),
+                    }
+                },
+                '\\' => match chars.next()? {
+                    'n' => word.push('\n'),
+                    't' => word.push('\t'),
+                    c if !c.is_ascii_alphanumeric() => word.push(c),
+                    _ => return None,
+                },
+                '$' | '(' | ')' | ';' | '|' | '&' | '<' | '>' | '*' | '?' | '{' | '}' => {
+                    return None;
+                }
+                c => word.push(c),
+            }
+        }
+        words.push(word);
+    }
+}
+
+/// Writes a string as an EDN literal
+pub(crate) fn edn_string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Writes a name as a keyword where EDN allows it, and as a string otherwise
+fn edn_key(name: &str) -> String {
+    let mut chars = name.chars();
+    let keyword = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || "*!_?<>=".contains(c))
+        && chars.all(|c| c.is_ascii_alphanumeric() || "*+!-_?<>=.".contains(c));
+    if keyword {
+        format!(":{name}")
+    } else {
+        edn_string(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FishConfig;
+    use clojure_reader::edn;
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            words(r#"alias ll 'ls -la'"#, false),
+            Some(vec!["alias".into(), "ll".into(), "ls -la".into()])
+        );
+        assert_eq!(
+            words(r#"echo "a \"b\"" c\ d 'it\'s' # note"#, false),
+            Some(vec![
+                "echo".into(),
+                r#"a "b""#.into(),
+                "c d".into(),
+                "it's".into()
+            ])
+        );
+        assert_eq!(words("set -gx PATH $HOME/bin", false), None);
+        assert_eq!(words(r#"echo "$USER""#, false), None);
+        assert_eq!(words("ls *.rs", false), None);
+        assert_eq!(words("cat a > b", false), None);
+        assert_eq!(words("fish_add_path ~/bin", false), None);
+        assert_eq!(
+            words("fish_add_path ~/bin", true),
+            Some(vec!["fish_add_path".into(), "~/bin".into()])
+        );
+    }
+
+    #[test]
+    fn test_sections() {
+        let source = r#"
+# Aliases
+alias ll 'ls -la'
+alias gs="git status"
+abbr -a -- gc 'git commit'
+set -gx EDITOR nvim
+set -gx PATH $HOME/bin $PATH
+set fish_greeting
+fish_add_path ~/.local/bin
+fish_add_path -g --move /opt/homebrew/bin
+fish_add_path --append /opt/a /opt/b
+fish_add_path ~/.local/bin/
+
+function mkcd
+    mkdir -p $argv[1]
+    and cd $argv[1]
+end
+
+if status is-interactive
+    starship init fish | source
+end
+"#;
+        assert_eq!(
+            from_fish(source),
+            r#"{:fish-greeting nil
+ :abbrs {:gc "git commit"}
+ :aliases {:ll "ls -la"
+           :gs "git status"}
+ :env {:EDITOR "nvim"}
+ :functions {:mkcd "mkdir -p $argv[1]\nand cd $argv[1]"}
+ :paths ["~/.local/bin"
+         {:path "/opt/homebrew/bin" :global true :move true}]
+ :fish ["set -gx PATH $HOME/bin $PATH"
+        "fish_add_path --append /opt/a /opt/b"
+        "if status is-interactive"
+        "    starship init fish | source"
+        "end"]}
+"#
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = r#"set fish_greeting 'Hello, "fish"'
+alias grep 'grep --color=auto'
+alias .. 'cd ..'
+set -gx WINPATH 'C:\\Users'
+function greet --description 'Say hi'
+    echo hi
+end
+"#;
+        let imported = from_fish(source);
+        let parsed = edn::read_string(&imported).unwrap();
+        let (config, diagnostics) = FishConfig::parse(&parsed);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        let fish = config.to_fish();
+        assert!(fish.contains(r#"set fish_greeting 'Hello, "fish"'"#));
+        assert!(fish.contains("alias grep 'grep --color=auto'"));
+        assert!(fish.contains("alias .. 'cd ..'"));
+        assert!(fish.contains(r"set -gx WINPATH 'C:\\Users'"));
+        assert!(fish.contains("function greet --description 'Say hi'\n    echo hi\nend"));
+    }
+
+    #[test]
+    fn test_function_options() {
+        let source = "function gco -d 'Check out' --argument-names branch remote -e fish_exit
+    git checkout $branch
+end
+function weird --unknown-flag
+    echo
+end
+";
+        assert_eq!(
+            from_fish(source),
+            r#"{:functions {:gco {:body "git checkout $branch" :description "Check out" :argument-names ["branch" "remote"] :on-event ["fish_exit"]}}
+ :fish ["function weird --unknown-flag"
+        "    echo"
+        "end"]}
+"#
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        let source = "set -gx EDITOR nvim
+set -g fish_prompt_pwd_dir_length 3
+set -Ux LESS -R --mouse
+set --global --export --path XDG_DATA_DIRS /usr/local/share /usr/share
+set -e GREP_OPTIONS
+set -e -U old_setting
+set -q TMUX
+set fish_color_cwd green
+";
+        assert_eq!(
+            from_fish(source),
+            r#"{:env {:EDITOR "nvim"
+       :fish_prompt_pwd_dir_length {:value "3" :export false}
+       :LESS {:value ["-R" "--mouse"] :scope :universal}
+       :XDG_DATA_DIRS {:value ["/usr/local/share" "/usr/share"] :path true}
+       :GREP_OPTIONS nil
+       :old_setting {:value nil :scope :universal}}
+ :fish ["set -q TMUX"
+        "set fish_color_cwd green"]}
+"#
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(from_fish("# nothing here\n"), "{}\n");
+    }
+}
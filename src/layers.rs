@@ -0,0 +1,471 @@
+//! Merging of configuration layers
+//!
+//! A configuration may be split into layers, such as a shared base with
+//! personal overrides on top, given as several files or as one vector of
+//! maps. Layers are merged in order:
+//!
+//! - maps merge entry by entry, recursively
+//! - vectors concatenate, unless the later one is marked `^:replace`
+//! - any other value from a later layer replaces the earlier one
+//! - `nil` removes the entry an earlier layer gave; with nothing to remove
+//!   it is kept, so `:fish-greeting nil` still disables the greeting
+//!
+//! A layer with a `:when` of its own only applies where the condition
+//! holds, so it is merged as one more `:conditional` block, keeping out the
+//! sections that only work at the top level.
+//!
+//! `^:replace` also makes a map replace the earlier one instead of merging
+//! into it. The EDN reader doesn't support metadata, so [`read_markers`]
+//! turns the markers into `#replace` tags before the text is read.
+//!
+//! A layer's `:include` vector names files to merge in before it; reading
+//! them is up to the caller, with [`includes`] listing them. A caller that
+//! has done so sets [`Options::includes_read`](crate::Options::includes_read),
+//! and `:include` is otherwise reported. The same goes for the `:colors
+//! :theme` files [`themes`] lists, whose text goes in
+//! [`Options::themes`](crate::Options::themes).
+
+use crate::config::{TOP_LEVEL_ONLY, key_segment};
+use crate::diagnostic::Segment;
+use crate::quote::unescape;
+use clojure_reader::edn::Edn;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+const MARKER: &str = "^:replace";
+
+/// Same width as [`MARKER`], so positions in the text don't move
+const TAG: &str = "#replace ";
+
+/// Rewrites each `^:replace` marker as a `#replace` tag, leaving strings,
+/// comments and every other position in the text as they are
+pub fn read_markers(text: &str) -> Cow<'_, str> {
+    if !text.contains(MARKER) {
+        return Cow::Borrowed(text);
+    }
+    let mut rewritten = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        let rest = &text[index..];
+        if in_string {
+            match c {
+                '\\' => {
+                    rewritten.push(c);
+                    rewritten.extend(chars.next().map(|(_, c)| c));
+                    continue;
+                }
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == '\\' {
+            // A character literal, which may be `\"` or `\;`
+            rewritten.push(c);
+            rewritten.extend(chars.next().map(|(_, c)| c));
+            continue;
+        } else if c == ';' {
+            let comment = &rest[..rest.find('\n').unwrap_or(rest.len())];
+            rewritten.push_str(comment);
+            for _ in 1..comment.chars().count() {
+                chars.next();
+            }
+            continue;
+        } else if rest.starts_with(MARKER)
+            && rest[MARKER.len()..]
+                .chars()
+                .next()
+                .is_none_or(|next| next.is_whitespace() || "[{(#\",".contains(next))
+        {
+            rewritten.push_str(TAG);
+            chars.nth(MARKER.len() - 2);
+            continue;
+        }
+        rewritten.push(c);
+    }
+    Cow::Owned(rewritten)
+}
+
+/// Whether a value is a vector of maps, read as layers
+pub fn is_layers(config: &Edn<'_>) -> bool {
+    matches!(config, Edn::Vector(items)
+        if !items.is_empty() && items.iter().all(|item| matches!(item, Edn::Map(_))))
+}
+
+/// The layers of a configuration, each with the path it was found at:
+/// the maps of a vector of maps, or else the value itself
+pub fn split<'e>(config: &Edn<'e>) -> Vec<(Vec<Segment>, Edn<'e>)> {
+    match config {
+        Edn::Vector(items) if is_layers(config) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (vec![Segment::Index(index)], item.clone()))
+            .collect(),
+        _ => vec![(Vec::new(), config.clone())],
+    }
+}
+
+/// The files named by each layer's `:include`, with the path of each entry
+///
+/// Entries that aren't strings are skipped, for the reader to report.
+pub fn includes(config: &Edn<'_>) -> Vec<(Vec<Segment>, String)> {
+    let mut files = Vec::new();
+    for (prefix, layer) in split(config) {
+        let Edn::Map(entries) = layer else {
+            continue;
+        };
+        let Some(Edn::Vector(items)) = entries.get(&Edn::Key("include")) else {
+            continue;
+        };
+        for (index, item) in items.iter().enumerate() {
+            if let Edn::Str(file) = item {
+                let mut path = prefix.clone();
+                path.extend([Segment::Key("include".to_string()), Segment::Index(index)]);
+                files.push((path, unescape(file)));
+            }
+        }
+    }
+    files
+}
+
+/// The `:colors :theme` files named by each layer, including its
+/// `:conditional` blocks and `:profiles`, with the path of each
+pub fn themes(config: &Edn<'_>) -> Vec<(Vec<Segment>, String)> {
+    let mut files = Vec::new();
+    for (prefix, layer) in split(config) {
+        find_themes(&layer, prefix, &mut files);
+    }
+    files
+}
+
+fn find_themes(config: &Edn<'_>, path: Vec<Segment>, files: &mut Vec<(Vec<Segment>, String)>) {
+    let Edn::Map(entries) = untagged(config) else {
+        return;
+    };
+    let key = |name: &str| Segment::Key(name.to_string());
+    if let Some(Edn::Map(colors)) = entries.get(&Edn::Key("colors")).map(untagged)
+        && let Some(Edn::Str(file)) = colors.get(&Edn::Key("theme"))
+    {
+        let mut theme_path = path.clone();
+        theme_path.extend([key("colors"), key("theme")]);
+        files.push((theme_path, unescape(file)));
+    }
+    if let Some(Edn::Vector(blocks)) = entries.get(&Edn::Key("conditional")).map(untagged) {
+        for (index, block) in blocks.iter().enumerate() {
+            let mut block_path = path.clone();
+            block_path.extend([key("conditional"), Segment::Index(index)]);
+            find_themes(block, block_path, files);
+        }
+    }
+    if let Some(Edn::Map(profiles)) = entries.get(&Edn::Key("profiles")).map(untagged) {
+        for (name, profile) in profiles {
+            let mut profile_path = path.clone();
+            profile_path.extend([key("profiles"), key_segment(name)]);
+            find_themes(profile, profile_path, files);
+        }
+    }
+}
+
+/// A value without its `#replace` tag
+fn untagged<'a, 'e>(value: &'a Edn<'e>) -> &'a Edn<'e> {
+    match value {
+        Edn::Tagged("replace", value) => value,
+        value => value,
+    }
+}
+
+/// A merged configuration, remembering which layer gave each value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merged<'e> {
+    pub config: Edn<'e>,
+    /// Paths in `config` whose value came whole from a layer, with the
+    /// layer's index and the path there
+    origins: HashMap<Vec<Segment>, (usize, Vec<Segment>)>,
+}
+
+impl Merged<'_> {
+    /// The layer that gave the value at `path`, and its path in that layer
+    pub fn origin(&self, path: &[Segment]) -> Option<(usize, Vec<Segment>)> {
+        (0..=path.len()).rev().find_map(|len| {
+            let (layer, layer_path) = self.origins.get(&path[..len])?;
+            let mut layer_path = layer_path.clone();
+            layer_path.extend_from_slice(&path[len..]);
+            Some((*layer, layer_path))
+        })
+    }
+}
+
+/// Merges layers in order, later ones overriding earlier ones
+pub fn merge<'e>(layers: Vec<Edn<'e>>) -> Merged<'e> {
+    let mut origins = HashMap::new();
+    let mut config = None;
+    for (index, layer) in layers.into_iter().enumerate() {
+        let mut merger = Merger {
+            layer: index,
+            origins: &mut origins,
+        };
+        config = Some(match layer {
+            Edn::Map(entries) if entries.contains_key(&Edn::Key("when")) => {
+                merger.conditional(config, entries)
+            }
+            layer => merger.value(config, layer, Vec::new(), Vec::new()),
+        });
+    }
+    Merged {
+        config: config.unwrap_or(Edn::Map(Default::default())),
+        origins,
+    }
+}
+
+struct Merger<'o> {
+    layer: usize,
+    origins: &'o mut HashMap<Vec<Segment>, (usize, Vec<Segment>)>,
+}
+
+impl Merger<'_> {
+    /// Merges a layer's value at `layer_path` into the value so far at `path`
+    fn value<'e>(
+        &mut self,
+        base: Option<Edn<'e>>,
+        value: Edn<'e>,
+        path: Vec<Segment>,
+        layer_path: Vec<Segment>,
+    ) -> Edn<'e> {
+        match (base, value) {
+            (Some(Edn::Map(mut base)), Edn::Map(entries)) => {
+                for (key, value) in entries {
+                    let mut entry_path = path.clone();
+                    entry_path.push(key_segment(&key));
+                    if value == Edn::Nil && base.remove(&key).is_some() {
+                        self.forget(&entry_path);
+                        continue;
+                    }
+                    let mut entry_layer_path = layer_path.clone();
+                    entry_layer_path.push(key_segment(&key));
+                    let merged = self.value(base.remove(&key), value, entry_path, entry_layer_path);
+                    base.insert(key, merged);
+                }
+                Edn::Map(base)
+            }
+            (Some(Edn::Vector(mut base)), Edn::Vector(items)) => {
+                for (index, item) in items.into_iter().enumerate() {
+                    let mut item_path = path.clone();
+                    item_path.push(Segment::Index(base.len()));
+                    let mut item_layer_path = layer_path.clone();
+                    item_layer_path.push(Segment::Index(index));
+                    self.record(item_path, item_layer_path);
+                    base.push(strip(item));
+                }
+                Edn::Vector(base)
+            }
+            (_, value) => {
+                self.record(path, layer_path);
+                strip(value)
+            }
+        }
+    }
+
+    /// Merges a layer with a `:when` as a `:conditional` block of everything
+    /// but its top-level-only sections, which are merged as usual
+    fn conditional<'e>(
+        &mut self,
+        base: Option<Edn<'e>>,
+        mut layer: BTreeMap<Edn<'e>, Edn<'e>>,
+    ) -> Edn<'e> {
+        let mut top = BTreeMap::new();
+        for key in TOP_LEVEL_ONLY {
+            if let Some((key, value)) = layer.remove_entry(&Edn::Key(key)) {
+                top.insert(key, value);
+            }
+        }
+        top.insert(Edn::Key("conditional"), Edn::Vector(vec![Edn::Map(layer)]));
+        let merged = self.value(base, Edn::Map(top), Vec::new(), Vec::new());
+        // The block's values are at the top of the layer
+        if let Edn::Map(entries) = &merged
+            && let Some(Edn::Vector(blocks)) = entries.get(&Edn::Key("conditional"))
+        {
+            let block = vec![
+                Segment::Key("conditional".to_string()),
+                Segment::Index(blocks.len() - 1),
+            ];
+            self.record(block, Vec::new());
+        }
+        merged
+    }
+
+    /// Notes that the value at `path` came whole from this layer
+    fn record(&mut self, path: Vec<Segment>, layer_path: Vec<Segment>) {
+        self.forget(&path);
+        self.origins.insert(path, (self.layer, layer_path));
+    }
+
+    /// Drops the origins of a value that has been replaced or removed
+    fn forget(&mut self, path: &[Segment]) {
+        self.origins.retain(|origin, _| !origin.starts_with(path));
+    }
+}
+
+/// Removes the `#replace` tags from a value that had nothing to replace
+fn strip(value: Edn<'_>) -> Edn<'_> {
+    match value {
+        Edn::Tagged("replace", value) => strip(*value),
+        Edn::Map(entries) => Edn::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, strip(value)))
+                .collect(),
+        ),
+        Edn::Vector(items) => Edn::Vector(items.into_iter().map(strip).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clojure_reader::edn;
+
+    fn merged(layers: &[&str]) -> String {
+        let layers = layers
+            .iter()
+            .map(|layer| edn::read_string(layer).unwrap())
+            .collect();
+        merge(layers).config.to_string()
+    }
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(
+            merged(&[
+                r#"{:aliases {:ll "ls -la" :la "ls -A"} :paths ["/a"] :fish-greeting "hi"}"#,
+                r#"{:aliases {:ll "eza -l" :la nil} :paths ["/b"] :fish-greeting nil}"#,
+            ]),
+            r#"{:aliases {:ll "eza -l"}, :paths ["/a" "/b"]}"#
+        );
+        assert_eq!(
+            merged(&[r#"{:fish-greeting nil}"#, r#"{:env {:PAGER nil}}"#]),
+            r#"{:env {:PAGER nil}, :fish-greeting nil}"#
+        );
+        assert_eq!(
+            merged(&[
+                r#"{:paths ["/a"] :env {:A "1" :B "2"}}"#,
+                r#"{:paths #replace ["/b"] :env #replace {:C "3"}}"#,
+            ]),
+            r#"{:env {:C "3"}, :paths ["/b"]}"#
+        );
+        assert_eq!(
+            merged(&[r#"{:paths #replace ["/a"]}"#]),
+            r#"{:paths ["/a"]}"#
+        );
+    }
+
+    #[test]
+    fn test_origins() {
+        let layers = vec![
+            edn::read_string(r#"{:paths ["/a" "/b"] :env {:A "1"}}"#).unwrap(),
+            edn::read_string(r#"{:paths ["/c"] :env {:A "2" :B "3"}}"#).unwrap(),
+            edn::read_string(r#"{:env nil}"#).unwrap(),
+            edn::read_string(r#"{:env {:C "4"}}"#).unwrap(),
+        ];
+        let merged = merge(layers);
+        let key = |name: &str| Segment::Key(name.to_string());
+
+        assert_eq!(
+            merged.origin(&[key("paths"), Segment::Index(1)]),
+            Some((0, vec![key("paths"), Segment::Index(1)]))
+        );
+        assert_eq!(
+            merged.origin(&[key("paths"), Segment::Index(2)]),
+            Some((1, vec![key("paths"), Segment::Index(0)]))
+        );
+        assert_eq!(
+            merged.origin(&[key("env"), key("C")]),
+            Some((3, vec![key("env"), key("C")]))
+        );
+        assert_eq!(
+            merged.origin(&[key("env"), key("A")]),
+            Some((3, vec![key("env"), key("A")]))
+        );
+    }
+
+    #[test]
+    fn test_conditional_layer() {
+        let layers = vec![
+            edn::read_string(r#"{:paths ["/a"] :conditional [{:when {:os :macos}}]}"#).unwrap(),
+            edn::read_string(r#"{:when {:os :linux} :autoload [:functions] :env {:A "1"}}"#)
+                .unwrap(),
+        ];
+        let merged = merge(layers);
+        let key = |name: &str| Segment::Key(name.to_string());
+
+        assert_eq!(
+            merged.config.to_string(),
+            r#"{:autoload [:functions], :conditional [{:when {:os :macos}} {:env {:A "1"}, :when {:os :linux}}], :paths ["/a"]}"#
+        );
+        assert_eq!(
+            merged.origin(&[key("conditional"), Segment::Index(1), key("env"), key("A")]),
+            Some((1, vec![key("env"), key("A")]))
+        );
+    }
+
+    #[test]
+    fn test_includes() {
+        let config =
+            edn::read_string(r#"[{:include ["git.edn" 42]} {:paths []} {:include ["a\\b.edn"]}]"#)
+                .unwrap();
+        let key = |name: &str| Segment::Key(name.to_string());
+        assert_eq!(
+            includes(&config),
+            [
+                (
+                    vec![Segment::Index(0), key("include"), Segment::Index(0)],
+                    "git.edn".to_string()
+                ),
+                (
+                    vec![Segment::Index(2), key("include"), Segment::Index(0)],
+                    "a\\b.edn".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            includes(&edn::read_string("{:include \"git.edn\"}").unwrap()),
+            []
+        );
+    }
+
+    #[test]
+    fn test_themes() {
+        let config = edn::read_string(
+            r#"[{:colors {:theme "a.theme"}}
+                {:conditional [{:when {:os :linux} :colors #replace {:theme "b.theme"}}]
+                 :profiles {:work {:colors {:theme "c.theme"}}}}]"#,
+        )
+        .unwrap();
+        let files: Vec<(String, String)> = themes(&config)
+            .into_iter()
+            .map(|(path, file)| (crate::diagnostic::location(&path), file))
+            .collect();
+        let file = |path: &str, file: &str| (path.to_string(), file.to_string());
+        assert_eq!(
+            files,
+            [
+                file("[0].colors.theme", "a.theme"),
+                file("[1].conditional[0].colors.theme", "b.theme"),
+                file("[1].profiles.work.colors.theme", "c.theme"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_markers() {
+        let text = r#"{:paths ^:replace ["^:replace"] ; ^:replace
+ :fish ^:replaced [] :env ^:replace{}}"#;
+        assert_eq!(
+            read_markers(text),
+            r#"{:paths #replace  ["^:replace"] ; ^:replace
+ :fish ^:replaced [] :env #replace {}}"#
+        );
+        assert!(matches!(read_markers("{:a 1}"), Cow::Borrowed(_)));
+    }
+}
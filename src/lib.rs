@@ -1,12 +1,49 @@
 use clojure_reader::edn::Edn;
 use std::collections::BTreeMap;
 
+mod color;
+mod config;
+mod diagnostic;
+pub mod diff;
+pub mod explain;
+pub mod import;
+pub mod layers;
+pub mod names;
+mod preset;
+pub mod quote;
+mod render;
+mod source;
+pub mod syntax;
+mod target;
+
+pub use config::{
+    Abbr, AbbrExpansion, AbbrPosition, Alias, Color, Completion, CompletionOption, Condition,
+    Conditional, EnvCondition, EnvTest, EnvVar, FishConfig, FishFunction, Greeting, KeyAction,
+    KeyBinding, KeyBindings, KeyMode, Options, Os, PathEntry, PathVar, Prompt, PromptSegment,
+    Scope, SegmentKind, Snippet, VariableScope,
+};
+pub use diagnostic::{ConfigError, Diagnostic, DiagnosticKind, Segment};
+pub use render::{COMPLETIONS_DIR, CONFIG_FILE, FUNCTIONS_DIR, OutputFile};
+pub use source::{SourceMap, Span};
+pub use target::Target;
+
+/// Describes the plugin to tilde: the files it writes and the flags that
+/// generate them
+///
+/// A path ending in `/` is a directory; its generator prints an EDN map of
+/// file paths to contents, as [`files_edn`] does.
 pub fn plugin_config() -> String {
+    let functions_dir = format!("{FUNCTIONS_DIR}/");
+    let completions_dir = format!("{COMPLETIONS_DIR}/");
     let mut files_map = BTreeMap::new();
-    files_map.insert(Edn::Key("fish"), Edn::Str("fish/config.fish"));
+    files_map.insert(Edn::Key("fish"), Edn::Str(CONFIG_FILE));
+    files_map.insert(Edn::Key("functions"), Edn::Str(&functions_dir));
+    files_map.insert(Edn::Key("completions"), Edn::Str(&completions_dir));
 
     let mut generators_map = BTreeMap::new();
     generators_map.insert(Edn::Key("fish"), Edn::Str("--gen-config"));
+    generators_map.insert(Edn::Key("functions"), Edn::Str("--gen-functions"));
+    generators_map.insert(Edn::Key("completions"), Edn::Str("--gen-completions"));
 
     let mut preambles_map = BTreeMap::new();
     preambles_map.insert(
@@ -24,174 +61,74 @@ pub fn plugin_config() -> String {
     format!("{}", Edn::Map(config_map))
 }
 
+/// Generates the contents of `config.fish` from an EDN configuration
+///
+/// Entries that don't match the expected shape are skipped; use
+/// [`fish_config_checked`] to find out which. A vector of maps is merged
+/// as [`layers`], and any other value that isn't a map produces a
+/// placeholder comment.
 pub fn fish_config(config: Edn) -> String {
-    let mut output = String::new();
-
-    // Add preamble if present
-    if let Edn::Map(config_map) = &config {
-        if let Some(Edn::Map(preambles)) = config_map.get(&Edn::Key("preambles")) {
-            if let Some(Edn::Str(preamble)) = preambles.get(&Edn::Key("tilde/all")) {
-                output.push_str(&preamble.replace("\\n", "\n"));
-                output.push('\n');
-            }
-        }
+    if !matches!(config, Edn::Map(_)) && !layers::is_layers(&config) {
+        return "# Basic fish configuration\n".to_string();
     }
+    FishConfig::parse(&config).0.to_fish()
+}
 
-    // Process the config to generate fish shell configuration
-    match config {
-        Edn::Map(config_map) => {
-            // Handle fish-greeting
-            if let Some(Edn::Str(fish_greeting)) = config_map.get(&Edn::Key("fish-greeting")) {
-                output.push_str(&format!("set fish_greeting '{fish_greeting}'\n"));
-            } else if config_map.contains_key(&Edn::Key("fish-greeting")) {
-                output.push_str("set fish_greeting\n");
-            }
-            output.push('\n');
-
-            // Handle snippets
-            for (key, value) in config_map.iter() {
-                if let Edn::Key(key_str) = key {
-                    if key_str.starts_with("snippet/") {
-                        if let Edn::Str(snippet_content) = value {
-                            output.push_str(&format!("# {}\n", &key_str.replace("snippet/", "")));
-                            let snippet =
-                                snippet_content.replace("\\n", "\n").replace("\\\"", "\"");
-                            output.push_str(&snippet);
-                            output.push('\n');
-                        }
-                    }
-                }
-            }
-
-            // Handle abbreviations
-            if let Some(Edn::Map(abbrs)) = config_map.get(&Edn::Key("abbrs")) {
-                output.push_str("# Abbreviations\n");
-                for (key, value) in abbrs {
-                    match (key, value) {
-                        (Edn::Key(abbr_name), Edn::Str(abbr_expansion)) => {
-                            output
-                                .push_str(&format!("abbr -a -- {abbr_name} '{abbr_expansion}'\n"));
-                        }
-                        (Edn::Str(abbr_name), Edn::Str(abbr_expansion)) => {
-                            output
-                                .push_str(&format!("abbr -a -- {abbr_name} '{abbr_expansion}'\n"));
-                        }
-                        _ => {}
-                    }
-                }
-                output.push('\n');
-            }
-
-            // Handle aliases
-            if let Some(Edn::Map(aliases)) = config_map.get(&Edn::Key("aliases")) {
-                output.push_str("# Aliases\n");
-                for (key, value) in aliases {
-                    match (key, value) {
-                        (Edn::Key(alias_name), Edn::Str(alias_command)) => {
-                            output.push_str(&format!("alias {alias_name} '{alias_command}'\n"));
-                        }
-                        (Edn::Str(alias_name), Edn::Str(alias_command)) => {
-                            output.push_str(&format!("alias {alias_name} '{alias_command}'\n"));
-                        }
-                        _ => {}
-                    }
-                }
-                output.push('\n');
-            }
-
-            // Handle environment variables
-            if let Some(Edn::Map(env_vars)) = config_map.get(&Edn::Key("env")) {
-                output.push_str("# Environment Variables\n");
-                for (key, value) in env_vars {
-                    match (key, value) {
-                        (Edn::Key(var_name), Edn::Str(var_value)) => {
-                            output.push_str(&format!("set -gx {var_name} '{var_value}'\n"));
-                        }
-                        (Edn::Str(var_name), Edn::Str(var_value)) => {
-                            output.push_str(&format!("set -gx {var_name} '{var_value}'\n"));
-                        }
-                        _ => {}
-                    }
-                }
-                output.push('\n');
-            }
+/// Generates `config.fish` and any autoloaded function and completion
+/// files, as
+/// [`FishConfig::to_files`] does
+///
+/// Like [`fish_config`], a value that isn't a map produces a placeholder
+/// `config.fish`.
+pub fn fish_files(config: Edn) -> Vec<OutputFile> {
+    fish_files_with(config, &Options::default())
+}
 
-            // Handle PATH additions
-            if let Some(Edn::Vector(paths)) = config_map.get(&Edn::Key("paths")) {
-                output.push_str("# PATH additions\n");
-                for path in paths {
-                    if let Edn::Str(path_str) = path {
-                        output.push_str(&format!("fish_add_path {path_str}\n"));
-                    }
-                }
-                output.push('\n');
-            }
+/// Like [`fish_files`], with explicit [`Options`]
+///
+/// With [`Options::target`] set, `:when` conditions are decided for that
+/// machine instead of being written as `if` blocks.
+pub fn fish_files_with(config: Edn, options: &Options) -> Vec<OutputFile> {
+    if !matches!(config, Edn::Map(_)) && !layers::is_layers(&config) {
+        return vec![OutputFile {
+            path: CONFIG_FILE.to_string(),
+            contents: fish_config(config),
+        }];
+    }
+    FishConfig::parse_with(&config, options).0.to_files()
+}
 
-            // Handle functions
-            if let Some(Edn::Map(functions)) = config_map.get(&Edn::Key("functions")) {
-                output.push_str("# Functions\n");
-                for (key, value) in functions {
-                    match (key, value) {
-                        (Edn::Key(func_name), Edn::Str(func_body)) => {
-                            output.push_str(&format!("function {func_name}\n"));
-                            // Handle multi-line function bodies with proper newline processing
-                            let processed_body = func_body.replace("\\n", "\n");
-                            for line in processed_body.lines() {
-                                if !line.trim().is_empty() {
-                                    output.push_str(&format!("    {line}\n"));
-                                }
-                            }
-                            output.push_str("end\n\n");
-                        }
-                        (Edn::Str(func_name), Edn::Str(func_body)) => {
-                            output.push_str(&format!("function {func_name}\n"));
-                            // Handle multi-line function bodies with proper newline processing
-                            let processed_body = func_body.replace("\\n", "\n");
-                            for line in processed_body.lines() {
-                                if !line.trim().is_empty() {
-                                    output.push_str(&format!("    {line}\n"));
-                                }
-                            }
-                            output.push_str("end\n\n");
-                        }
-                        _ => {}
-                    }
-                }
-            }
+/// Writes files as an EDN map of their paths to their contents
+pub fn files_edn(files: &[OutputFile]) -> String {
+    let entries: Vec<String> = files
+        .iter()
+        .map(|file| {
+            format!(
+                "{} {}",
+                import::edn_string(&file.path),
+                import::edn_string(&file.contents)
+            )
+        })
+        .collect();
+    format!("{{{}}}", entries.join("\n "))
+}
 
-            // Handle raw fish commands
-            if let Some(Edn::Vector(commands)) = config_map.get(&Edn::Key("fish")) {
-                output.push_str("# Custom Fish Commands\n");
-                for command in commands {
-                    if let Edn::Str(cmd_str) = command {
-                        output.push_str(&format!("{cmd_str}\n"));
-                    }
-                }
-                output.push('\n');
-            }
+/// Generates the contents of `config.fish`, or every problem that prevents
+/// the configuration from being used as written
+pub fn fish_config_checked(config: Edn) -> Result<String, Vec<Diagnostic>> {
+    fish_config_checked_with(config, &Options::default())
+}
 
-            // Handle prompt configuration
-            if let Some(Edn::Map(prompt_config)) = config_map.get(&Edn::Key("prompt")) {
-                output.push_str("# Prompt Configuration\n");
-                if let Some(Edn::Str(prompt_style)) = prompt_config.get(&Edn::Key("style")) {
-                    output.push_str(&format!("set -g theme {prompt_style}\n"));
-                }
-                if let Some(Edn::Bool(show_git)) = prompt_config.get(&Edn::Key("show-git")) {
-                    output.push_str(&format!(
-                        "set -g fish_prompt_show_git {}\n",
-                        if *show_git { "true" } else { "false" }
-                    ));
-                }
-                output.push('\n');
-            }
-        }
-        _ => {
-            // If config is not a map, return a basic config
-            output.push_str("# Basic fish configuration\n");
-        }
+/// Like [`fish_config_checked`], with explicit [`Options`]
+///
+/// With [`Options::strict`] set, unrecognised keys are reported too.
+pub fn fish_config_checked_with(config: Edn, options: &Options) -> Result<String, Vec<Diagnostic>> {
+    let (config, diagnostics) = FishConfig::parse_with(&config, options);
+    if diagnostics.is_empty() {
+        Ok(config.to_fish())
+    } else {
+        Err(diagnostics)
     }
-
-    output
 }
 
 #[cfg(test)]
@@ -222,6 +159,363 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_autoload_functions() {
+        let config = edn::read_string(
+            r##"{:preambles {:tilde/all "# generated\n"}
+                :functions {:gst "git status" :mkcd {:body "mkdir -p $argv[1]" :description "Make"}}
+                :autoload [:functions]}"##,
+        )
+        .unwrap();
+        let files = fish_files(config);
+        let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "fish/config.fish",
+                "fish/functions/gst.fish",
+                "fish/functions/mkcd.fish"
+            ]
+        );
+        assert!(!files[0].contents.contains("function"));
+        assert_eq!(
+            files[2].contents,
+            "# generated\n\nfunction mkcd --description 'Make'\n    mkdir -p $argv[1]\nend\n"
+        );
+        assert_eq!(
+            files_edn(&files[1..2]),
+            r##"{"fish/functions/gst.fish" "# generated\n\nfunction gst\n    git status\nend\n"}"##
+        );
+    }
+
+    #[test]
+    fn test_autoload_keeps_event_handlers() {
+        let config = edn::read_string(
+            r#"{:functions {:onpwd {:body "ls" :on-variable "PWD"} :gst "git status"}
+                :autoload [:functions]}"#,
+        )
+        .unwrap();
+        let files = fish_files(config);
+        let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["fish/config.fish", "fish/functions/gst.fish"]);
+        assert_eq!(
+            files[0].contents,
+            "\n# Functions\nfunction onpwd --on-variable PWD\n    ls\nend\n\n"
+        );
+    }
+
+    #[test]
+    fn test_completions() {
+        let config = edn::read_string(
+            r#"{:completions {:deploy [{:no-files true}
+                                       {:short "h" :long "help" :description "Show help"}
+                                       {:long "env" :require-parameter true :arguments ["staging" "prod"]}
+                                       {:arguments "(__fish_print_hostnames)" :condition "__fish_use_subcommand"}]}}"#,
+        )
+        .unwrap();
+        let result = fish_config(config);
+
+        assert!(result.contains(
+            "# Completions\n\
+             complete -c deploy -f\n\
+             complete -c deploy -s h -l help -d 'Show help'\n\
+             complete -c deploy -l env -r -a 'staging prod'\n\
+             complete -c deploy -n '__fish_use_subcommand' -a '(__fish_print_hostnames)'\n"
+        ));
+    }
+
+    #[test]
+    fn test_autoload_completions() {
+        let config = edn::read_string(
+            r#"{:completions {:deploy [{:long "help"}]} :autoload [:completions]}"#,
+        )
+        .unwrap();
+        let files = fish_files(config);
+
+        assert!(!files[0].contents.contains("complete"));
+        assert_eq!(
+            files[1],
+            OutputFile {
+                path: "fish/completions/deploy.fish".to_string(),
+                contents: "complete -c deploy -l help\n".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let config = edn::read_string(
+            r#"{:aliases {:ll "ls -la" :pbcopy {:value "xclip -sel clip" :when {:os [:linux :freebsd]}}}
+                :conditional [{:when {:hostname-regex "build-.*" :env {:CI true :TERM "dumb"}}
+                               :env {:JOBS "32"}
+                               :fish ["ulimit -n 4096"]}]}"#,
+        )
+        .unwrap();
+        let result = fish_config(config);
+
+        assert!(result.contains("# Aliases\nalias ll 'ls -la'\n\n"));
+        assert!(result.contains(
+            "if contains -- (uname) Linux FreeBSD\n    \
+             # Aliases\n    \
+             alias pbcopy 'xclip -sel clip'\n\
+             end\n"
+        ));
+        assert!(result.contains(
+            "if string match -qr -- '^(?:build-.*)$' $hostname; and set -q CI; and test \"$TERM\" = 'dumb'\n    \
+             # Environment Variables\n    \
+             set -gx JOBS '32'\n\
+             \n    \
+             # Custom Fish Commands\n    \
+             ulimit -n 4096\n\
+             end\n"
+        ));
+    }
+
+    #[test]
+    fn test_conditional_file() {
+        let config = edn::read_string(
+            r#"{:when {:os :linux :hostname-regex "build-.*"}
+                :env {:JOBS "32"}
+                :paths [{:path "/opt/ci/bin" :when {:env {:CI true}}}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fish_config(config),
+            "\nif test (uname) = Linux; and string match -qr -- '^(?:build-.*)$' $hostname\n    \
+             # Environment Variables\n    \
+             set -gx JOBS '32'\n\
+             \n    \
+             if set -q CI\n        \
+             # PATH additions\n        \
+             fish_add_path /opt/ci/bin\n    \
+             end\n\
+             end\n\n"
+        );
+    }
+
+    #[test]
+    fn test_scopes() {
+        let config = edn::read_string(
+            r#"{:fish-greeting nil
+                :abbrs {:gs "git status"}
+                :env {:EDITOR "nvim"}
+                :prompt {:right [:time]}
+                :scope {:env :login :prompt :always}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fish_config(config),
+            "if status is-interactive\n    set fish_greeting\nend\n\n\
+             if status is-interactive\n    # Abbreviations\n    abbr -a -- gs 'git status'\nend\n\n\
+             if status is-login\n    # Environment Variables\n    set -gx EDITOR 'nvim'\nend\n\n\
+             # Prompt Configuration\n\
+             function fish_right_prompt\n    \
+             set -l separator ''\n    \
+             set -l segment (date +%H:%M)\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator $segment\n        \
+             set separator ' '\n    \
+             end\n\
+             end\n\n"
+        );
+    }
+
+    #[test]
+    fn test_scopes_keep_multiline_values() {
+        let config = edn::read_string(
+            r#"{:fish-greeting "Hello\nWorld"
+                :abbrs {:m "echo one\n\n  two"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fish_config(config),
+            "if status is-interactive\n    set fish_greeting 'Hello\nWorld'\nend\n\n\
+             if status is-interactive\n    # Abbreviations\n    abbr -a -- m 'echo one\n\n  two'\nend\n\n"
+        );
+    }
+
+    #[test]
+    fn test_key_bindings() {
+        let config = edn::read_string(
+            r#"{:key-bindings {:mode :hybrid
+                              :bindings [{:mode :insert :keys "\\cf" :command "accept-autosuggestion"}
+                                         {:mode :insert :keys "jk" :sets-mode :default :command ["cancel" "repaint-mode"]}
+                                         {:keys "\\cg" :fish "git status; commandline -f repaint"}]}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fish_config(config),
+            "\nif status is-interactive\n    \
+             # Key Bindings\n    \
+             function fish_hybrid_key_bindings --description 'Vi bindings with the emacs bindings in every mode'\n        \
+             for mode in default insert visual\n            \
+             fish_default_key_bindings -M $mode\n        \
+             end\n        \
+             fish_vi_key_bindings --no-erase\n    \
+             end\n    \
+             set -g fish_key_bindings fish_hybrid_key_bindings\n    \
+             function fish_user_key_bindings\n        \
+             bind -M insert \\cf accept-autosuggestion\n        \
+             bind -M insert -m default jk cancel repaint-mode\n        \
+             bind \\cg 'git status; commandline -f repaint'\n    \
+             end\n\
+             end\n\n"
+        );
+    }
+
+    #[test]
+    fn test_prompt_segments() {
+        let config = edn::read_string(
+            r#"{:prompt {:left [{:segment :cwd :color "blue --bold"}
+                               {:segment :git-branch :color "magenta" :prefix "(" :suffix ")" :separator ""}
+                               {:segment :git-dirty :symbol "✗" :color "yellow"}
+                               {:segment :status :color "red" :prefix "[" :suffix "]"}
+                               {:text "❯ "}]
+                        :right [:duration :jobs :venv :hostname]}
+                :scope {:prompt :always}}"#,
+        )
+        .unwrap();
+        let output = fish_config(config);
+
+        assert_eq!(
+            output,
+            "\n# Prompt Configuration\n\
+             function fish_prompt\n    \
+             set -l last_status $status\n    \
+             set -l separator ''\n    \
+             set -l segment (prompt_pwd)\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator (set_color blue --bold) $segment (set_color normal)\n        \
+             set separator ' '\n    \
+             end\n    \
+             set -l segment (fish_git_prompt '%s')\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator (set_color magenta) '(' $segment ')' (set_color normal)\n        \
+             set separator ''\n    \
+             end\n    \
+             set -l segment (command git status --porcelain --ignore-submodules 2>/dev/null | string length -q; and echo '✗')\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator (set_color yellow) $segment (set_color normal)\n        \
+             set separator ' '\n    \
+             end\n    \
+             set -l segment (test $last_status -ne 0; and echo $last_status)\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator (set_color red) '[' $segment ']' (set_color normal)\n        \
+             set separator ' '\n    \
+             end\n    \
+             set -l segment '❯ '\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator $segment\n        \
+             set separator ' '\n    \
+             end\n\
+             end\n\
+             function fish_right_prompt\n    \
+             set -l separator ''\n    \
+             set -l segment (set -q CMD_DURATION; and test $CMD_DURATION -ge 1000; and math -s1 $CMD_DURATION / 1000)s\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator $segment\n        \
+             set separator ' '\n    \
+             end\n    \
+             set -l segment (jobs -q; and jobs -p | count)\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator $segment\n        \
+             set separator ' '\n    \
+             end\n    \
+             set -l segment (set -q VIRTUAL_ENV; and string replace -r '.*/' '' -- $VIRTUAL_ENV)\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator $segment\n        \
+             set separator ' '\n    \
+             end\n    \
+             set -l segment (prompt_hostname)\n    \
+             if test -n \"$segment\"\n        \
+             echo -n -s $separator $segment\n        \
+             set separator ' '\n    \
+             end\n\
+             end\n\n"
+        );
+        assert_eq!(syntax::check(&output), Ok(()));
+    }
+
+    #[test]
+    fn test_colors() {
+        let config = edn::read_string(
+            r##"{:colors {:fish_color_command "blue --bold"
+                         :fish_color_comment ["#6272a4" "--italics"]
+                         :fish_pager_color_progress ["brwhite" "--background=cyan"]}}"##,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fish_config(config),
+            "\nif status is-interactive\n    \
+             # Colors\n    \
+             set -g fish_color_command blue --bold\n    \
+             set -g fish_color_comment '#6272a4' --italics\n    \
+             set -g fish_pager_color_progress brwhite --background=cyan\n\
+             end\n\n"
+        );
+    }
+
+    #[test]
+    fn test_env_options() {
+        let config = edn::read_string(
+            r#"{:env {:EDITOR "nvim"
+                      :LESS ["-R" "--mouse"]
+                      :PAGER nil
+                      :OLD {:value nil :scope :universal}
+                      :fish_color_cwd {:value "green" :scope :universal :export false}
+                      :XDG_DATA_DIRS {:value ["/usr/local/share" "/usr/share"] :path true}
+                      :tmp {:value [] :scope :local}}
+                :unset [:GREP_OPTIONS]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fish_config(config),
+            "\n# Environment Variables\n\
+             set -gx EDITOR 'nvim'\n\
+             set -gx LESS '-R' '--mouse'\n\
+             set -e -U OLD\n\
+             set -e PAGER\n\
+             set -gx --path XDG_DATA_DIRS '/usr/local/share' '/usr/share'\n\
+             set -Uu fish_color_cwd 'green'\n\
+             set -lx tmp\n\
+             set -e GREP_OPTIONS\n\n"
+        );
+    }
+
+    #[test]
+    fn test_path_options() {
+        let config = edn::read_string(
+            r#"{:paths ["~/bin" {:path "/opt/homebrew/bin" :global true :append true :move true}]
+                :path-vars {:MANPATH ["/opt/man" {:path "~/man" :move true :if-exists true}]
+                            :XDG_DATA_DIRS [{:path "~/.nix-profile/share" :append true}]}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fish_config(config),
+            "\n# PATH additions\n\
+             fish_add_path ~/bin\n\
+             fish_add_path --global --append --move /opt/homebrew/bin\n\n\
+             # Path Variables\n\
+             set -q MANPATH; or set -gx MANPATH ''\n\
+             contains -- /opt/man $MANPATH; or set -gx --prepend MANPATH /opt/man\n\
+             if test -d ~/man\n    \
+             if set -l index (contains -i -- ~/man $MANPATH)\n        \
+             set -e MANPATH[$index]\n    \
+             end\n    \
+             set -gx --prepend MANPATH ~/man\n\
+             end\n\
+             contains -- ~/.nix-profile/share $XDG_DATA_DIRS; \
+             or set -gx --path --append XDG_DATA_DIRS ~/.nix-profile/share\n\n"
+        );
+    }
+
     #[test]
     fn test_empty_config() {
         let config = Edn::Map(BTreeMap::new());
@@ -271,6 +565,34 @@ mod tests {
         assert!(result.contains("alias grep 'grep --color=auto'"));
     }
 
+    #[test]
+    fn test_values_with_quotes_are_escaped() {
+        let mut aliases = BTreeMap::new();
+        aliases.insert(
+            Edn::Key("localip"),
+            Edn::Str("hostname -I | awk '{print $1}'"),
+        );
+
+        let mut env_vars = BTreeMap::new();
+        env_vars.insert(Edn::Key("WINPATH"), Edn::Str("C:\\Users"));
+
+        let mut config_map = BTreeMap::new();
+        config_map.insert(Edn::Key("aliases"), Edn::Map(aliases));
+        config_map.insert(Edn::Key("env"), Edn::Map(env_vars));
+        config_map.insert(Edn::Key("fish-greeting"), Edn::Str("It's fish"));
+        config_map.insert(
+            Edn::Key("paths"),
+            Edn::Vector(vec![Edn::Str("~/My Apps/bin")]),
+        );
+
+        let result = fish_config(Edn::Map(config_map));
+
+        assert!(result.contains("alias localip 'hostname -I | awk \\'{print $1}\\''"));
+        assert!(result.contains("set -gx WINPATH 'C:\\\\Users'"));
+        assert!(result.contains("set fish_greeting 'It\\'s fish'"));
+        assert!(result.contains("fish_add_path ~'/My Apps/bin'"));
+    }
+
     #[test]
     fn test_abbreviations() {
         let mut abbrs = BTreeMap::new();
@@ -288,6 +610,25 @@ mod tests {
         assert!(result.contains("abbr -a -- gc 'git commit'"));
     }
 
+    #[test]
+    fn test_abbreviation_options() {
+        let config = edn::read_string(
+            r#"{:abbrs {:gco {:expansion "git checkout %" :position :anywhere :set-cursor true}
+                        :L {:expansion "| less" :position :anywhere}
+                        :dotdot {:regex "^\\.\\.+$" :function "multicd"}
+                        :gcm {:expansion "git commit -m '@'" :set-cursor "@"}}}"#,
+        )
+        .unwrap();
+        let result = fish_config_checked(config).unwrap();
+
+        assert!(
+            result.contains("abbr -a --position anywhere --set-cursor -- gco 'git checkout %'")
+        );
+        assert!(result.contains("abbr -a --position anywhere -- L '| less'"));
+        assert!(result.contains(r"abbr -a --regex '^\\.\\.+$' --function multicd -- dotdot"));
+        assert!(result.contains(r"abbr -a --set-cursor=@ -- gcm 'git commit -m \'@\''"));
+    }
+
     #[test]
     fn test_environment_variables() {
         let mut env_vars = BTreeMap::new();
@@ -369,6 +710,34 @@ mod tests {
         assert!(result.contains("end"));
     }
 
+    #[test]
+    fn test_function_options() {
+        let config = edn::read_string(
+            r#"{:functions {:gco {:body "git checkout $branch"
+                                  :description "Check out a branch"
+                                  :argument-names ["branch"]
+                                  :wraps "git checkout"}
+                            :on_exit {:body "echo bye" :on-event "fish_exit" :on-signal ["SIGHUP" 15]}
+                            :watch {:body "echo $PWD" :on-variable "PWD" :inherit-variable "x" :no-scope-shadowing true}
+                            :plain "echo plain"}}"#,
+        )
+        .unwrap();
+        let result = fish_config_checked(config).unwrap();
+
+        assert!(result.contains(
+            "function gco --description 'Check out a branch' --argument-names branch --wraps 'git checkout'\n    git checkout $branch\nend"
+        ));
+        assert!(
+            result.contains(
+                "function on_exit --on-event fish_exit --on-signal SIGHUP --on-signal 15\n"
+            )
+        );
+        assert!(result.contains(
+            "function watch --on-variable PWD --inherit-variable x --no-scope-shadowing\n"
+        ));
+        assert!(result.contains("function plain\n    echo plain\nend"));
+    }
+
     #[test]
     fn test_raw_fish_commands() {
         let commands = vec![
@@ -390,7 +759,10 @@ mod tests {
     #[test]
     fn test_prompt_configuration() {
         let mut prompt_config = BTreeMap::new();
-        prompt_config.insert(Edn::Key("style"), Edn::Str("robbyrussell"));
+        prompt_config.insert(
+            Edn::Key("left"),
+            Edn::Vector(vec![Edn::Key("cwd"), Edn::Key("git-branch")]),
+        );
         prompt_config.insert(Edn::Key("show-git"), Edn::Bool(true));
 
         let mut config_map = BTreeMap::new();
@@ -400,13 +772,17 @@ mod tests {
         let result = fish_config(config);
 
         assert!(result.contains("# Prompt Configuration"));
-        assert!(result.contains("set -g theme robbyrussell"));
-        assert!(result.contains("set -g fish_prompt_show_git true"));
+        assert!(result.contains("function fish_prompt"));
+        assert!(result.contains("set -l segment (fish_git_prompt '%s')"));
     }
 
     #[test]
     fn test_prompt_configuration_false() {
         let mut prompt_config = BTreeMap::new();
+        prompt_config.insert(
+            Edn::Key("left"),
+            Edn::Vector(vec![Edn::Key("cwd"), Edn::Key("git-dirty")]),
+        );
         prompt_config.insert(Edn::Key("show-git"), Edn::Bool(false));
 
         let mut config_map = BTreeMap::new();
@@ -415,7 +791,8 @@ mod tests {
         let config = Edn::Map(config_map);
         let result = fish_config(config);
 
-        assert!(result.contains("set -g fish_prompt_show_git false"));
+        assert!(result.contains("set -l segment (prompt_pwd)"));
+        assert!(!result.contains("git status"));
     }
 
     #[test]
@@ -495,6 +872,162 @@ mod tests {
         assert!(!result.contains("42"));
     }
 
+    #[test]
+    fn test_fish_config_checked_valid() {
+        let mut aliases = BTreeMap::new();
+        aliases.insert(Edn::Key("ll"), Edn::Str("ls -la"));
+
+        let mut config_map = BTreeMap::new();
+        config_map.insert(Edn::Key("aliases"), Edn::Map(aliases));
+
+        let result = fish_config_checked(Edn::Map(config_map)).unwrap();
+        assert!(result.contains("alias ll 'ls -la'"));
+    }
+
+    #[test]
+    fn test_fish_config_checked_reports_invalid_types() {
+        let mut aliases = BTreeMap::new();
+        aliases.insert(Edn::Key("valid"), Edn::Str("ls -la"));
+        aliases.insert(Edn::Key("invalid"), Edn::Int(42));
+
+        let mut config_map = BTreeMap::new();
+        config_map.insert(Edn::Key("aliases"), Edn::Map(aliases));
+
+        let diagnostics = fish_config_checked(Edn::Map(config_map)).unwrap_err();
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::type_mismatch(
+                vec![
+                    Segment::Key("aliases".to_string()),
+                    Segment::Key("invalid".to_string())
+                ],
+                "string",
+                "integer",
+            )]
+        );
+        assert_eq!(diagnostics[0].section(), Some("aliases"));
+    }
+
+    #[test]
+    fn test_fish_config_checked_with_strict() {
+        let mut config_map = BTreeMap::new();
+        config_map.insert(Edn::Key("alias"), Edn::Map(BTreeMap::new()));
+
+        let config = Edn::Map(config_map);
+        assert!(fish_config_checked(config.clone()).is_ok());
+
+        let options = Options {
+            strict: true,
+            ..Options::default()
+        };
+        let diagnostics = fish_config_checked_with(config, &options).unwrap_err();
+        assert_eq!(
+            diagnostics[0].kind,
+            DiagnosticKind::UnknownKey {
+                suggestion: Some("aliases".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn test_layered_config() {
+        let text = r#"[{:aliases {:ll "ls -l" :la "ls -A"} :paths ["/a"]}
+                       {:aliases {:la nil :g 42} :paths ^:replace ["/b"]}]"#;
+        let text = layers::read_markers(text);
+        let config = edn::read_string(&text).unwrap();
+
+        assert_eq!(
+            fish_config(config.clone()),
+            "\n# Aliases\nalias ll 'ls -l'\n\n# PATH additions\nfish_add_path /b\n\n"
+        );
+        assert_eq!(
+            fish_config_checked(config).unwrap_err(),
+            vec![Diagnostic::type_mismatch(
+                vec![
+                    Segment::Index(1),
+                    Segment::Key("aliases".to_string()),
+                    Segment::Key("g".to_string())
+                ],
+                "string",
+                "integer",
+            )]
+        );
+    }
+
+    #[test]
+    fn test_include_needs_reading_first() {
+        let config = edn::read_string(r#"{:include ["git.edn"] :aliases {:ll "ls -l"}}"#).unwrap();
+
+        let diagnostics = fish_config_checked(config.clone()).unwrap_err();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "include: invalid value, included files are only read by the `tilde-fish` command; merge them first"
+            ]
+        );
+        let options = Options {
+            includes_read: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            fish_config_checked_with(config, &options).unwrap(),
+            "\n# Aliases\nalias ll 'ls -l'\n\n"
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config = edn::read_string(
+            r#"{:aliases {:ll "ls -l" :la "ls -A"}
+                :profiles {:work {:aliases {:la nil :gs "git status"} :paths ["~/work/bin"]}
+                           :home {:aliases {:ll 42}}}}"#,
+        )
+        .unwrap();
+        let work = Options {
+            profile: Some("work".to_string()),
+            ..Options::default()
+        };
+        assert_eq!(
+            fish_config_checked_with(config.clone(), &work).unwrap_err(),
+            vec![Diagnostic::type_mismatch(
+                vec![
+                    Segment::Key("profiles".to_string()),
+                    Segment::Key("home".to_string()),
+                    Segment::Key("aliases".to_string()),
+                    Segment::Key("ll".to_string())
+                ],
+                "string",
+                "integer",
+            )]
+        );
+        assert_eq!(
+            fish_files_with(config.clone(), &work)[0].contents,
+            "\n# Aliases\nalias gs 'git status'\nalias ll 'ls -l'\n\n\
+             # PATH additions\nfish_add_path ~/work/bin\n\n"
+        );
+        assert_eq!(
+            fish_config(config.clone()),
+            "\n# Aliases\nalias la 'ls -A'\nalias ll 'ls -l'\n\n"
+        );
+
+        let options = Options {
+            profile: Some("wrok".to_string()),
+            ..Options::default()
+        };
+        let diagnostics = fish_config_checked_with(config, &options).unwrap_err();
+        assert_eq!(
+            diagnostics[0].kind,
+            DiagnosticKind::Unknown {
+                what: "profile",
+                suggestion: Some("work".to_string())
+            }
+        );
+        assert_eq!(diagnostics[0].location(), "profiles.wrok");
+    }
+
     #[test]
     fn test_non_map_config() {
         // Test with non-map config (should return basic config)
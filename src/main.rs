@@ -1,36 +1,707 @@
-use clojure_reader::edn;
-use std::io::Read;
-use tilde_fish::{fish_config, plugin_config};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use clojure_reader::edn::{self, Edn};
+use clojure_reader::error::Code;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tilde_fish::explain::{TOPICS, topic};
+use tilde_fish::layers::{self, Merged};
+use tilde_fish::{
+    COMPLETIONS_DIR, Diagnostic, DiagnosticKind, FUNCTIONS_DIR, FishConfig, Options, Os,
+    OutputFile, Segment, SourceMap, Target, diff, files_edn, fish_files_with, import,
+    plugin_config,
+};
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
+/// Why a run failed; each variant has its own exit status
+#[derive(Debug)]
+enum Error {
+    /// The configuration was read but contained rejected values (exit 1)
+    Invalid {
+        sources: Vec<SourceMap>,
+        /// Each with the index of the source it points into
+        diagnostics: Vec<(usize, Diagnostic)>,
+    },
+    /// The command line wasn't understood (exit 2)
+    Usage(String),
+    /// The input wasn't valid EDN (exit 3)
+    Parse {
+        source: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// Input couldn't be read or output couldn't be written (exit 4)
+    Io {
+        source: String,
+        error: std::io::Error,
+    },
+}
+
+impl Error {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Error::Invalid { .. } => 1,
+            Error::Usage(_) => 2,
+            Error::Parse { .. } => 3,
+            Error::Io { .. } => 4,
+        }
+    }
+
+    fn parse(source: &str, error: clojure_reader::error::Error) -> Self {
+        let message = match error.code {
+            Code::HashMapDuplicateKey => "duplicate map key".to_string(),
+            Code::SetDuplicateKey => "duplicate set element".to_string(),
+            Code::InvalidChar => "invalid character".to_string(),
+            Code::InvalidEscape => "invalid escape sequence".to_string(),
+            Code::InvalidKeyword => "invalid keyword".to_string(),
+            Code::InvalidNumber => "invalid number".to_string(),
+            Code::InvalidRadix(_) => "invalid number radix".to_string(),
+            Code::UnexpectedEOF => "unexpected end of input".to_string(),
+            Code::UnmatchedDelimiter(delimiter) => format!("unmatched `{delimiter}`"),
+            Code::NoFloatFeature => "floating point numbers are not supported".to_string(),
+            code => format!("{code:?}"),
+        };
+        Error::Parse {
+            source: source.to_string(),
+            line: error.line,
+            column: error.column,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Invalid {
+                sources,
+                diagnostics,
+            } => {
+                for (source, diagnostic) in diagnostics {
+                    writeln!(f, "{}\n", sources[*source].report("error", diagnostic))?;
+                }
+                let plural = if diagnostics.len() == 1 { "" } else { "s" };
+                write!(
+                    f,
+                    "{}: {} problem{plural} found",
+                    names(sources),
+                    diagnostics.len()
+                )
+            }
+            Error::Usage(usage) => write!(f, "{}", usage.trim_end()),
+            Error::Parse {
+                source,
+                line,
+                column,
+                message,
+            } => match (line, column) {
+                (Some(line), Some(column)) => {
+                    write!(f, "error: {source}:{line}:{column}: invalid EDN, {message}")
+                }
+                _ => write!(f, "error: {source}: invalid EDN, {message}"),
+            },
+            Error::Io { source, error } => write!(f, "error: {source}: {error}"),
+        }
+    }
+}
+
+/// Reads the named file, or stdin when there is none or it is `-`,
+/// returning the name to report it by along with its contents
+fn read_input(file: Option<&str>) -> Result<(String, String), Error> {
+    match file {
+        Some(file) if file != "-" => match std::fs::read_to_string(file) {
+            Ok(input) => Ok((file.to_string(), input)),
+            Err(error) => Err(Error::Io {
+                source: file.to_string(),
+                error,
+            }),
+        },
+        _ => {
+            let mut input = String::new();
+            match std::io::stdin().read_to_string(&mut input) {
+                Ok(_) => Ok(("<stdin>".to_string(), input)),
+                Err(error) => Err(Error::Io {
+                    source: "<stdin>".to_string(),
+                    error,
+                }),
+            }
+        }
+    }
+}
+
+fn read_edn<'i>(source: &str, input: &'i str) -> Result<Edn<'i>, Error> {
+    edn::read_string(input).map_err(|error| Error::parse(source, error))
+}
+
+/// An EDN configuration named on the command line, or included by one
+struct Input {
+    source: SourceMap,
+    /// The text with its `^:replace` markers made readable
+    edn: String,
+    /// The `:colors :theme` files it names, each with its text or the
+    /// error reading it
+    themes: Vec<(String, Result<String, String>)>,
+}
+
+/// Reads the `file` arguments in order, or stdin when there are none,
+/// each preceded by the files it includes
+fn read_inputs(matches: &ArgMatches) -> Result<Vec<Input>, Error> {
+    let files: Vec<Option<&str>> = match matches.get_many::<String>("file") {
+        Some(files) => files.map(|file| Some(file.as_str())).collect(),
+        None => vec![None],
+    };
+    let mut loader = Loader::default();
+    for file in files {
+        let (name, text) = read_input(file)?;
+        let path = file
+            .filter(|file| *file != "-")
+            .and_then(|file| std::fs::canonicalize(file).ok());
+        loader.load(name, text, path)?;
+    }
+    Ok(loader.inputs)
+}
+
+/// Reads inputs and, before each, the files its `:include` names
+#[derive(Default)]
+struct Loader {
+    inputs: Vec<Input>,
+    /// Every file read so far, so an included file is only read once
+    read: Vec<PathBuf>,
+    /// The files whose includes are being read, to catch a cycle
+    including: Vec<PathBuf>,
+}
+
+impl Loader {
+    /// Reads an input whose file, if it has one, is at `path`; its
+    /// includes and themes are relative to that file, or to the working
+    /// directory
+    fn load(&mut self, name: String, text: String, path: Option<PathBuf>) -> Result<(), Error> {
+        let mut input = Input {
+            source: SourceMap::new(&name, &text),
+            edn: layers::read_markers(&text).into_owned(),
+            themes: Vec::new(),
+        };
+        let cfg = read_edn(&name, &input.edn)?;
+
+        let dir = match path {
+            Some(_) => Path::new(&name).parent().unwrap_or(Path::new("")),
+            None => Path::new(""),
+        };
+        for (_, file) in layers::themes(&cfg) {
+            let theme = dir.join(expand_home(&file));
+            let text = std::fs::read_to_string(&theme)
+                .map_err(|error| format!("{}: {error}", theme.display()));
+            input.themes.push((file, text));
+        }
+        self.read.extend(path.clone());
+        self.including.extend(path.clone());
+        for (include_path, file) in layers::includes(&cfg) {
+            let file = dir.join(file);
+            let io_error = |error| Error::Io {
+                source: file.display().to_string(),
+                error,
+            };
+            let canonical = std::fs::canonicalize(&file).map_err(io_error)?;
+            if self.including.contains(&canonical) {
+                let mut sources: Vec<SourceMap> = std::mem::take(&mut self.inputs)
+                    .into_iter()
+                    .map(|input| input.source)
+                    .collect();
+                sources.push(input.source);
+                let diagnostic = Diagnostic {
+                    path: include_path,
+                    kind: DiagnosticKind::InvalidValue {
+                        reason: "includes a file that is already including this one",
+                    },
+                };
+                return Err(Error::Invalid {
+                    diagnostics: vec![(sources.len() - 1, diagnostic)],
+                    sources,
+                });
+            }
+            if self.read.contains(&canonical) {
+                continue;
+            }
+            let text = std::fs::read_to_string(&canonical).map_err(io_error)?;
+            self.load(file.display().to_string(), text, Some(canonical))?;
+        }
+        if path.is_some() {
+            self.including.pop();
+        }
+
+        self.inputs.push(input);
+        Ok(())
+    }
+}
+
+/// Expands a leading `~/` to the home directory, as fish would
+fn expand_home(file: &str) -> PathBuf {
+    match (file.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(file),
+    }
+}
+
+/// The inputs' names, for messages about all of them
+fn names(sources: &[SourceMap]) -> String {
+    let names: Vec<&str> = sources.iter().map(SourceMap::name).collect();
+    names.join(", ")
+}
+
+/// The layers of every input, merged in order
+struct Config<'i> {
+    merged: Merged<'i>,
+    /// The input each layer was read from, and its path there
+    layers: Vec<(usize, Vec<Segment>)>,
+}
+
+impl<'i> Config<'i> {
+    fn read(inputs: &'i [Input]) -> Result<Self, Error> {
+        let mut layers = Vec::new();
+        let mut values = Vec::new();
+        for (index, input) in inputs.iter().enumerate() {
+            let cfg = read_edn(input.source.name(), &input.edn)?;
+            for (prefix, layer) in layers::split(&cfg) {
+                layers.push((index, prefix));
+                values.push(layer);
+            }
+        }
+        Ok(Config {
+            merged: layers::merge(values),
+            layers,
+        })
+    }
+
+    /// Reads the merged configuration, pointing each diagnostic into the
+    /// input that gave the value; one without an origin goes to the last
+    fn diagnostics(&self, options: &Options) -> Vec<(usize, Diagnostic)> {
+        let (_, diagnostics) = FishConfig::parse_with(&self.merged.config, options);
+        let last = self.layers.last().map_or(0, |(input, _)| *input);
+        diagnostics
+            .into_iter()
+            .map(
+                |mut diagnostic| match self.merged.origin(&diagnostic.path) {
+                    Some((layer, path)) => {
+                        let (input, prefix) = &self.layers[layer];
+                        diagnostic.path = [prefix.clone(), path].concat();
+                        (*input, diagnostic)
+                    }
+                    None => (last, diagnostic),
+                },
+            )
+            .collect()
+    }
+}
+
+/// Writes the output of a command to `--output`, or stdout if not given
+fn write_output(matches: &ArgMatches, output: &str) -> Result<(), Error> {
+    let output = if output.ends_with('\n') {
+        output.to_string()
+    } else {
+        format!("{output}\n")
+    };
+    if let Some(path) = matches.try_get_one::<String>("output").ok().flatten() {
+        return std::fs::write(path, output).map_err(|error| Error::Io {
+            source: path.clone(),
+            error,
+        });
+    }
+    let mut stdout = std::io::stdout().lock();
+    match stdout
+        .write_all(output.as_bytes())
+        .and_then(|()| stdout.flush())
+    {
+        // The reader, e.g. `head`, has all it wants
+        Err(error) if error.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|error| Error::Io {
+            source: "<stdout>".to_string(),
+            error,
+        }),
+    }
+}
+
+fn input_arg(help: &'static str) -> Arg {
+    Arg::new("file").value_name("FILE").help(help)
+}
+
+fn edn_input_arg() -> Arg {
+    input_arg(
+        "EDN configurations to read and merge, later ones overriding earlier ones; \
+         standard input if omitted or `-`",
+    )
+    .num_args(0..)
+    .action(ArgAction::Append)
+}
+
+fn profile_arg() -> Arg {
+    Arg::new("profile")
+        .long("profile")
+        .value_name("NAME")
+        .help("Lay the `:profiles` entry NAME over the configuration [env: TILDE_FISH_PROFILE]")
+}
+
+fn strict_arg() -> Arg {
+    Arg::new("strict")
+        .long("strict")
+        .action(ArgAction::SetTrue)
+        .help("Also reject keys that aren't part of the configuration format")
+}
+
+/// `--resolve`, and the `--os` and `--hostname` overrides that imply it
+fn resolve_args() -> [Arg; 3] {
+    [
+        Arg::new("resolve")
+            .long("resolve")
+            .action(ArgAction::SetTrue)
+            .help("Decide `:when` conditions for this machine instead of writing `if` blocks"),
+        Arg::new("os")
+            .long("os")
+            .value_name("OS")
+            .value_parser(Os::ALL.map(Os::keyword))
+            .help("Resolve conditions for OS instead of this machine's system"),
+        Arg::new("hostname")
+            .long("hostname")
+            .value_name("NAME")
+            .help("Resolve conditions for host NAME instead of this machine's name"),
+    ]
+}
+
+/// The options for reading a configuration from the command line
+/// The options the command line gives, with the theme files `inputs` read
+fn options(matches: &ArgMatches, inputs: &[Input]) -> Options {
+    let os = matches.try_get_one::<String>("os").ok().flatten();
+    let hostname = matches.try_get_one::<String>("hostname").ok().flatten();
+    let resolve = matches!(matches.try_get_one::<bool>("resolve"), Ok(Some(true)));
+    let target = (resolve || os.is_some() || hostname.is_some()).then(|| {
+        let mut target = Target::current();
+        if let Some(os) = os {
+            target.os = Os::from_keyword(os);
+        }
+        if let Some(hostname) = hostname {
+            target.hostname = hostname.clone();
+        }
+        target
+    });
+    // An empty variable selects no profile, like an unset one
+    let profile = matches
+        .get_one::<String>("profile")
+        .cloned()
+        .or_else(|| std::env::var("TILDE_FISH_PROFILE").ok())
+        .filter(|profile| !profile.is_empty());
+    Options {
+        strict: matches.get_flag("strict"),
+        target,
+        profile,
+        includes_read: true,
+        // A later input naming the same theme is the one merged last
+        themes: inputs
+            .iter()
+            .flat_map(|input| input.themes.iter().cloned())
+            .collect(),
+    }
+}
+
+fn output_arg() -> Arg {
+    Arg::new("output")
+        .short('o')
+        .long("output")
+        .value_name("PATH")
+        .help("Write to PATH instead of standard output")
+}
+
+fn cli() -> Command {
+    Command::new("tilde-fish")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Generates fish shell configuration from EDN")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .after_help(
+            "As a tilde plugin, `--config`, `--gen-config [--strict]`, `--gen-functions` and\n\
+             `--gen-completions` are accepted in place of `config`, `gen`, `gen --functions` and\n\
+             `gen --completions`.",
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Print the tilde plugin configuration")
+                .arg(output_arg()),
+        )
+        .subcommand(
+            Command::new("gen")
+                .about("Generate config.fish from an EDN configuration")
+                .long_about(
+                    "Generate config.fish from an EDN configuration.\n\n\
+                     Values that can't be used are left out and reported as warnings, unless \
+                     --strict is given, in which case nothing is written. A `:when` where \
+                     conditions aren't supported, or a --profile that doesn't exist, is always \
+                     an error.\n\n\
+                     Sections listed in `:autoload` are left out of config.fish; --functions \
+                     and --completions print their files as an EDN map of paths to contents, \
+                     and --output-dir writes config.fish and every other file under a \
+                     directory.",
+                )
+                .arg(edn_input_arg())
+                .arg(profile_arg())
+                .arg(strict_arg())
+                .args(resolve_args())
+                .arg(
+                    Arg::new("functions")
+                        .long("functions")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the autoloaded function files instead of config.fish"),
+                )
+                .arg(
+                    Arg::new("completions")
+                        .long("completions")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("functions")
+                        .help("Print the autoloaded completion files instead of config.fish"),
+                )
+                .arg(
+                    Arg::new("output-dir")
+                        .long("output-dir")
+                        .value_name("DIR")
+                        .conflicts_with_all(["output", "functions", "completions"])
+                        .help("Write fish/config.fish and any autoloaded files under DIR"),
+                )
+                .arg(output_arg()),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Validate an EDN configuration without generating it")
+                .long_about(
+                    "Validate an EDN configuration without generating it.\n\n\
+                     Every problem is reported, and the exit status is 1 if there was any.",
+                )
+                .arg(edn_input_arg())
+                .arg(profile_arg())
+                .arg(strict_arg()),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Show how the generated config.fish differs from an existing one")
+                .arg(edn_input_arg())
+                .arg(profile_arg())
+                .arg(strict_arg())
+                .args(resolve_args())
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .value_name("PATH")
+                        .help("The file to compare with [default: ~/.config/fish/config.fish]"),
+                )
+                .arg(output_arg()),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Convert an existing config.fish to an EDN configuration")
+                .arg(input_arg(
+                    "config.fish to read; standard input if omitted or `-`",
+                ))
+                .arg(output_arg()),
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Describe a configuration section, or list them all")
+                .arg(Arg::new("section").value_name("SECTION"))
+                .arg(output_arg()),
+        )
+}
+
+/// Reads and generates an EDN configuration, reporting skipped values as
+/// warnings, or failing on any of them with `--strict` and on fatal ones
+/// without it
+///
+/// The first file is always `config.fish`.
+fn generate(matches: &ArgMatches) -> Result<Vec<OutputFile>, Error> {
+    let inputs = read_inputs(matches)?;
+    let config = Config::read(&inputs)?;
 
-    if args.len() > 1 && args[1] == "--config" {
-        let config = plugin_config();
-        println!("{config}");
-        return;
+    let options = options(matches, &inputs);
+    let diagnostics = config.diagnostics(&options);
+    if (options.strict && !diagnostics.is_empty())
+        || diagnostics
+            .iter()
+            .any(|(_, diagnostic)| diagnostic.is_fatal())
+    {
+        return Err(Error::Invalid {
+            sources: inputs.into_iter().map(|input| input.source).collect(),
+            diagnostics,
+        });
     }
 
-    if args.len() > 1 && args[1] == "--gen-config" {
-        // Read from stdin
-        let mut input = String::new();
-        std::io::stdin()
-            .read_to_string(&mut input)
-            .expect("Failed to read from stdin");
+    // Report skipped entries without failing the generation
+    for (input, diagnostic) in &diagnostics {
+        eprintln!("{}\n", inputs[*input].source.report("warning", diagnostic));
+    }
+
+    Ok(fish_files_with(config.merged.config, &options))
+}
+
+/// Writes every generated file under `dir`, creating directories as needed
+fn write_files(dir: &str, files: &[OutputFile]) -> Result<(), Error> {
+    for file in files {
+        let path = PathBuf::from(dir).join(&file.path);
+        let io_error = |error| Error::Io {
+            source: path.display().to_string(),
+            error,
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        std::fs::write(&path, &file.contents).map_err(io_error)?;
+    }
+    Ok(())
+}
+
+fn check(matches: &ArgMatches) -> Result<(), Error> {
+    let inputs = read_inputs(matches)?;
+    let config = Config::read(&inputs)?;
+
+    // Every diagnostic is an error here, since generation would drop the value
+    let diagnostics = config.diagnostics(&options(matches, &inputs));
+    let sources: Vec<SourceMap> = inputs.into_iter().map(|input| input.source).collect();
+    if !diagnostics.is_empty() {
+        return Err(Error::Invalid {
+            sources,
+            diagnostics,
+        });
+    }
+    write_output(matches, &format!("{}: ok", names(&sources)))
+}
 
-        // Parse the EDN
-        let cfg = edn::read_string(&input).expect("Failed to parse EDN from stdin");
+/// The config.fish fish reads by default
+fn default_target() -> PathBuf {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_default();
+    config_home.join("fish").join("config.fish")
+}
+
+fn diff(matches: &ArgMatches) -> Result<(), Error> {
+    let generated = generate(matches)?.swap_remove(0).contents;
+    let target = matches
+        .get_one::<String>("target")
+        .map_or_else(default_target, PathBuf::from);
+    let name = target.display().to_string();
+    // A missing file is shown as entirely added
+    let existing = match std::fs::read_to_string(&target) {
+        Ok(existing) => existing,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(error) => {
+            return Err(Error::Io {
+                source: name,
+                error,
+            });
+        }
+    };
+    let changes = diff::unified(&name, "generated", &existing, &generated);
+    if changes.is_empty() {
+        eprintln!("{name} is up to date");
+        return Ok(());
+    }
+    write_output(matches, &changes)
+}
+
+fn explain(matches: &ArgMatches) -> Result<(), Error> {
+    let Some(name) = matches.get_one::<String>("section") else {
+        let width = TOPICS
+            .iter()
+            .map(|topic| topic.name.len())
+            .max()
+            .unwrap_or(0);
+        let mut list = String::from("Configuration sections:\n");
+        for topic in TOPICS {
+            list.push_str(&format!("  :{:width$}  {}\n", topic.name, topic.summary));
+        }
+        list.push_str("\nRun `tilde-fish explain <SECTION>` for details.");
+        return write_output(matches, &list);
+    };
+    let Some(topic) = topic(name) else {
+        return Err(Error::Usage(format!(
+            "error: no section named `{name}`; run `tilde-fish explain` to list them"
+        )));
+    };
+    write_output(
+        matches,
+        &format!(":{} - {}\n\n{}", topic.name, topic.summary, topic.details),
+    )
+}
 
-        let config = fish_config(cfg);
-        println!("{config}");
-        return;
+fn run(mut args: Vec<String>) -> Result<(), Error> {
+    // The flags tilde invokes plugins with
+    if let Some(first) = args.get_mut(1) {
+        match first.as_str() {
+            "--config" => *first = "config".to_string(),
+            "--gen-config" => *first = "gen".to_string(),
+            "--gen-functions" => {
+                *first = "gen".to_string();
+                args.insert(2, "--functions".to_string());
+            }
+            "--gen-completions" => {
+                *first = "gen".to_string();
+                args.insert(2, "--completions".to_string());
+            }
+            "--check" => *first = "check".to_string(),
+            _ => {}
+        }
     }
 
-    if args.len() < 3 {
-        eprintln!(
-            "Usage: {} [pattern] [path] or {} --config or {} --gen-config < input.edn",
-            args[0], args[0], args[0]
-        );
-        std::process::exit(1);
+    let matches = match cli().try_get_matches_from(args) {
+        Ok(matches) => matches,
+        Err(error) if !error.use_stderr() => {
+            // --help and --version
+            print!("{}", error.render());
+            return Ok(());
+        }
+        Err(error) => return Err(Error::Usage(error.render().to_string())),
+    };
+
+    match matches.subcommand() {
+        Some(("config", matches)) => write_output(matches, &plugin_config()),
+        Some(("gen", matches)) => {
+            let mut files = generate(matches)?;
+            if let Some(dir) = matches.get_one::<String>("output-dir") {
+                write_files(dir, &files)
+            } else if let Some(dir) = [
+                ("functions", FUNCTIONS_DIR),
+                ("completions", COMPLETIONS_DIR),
+            ]
+            .into_iter()
+            .find_map(|(flag, dir)| matches.get_flag(flag).then_some(dir))
+            {
+                let files: Vec<OutputFile> = files
+                    .into_iter()
+                    .filter(|file| {
+                        file.path
+                            .strip_prefix(dir)
+                            .is_some_and(|name| name.starts_with('/'))
+                    })
+                    .collect();
+                write_output(matches, &files_edn(&files))
+            } else {
+                write_output(matches, &files.swap_remove(0).contents)
+            }
+        }
+        Some(("check", matches)) => check(matches),
+        Some(("diff", matches)) => diff(matches),
+        Some(("import", matches)) => {
+            let (_, input) = read_input(matches.get_one::<String>("file").map(String::as_str))?;
+            write_output(matches, &import::from_fish(&input))
+        }
+        Some(("explain", matches)) => explain(matches),
+        _ => unreachable!("clap requires a subcommand"),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::from(error.exit_code())
+        }
     }
 }
@@ -0,0 +1,342 @@
+//! Identifier rules for the names fish accepts in each section
+//!
+//! Each check returns the reason a name is rejected, for use in a
+//! [`DiagnosticKind::InvalidName`](crate::DiagnosticKind::InvalidName).
+
+/// Words `function` refuses as a name, since fish parses them as syntax or
+/// must never be shadowed
+const RESERVED: &[&str] = &[
+    "!", "[", "and", "begin", "break", "builtin", "case", "command", "continue", "else", "end",
+    "eval", "exec", "for", "function", "if", "not", "or", "return", "set", "switch", "test",
+    "time", "while",
+];
+
+/// Variable names: letters, digits and underscores, not starting with a
+/// digit so the variable can be exported to other programs
+pub fn variable(name: &str) -> Result<(), &'static str> {
+    let Some(first) = name.chars().next() else {
+        return Err("variable names cannot be empty");
+    };
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err("variable names may only contain letters, digits and underscores");
+    }
+    if first.is_ascii_digit() {
+        return Err("variable names cannot start with a digit");
+    }
+    Ok(())
+}
+
+/// Variables in `:path-vars`, which are variable names other than `PATH`,
+/// since `fish_add_path` manages that through `:paths`
+pub(crate) fn path_variable(name: &str) -> Result<(), &'static str> {
+    variable(name)?;
+    if name == "PATH" {
+        return Err("`PATH` is managed with `:paths`");
+    }
+    Ok(())
+}
+
+/// The input functions `bind` runs instead of a command, from
+/// `bind --function-names`
+pub(crate) const BIND_FUNCTIONS: &[&str] = &[
+    "accept-autosuggestion",
+    "and",
+    "backward-bigword",
+    "backward-char",
+    "backward-delete-char",
+    "backward-jump",
+    "backward-jump-till",
+    "backward-kill-bigword",
+    "backward-kill-line",
+    "backward-kill-path-component",
+    "backward-kill-word",
+    "backward-word",
+    "begin-selection",
+    "begin-undo-group",
+    "beginning-of-buffer",
+    "beginning-of-history",
+    "beginning-of-line",
+    "cancel",
+    "cancel-commandline",
+    "capitalize-word",
+    "clear-screen",
+    "complete",
+    "complete-and-search",
+    "delete-char",
+    "delete-or-exit",
+    "down-line",
+    "downcase-word",
+    "end-of-buffer",
+    "end-of-history",
+    "end-of-line",
+    "end-of-selection",
+    "end-undo-group",
+    "execute",
+    "exit",
+    "expand-abbr",
+    "force-repaint",
+    "forward-bigword",
+    "forward-char",
+    "forward-jump",
+    "forward-jump-till",
+    "forward-single-char",
+    "forward-word",
+    "history-pager",
+    "history-pager-delete",
+    "history-prefix-search-backward",
+    "history-prefix-search-forward",
+    "history-search-backward",
+    "history-search-forward",
+    "history-token-search-backward",
+    "history-token-search-forward",
+    "insert-line-over",
+    "insert-line-under",
+    "jump-till-matching-bracket",
+    "jump-to-matching-bracket",
+    "kill-bigword",
+    "kill-inner-line",
+    "kill-line",
+    "kill-selection",
+    "kill-whole-line",
+    "kill-word",
+    "nextd-or-forward-word",
+    "or",
+    "pager-toggle-search",
+    "prevd-or-backward-word",
+    "redo",
+    "repaint",
+    "repaint-mode",
+    "repeat-jump",
+    "repeat-jump-reverse",
+    "scrollback-push",
+    "self-insert",
+    "self-insert-notfirst",
+    "suppress-autosuggestion",
+    "swap-selection-start-stop",
+    "togglecase-char",
+    "togglecase-selection",
+    "transpose-chars",
+    "transpose-words",
+    "undo",
+    "up-line",
+    "upcase-word",
+    "yank",
+    "yank-pop",
+];
+
+/// Key sequences for `bind`, which are written unquoted so fish decodes
+/// escapes such as `\cf` and `\e`, and so can't contain anything else
+/// fish would interpret
+pub(crate) fn keys(keys: &str) -> Result<(), &'static str> {
+    if keys.is_empty() {
+        return Err("key sequences cannot be empty");
+    }
+    if keys.starts_with('-') {
+        return Err("key sequences cannot start with `-`");
+    }
+    if keys
+        .contains(|c: char| c.is_whitespace() || c.is_control() || "$()<>{}|;&'\"#*?~".contains(c))
+    {
+        return Err(
+            "key sequences are written with escapes such as `\\cf` instead of quotes, whitespace or special characters",
+        );
+    }
+    // An odd backslash at the end would escape the newline after it
+    if keys.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1 {
+        return Err("key sequences cannot end with a lone `\\`");
+    }
+    Ok(())
+}
+
+/// Bind modes, named like variables since fish keeps the current one in
+/// `$fish_bind_mode`
+pub(crate) fn bind_mode(mode: &str) -> Result<(), &'static str> {
+    if mode.is_empty() || !mode.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err("bind modes may only contain letters, digits and underscores");
+    }
+    Ok(())
+}
+
+/// Function (and alias) names: any word that is usable as a file name,
+/// doesn't look like an option and isn't a reserved word
+pub fn function(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("function names cannot be empty");
+    }
+    if name.starts_with('-') {
+        return Err("function names cannot start with `-`");
+    }
+    if name.contains(|c: char| c == '/' || c.is_whitespace() || c.is_control()) {
+        return Err("function names cannot contain `/`, whitespace or control characters");
+    }
+    if RESERVED.contains(&name) {
+        return Err("function names cannot be a reserved word");
+    }
+    Ok(())
+}
+
+/// Abbreviation names: the word typed at the prompt, so no whitespace
+pub fn abbr(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("abbreviation names cannot be empty");
+    }
+    if name.contains(|c: char| c.is_whitespace() || c.is_control()) {
+        return Err("abbreviation names cannot contain whitespace or control characters");
+    }
+    Ok(())
+}
+
+/// Event names for `--on-event`, which are emitted by name
+pub(crate) fn event(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("event names cannot be empty");
+    }
+    if name.contains(|c: char| c.is_whitespace() || c.is_control()) {
+        return Err("event names cannot contain whitespace or control characters");
+    }
+    Ok(())
+}
+
+/// Signals for `--on-signal`, by name (`SIGINT` or `INT`) or number
+pub(crate) fn signal(signal: &str) -> Result<(), &'static str> {
+    if signal.is_empty() || !signal.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("signals are given by name, like `SIGINT`, or by number");
+    }
+    Ok(())
+}
+
+/// Jobs for `--on-job-exit`, by PID or as `caller`
+pub(crate) fn job(job: &str) -> Result<(), &'static str> {
+    if job != "caller" && job.parse::<u32>().is_err() {
+        return Err("jobs are given by PID or as `caller`");
+    }
+    Ok(())
+}
+
+/// Host names in `:when {:hostname ...}`
+pub(crate) fn hostname(name: &str) -> Result<(), &'static str> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    {
+        return Err("host names may only contain letters, digits, `-` and `.`");
+    }
+    Ok(())
+}
+
+/// Commands given completions: the word typed at the prompt, which is also
+/// the name of its completions file
+pub(crate) fn command(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("command names cannot be empty");
+    }
+    if name.starts_with('-') {
+        return Err("command names cannot start with `-`");
+    }
+    if name.contains(|c: char| c == '/' || c.is_whitespace() || c.is_control()) {
+        return Err("command names cannot contain `/`, whitespace or control characters");
+    }
+    Ok(())
+}
+
+/// Short options for `complete -s`, a single character without its `-`
+pub(crate) fn short_option(option: &str) -> Result<(), &'static str> {
+    let mut chars = option.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c != '-' && !c.is_whitespace() && !c.is_control() => Ok(()),
+        _ => Err("short options are a single character, without the `-`"),
+    }
+}
+
+/// Long options for `complete -l`, without their leading `--`
+pub(crate) fn long_option(option: &str) -> Result<(), &'static str> {
+    if option.is_empty() || option.starts_with('-') {
+        return Err("long options are given without the leading `--`");
+    }
+    if option.contains(|c: char| c == '=' || c.is_whitespace() || c.is_control()) {
+        return Err("long options cannot contain `=`, whitespace or control characters");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_sequences() {
+        assert!(keys(r"\cf").is_ok());
+        assert!(keys(r"\e\[A").is_ok());
+        assert!(keys("ctrl-alt-f").is_ok());
+        assert!(keys(r"\\").is_ok());
+        assert!(keys("").is_err());
+        assert!(keys("-k").is_err());
+        assert!(keys("'x'").is_err());
+        assert!(keys("a b").is_err());
+        assert!(keys(r"\e\").is_err());
+        assert!(BIND_FUNCTIONS.is_sorted());
+    }
+
+    #[test]
+    fn test_path_variable_names() {
+        assert!(path_variable("MANPATH").is_ok());
+        assert!(path_variable("PATH").is_err());
+        assert!(path_variable("1PATH").is_err());
+    }
+
+    #[test]
+    fn test_variable_names() {
+        assert!(variable("EDITOR").is_ok());
+        assert!(variable("_private_2").is_ok());
+        assert!(variable("").is_err());
+        assert!(variable("1FOO").is_err());
+        assert!(variable("MY-VAR").is_err());
+        assert!(variable("MY VAR").is_err());
+    }
+
+    #[test]
+    fn test_function_names() {
+        assert!(function("mkcd").is_ok());
+        assert!(function("git-clean-branches").is_ok());
+        assert!(function("..").is_ok());
+        assert!(function("").is_err());
+        assert!(function("-h").is_err());
+        assert!(function("my alias").is_err());
+        assert!(function("a/b").is_err());
+        assert!(function("if").is_err());
+    }
+
+    #[test]
+    fn test_abbr_names() {
+        assert!(abbr("gs").is_ok());
+        assert!(abbr("-C").is_ok());
+        assert!(abbr("").is_err());
+        assert!(abbr("g s").is_err());
+    }
+
+    #[test]
+    fn test_handler_names() {
+        assert!(event("fish_prompt").is_ok());
+        assert!(event("my event").is_err());
+        assert!(signal("SIGINT").is_ok());
+        assert!(signal("10").is_ok());
+        assert!(signal("SIG-INT").is_err());
+        assert!(job("caller").is_ok());
+        assert!(job("1234").is_ok());
+        assert!(job("%self").is_err());
+    }
+
+    #[test]
+    fn test_completion_names() {
+        assert!(command("git-town").is_ok());
+        assert!(command("-x").is_err());
+        assert!(command("bin/tool").is_err());
+        assert!(short_option("h").is_ok());
+        assert!(short_option("-h").is_err());
+        assert!(short_option("hv").is_err());
+        assert!(long_option("dry-run").is_ok());
+        assert!(long_option("--dry-run").is_err());
+        assert!(long_option("file=x").is_err());
+    }
+}
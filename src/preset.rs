@@ -0,0 +1,105 @@
+//! The bundled prompts selected with `:prompt {:style ...}`
+//!
+//! Each preset is an ordinary segment prompt, so `:left`, `:right`,
+//! `:separator` and `:show-git` given next to `:style` override its parts.
+
+use crate::config::{Prompt, PromptSegment, SegmentKind};
+
+/// The names `:style` accepts
+pub(crate) const STYLES: &[&str] = &["minimal", "robbyrussell", "two-line", "powerline"];
+
+/// The prompt a style expands to
+pub(crate) fn prompt(style: &str) -> Option<Prompt> {
+    let prompt = match style {
+        // ~/src/tilde ❯
+        "minimal" => Prompt {
+            left: vec![
+                colored(SegmentKind::Cwd, "blue"),
+                colored(SegmentKind::Status, "red"),
+                colored(text("❯ "), "magenta"),
+            ],
+            right: Vec::new(),
+            separator: " ".to_string(),
+        },
+        // ➜  tilde git:(main) ✗
+        "robbyrussell" => Prompt {
+            left: vec![
+                PromptSegment {
+                    suffix: "  ".to_string(),
+                    ..colored(text("➜"), "green --bold")
+                },
+                PromptSegment {
+                    suffix: " ".to_string(),
+                    ..colored(SegmentKind::Cwd, "cyan --bold")
+                },
+                PromptSegment {
+                    prefix: "git:(".to_string(),
+                    suffix: ") ".to_string(),
+                    ..colored(SegmentKind::GitBranch, "blue --bold")
+                },
+                PromptSegment {
+                    suffix: " ".to_string(),
+                    ..colored(SegmentKind::GitDirty("✗".to_string()), "yellow")
+                },
+            ],
+            right: Vec::new(),
+            separator: String::new(),
+        },
+        // ~/src/tilde main * 3.2s
+        // ❯                                  14:05
+        "two-line" => Prompt {
+            left: vec![
+                colored(SegmentKind::Cwd, "cyan --bold"),
+                colored(SegmentKind::GitBranch, "magenta"),
+                colored(SegmentKind::GitDirty("*".to_string()), "yellow"),
+                colored(SegmentKind::Duration, "yellow"),
+                PromptSegment {
+                    separator: Some(String::new()),
+                    ..PromptSegment::new(text("\n"))
+                },
+                colored(SegmentKind::Status, "red"),
+                colored(text("❯ "), "green"),
+            ],
+            right: vec![colored(SegmentKind::Time, "brblack")],
+            separator: " ".to_string(),
+        },
+        // A single bar:  ~/src/tilde  main  *
+        "powerline" => Prompt {
+            left: vec![
+                bar(SegmentKind::Venv, "white"),
+                bar(SegmentKind::Cwd, "brblue"),
+                bar(SegmentKind::GitBranch, "brmagenta"),
+                bar(SegmentKind::GitDirty("±".to_string()), "bryellow"),
+                bar(SegmentKind::Status, "brred"),
+                PromptSegment {
+                    suffix: " ".to_string(),
+                    ..colored(text("\u{e0b0}"), "brblack")
+                },
+            ],
+            right: Vec::new(),
+            separator: String::new(),
+        },
+        _ => return None,
+    };
+    Some(prompt)
+}
+
+fn text(text: &str) -> SegmentKind {
+    SegmentKind::Text(text.to_string())
+}
+
+fn colored(kind: SegmentKind, color: &str) -> PromptSegment {
+    PromptSegment {
+        color: color.split_whitespace().map(str::to_string).collect(),
+        ..PromptSegment::new(kind)
+    }
+}
+
+/// A powerline segment: padded, on the bar's background
+fn bar(kind: SegmentKind, color: &str) -> PromptSegment {
+    PromptSegment {
+        prefix: " ".to_string(),
+        suffix: " ".to_string(),
+        ..colored(kind, &format!("{color} --background=brblack"))
+    }
+}
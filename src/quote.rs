@@ -0,0 +1,143 @@
+//! Quoting of values for fish command lines
+//!
+//! Every string the generator places on a fish command line goes through
+//! this module, so that the shell reads back exactly the original value.
+//! Fish cannot pass NUL characters in arguments, so those are the only
+//! values that don't survive a round trip.
+//!
+//! The other way round, `unescape` decodes the escapes of EDN strings as
+//! they are read, so `"a\nb"` is two lines wherever it is given.
+
+/// Characters that have no special meaning anywhere in an unquoted fish word
+fn is_plain(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ':' | ',' | '+' | '@' | '=')
+}
+
+/// Wraps a value in single quotes, escaping `\` and `'`
+///
+/// Inside single quotes fish only interprets `\\` and `\'`, so this is safe
+/// for any value, including ones spanning several lines.
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for c in value.chars() {
+        if c == '\\' || c == '\'' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Returns the value as a bare word when it contains nothing fish would
+/// interpret, and single-quoted otherwise
+pub fn escape(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(is_plain) {
+        value.to_string()
+    } else {
+        quote(value)
+    }
+}
+
+/// Like [`escape`], but leaves a leading `~` or `~user` unquoted so fish
+/// still expands it to a home directory
+pub fn escape_path(path: &str) -> String {
+    if let Some(rest) = path.strip_prefix('~') {
+        let user_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(rest.len());
+        let (user, rest) = rest.split_at(user_len);
+        if rest.is_empty() || rest.starts_with('/') {
+            let mut escaped = format!("~{user}");
+            if !rest.is_empty() {
+                escaped.push_str(&escape(rest));
+            }
+            return escaped;
+        }
+    }
+    escape(path)
+}
+
+/// Decodes the escape sequences of an EDN string, which the reader leaves in
+/// place
+///
+/// Unknown escapes are kept as written, since the reader only lets them
+/// through for values built in code.
+pub(crate) fn unescape(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let rest = chars.as_str();
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('b') => value.push('\u{8}'),
+            Some('f') => value.push('\u{c}'),
+            Some(escaped @ ('\\' | '"')) => value.push(escaped),
+            Some('u')
+                if let Some(code) = rest.get(1..5)
+                    && let Ok(code) = u32::from_str_radix(code, 16)
+                    && let Some(decoded) = char::from_u32(code) =>
+            {
+                value.push(decoded);
+                chars.nth(3);
+            }
+            _ => {
+                value.push('\\');
+                chars = rest.chars();
+            }
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("ls -la"), "'ls -la'");
+        assert_eq!(quote("awk '{print $1}'"), r"'awk \'{print $1}\''");
+        assert_eq!(quote(r"C:\path"), r"'C:\\path'");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("nvim"), "nvim");
+        assert_eq!(escape("/usr/local/bin"), "/usr/local/bin");
+        assert_eq!(escape(""), "''");
+        assert_eq!(escape("a b"), "'a b'");
+        assert_eq!(escape("$HOME"), "'$HOME'");
+        assert_eq!(escape("*.rs"), "'*.rs'");
+        assert_eq!(escape("~"), "'~'");
+    }
+
+    #[test]
+    fn test_escape_path() {
+        assert_eq!(escape_path("~/.local/bin"), "~/.local/bin");
+        assert_eq!(escape_path("~"), "~");
+        assert_eq!(escape_path("~alice/bin"), "~alice/bin");
+        assert_eq!(escape_path("~/My Apps/bin"), "~'/My Apps/bin'");
+        assert_eq!(escape_path("/path with spaces"), "'/path with spaces'");
+        assert_eq!(escape_path("~weird name"), "'~weird name'");
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"plain"), "plain");
+        assert_eq!(unescape(r"a\nb\tc"), "a\nb\tc");
+        assert_eq!(unescape(r#"say \"hi\""#), r#"say "hi""#);
+        assert_eq!(unescape(r"C:\\Users"), r"C:\Users");
+        assert_eq!(unescape(r"\u00e9t\u00e9"), "été");
+        assert_eq!(unescape(r"C:\Users"), r"C:\Users");
+        assert_eq!(unescape(r"trailing\"), r"trailing\");
+    }
+}
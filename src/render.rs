@@ -0,0 +1,647 @@
+//! Rendering of a [`FishConfig`] to fish source
+
+use crate::config::{
+    Abbr, AbbrExpansion, AbbrPosition, Completion, CompletionOption, Condition, EnvTest, EnvVar,
+    FishConfig, FishFunction, Greeting, KeyAction, KeyBindings, KeyMode, PathEntry, PathVar,
+    PromptSegment, Scope, SegmentKind, VariableScope, scope, whole_match,
+};
+use crate::quote::{escape, escape_path, quote};
+use crate::syntax;
+use std::collections::BTreeMap;
+
+impl FishConfig {
+    /// Renders the configuration as the contents of a `config.fish` file
+    pub fn to_fish(&self) -> String {
+        self.render(&self.scopes)
+    }
+
+    /// Renders `config.fish` with the given `:scope` overrides, which
+    /// `:conditional` blocks take from the top level
+    fn render(&self, scopes: &BTreeMap<String, Scope>) -> String {
+        let mut output = String::new();
+        let section = |name: &str, code: String| gate(scope(scopes, name), &code);
+
+        if let Some(preamble) = &self.preamble {
+            output.push_str(preamble);
+            output.push('\n');
+        }
+
+        match &self.greeting {
+            Some(Greeting::Message(message)) => output.push_str(&section(
+                "fish-greeting",
+                format!("set fish_greeting {}\n", quote(message)),
+            )),
+            Some(Greeting::Disabled) => {
+                output.push_str(&section("fish-greeting", "set fish_greeting\n".to_string()))
+            }
+            None => {}
+        }
+        output.push('\n');
+
+        for snippet in &self.snippets {
+            output.push_str(&section(
+                &format!("snippet/{}", snippet.name),
+                format!("# {}\n{}\n", snippet.name, snippet.content),
+            ));
+        }
+
+        if let Some(abbrs) = &self.abbrs {
+            let mut code = String::from("# Abbreviations\n");
+            for abbr in abbrs {
+                code.push_str(&abbr_command(abbr));
+                code.push('\n');
+            }
+            output.push_str(&section("abbrs", code + "\n"));
+        }
+
+        if let Some(aliases) = &self.aliases {
+            let mut code = String::from("# Aliases\n");
+            for alias in aliases {
+                code.push_str(&format!(
+                    "alias {} {}\n",
+                    escape(&alias.name),
+                    quote(&alias.command)
+                ));
+            }
+            output.push_str(&section("aliases", code + "\n"));
+        }
+
+        if let Some(env) = &self.env {
+            let mut code = String::from("# Environment Variables\n");
+            for var in env {
+                code.push_str(&set_command(var));
+                code.push('\n');
+            }
+            output.push_str(&section("env", code + "\n"));
+        }
+
+        if let Some(paths) = &self.paths {
+            let mut code = String::from("# PATH additions\n");
+            for entry in paths {
+                code.push_str(&add_path_command(entry));
+                code.push('\n');
+            }
+            output.push_str(&section("paths", code + "\n"));
+        }
+
+        if let Some(path_vars) = &self.path_vars {
+            let mut code = String::from("# Path Variables\n");
+            for var in path_vars {
+                code.push_str(&path_var_commands(var));
+            }
+            output.push_str(&section("path-vars", code + "\n"));
+        }
+
+        // Event handlers stay here when autoloading, so fish sees them
+        let functions: Vec<&FishFunction> = self
+            .functions
+            .iter()
+            .flatten()
+            .filter(|function| !self.autoload_functions || function.handles_events())
+            .collect();
+        if self.functions.is_some() && !(self.autoload_functions && functions.is_empty()) {
+            let mut code = String::from("# Functions\n");
+            for function in functions {
+                code.push_str(&function_definition(function));
+                code.push('\n');
+            }
+            output.push_str(&section("functions", code));
+        }
+
+        if let Some(completions) = &self.completions
+            && !self.autoload_completions
+        {
+            let mut code = String::from("# Completions\n");
+            for completion in completions {
+                code.push_str(&completion_commands(completion));
+            }
+            output.push_str(&section("completions", code + "\n"));
+        }
+
+        for conditional in &self.conditionals {
+            let code = conditional.config.render(scopes);
+            output.push_str(&format!(
+                "if {}\n{}end\n\n",
+                condition_test(&conditional.when),
+                indent(code.trim_matches('\n'))
+            ));
+        }
+
+        if let Some(commands) = &self.fish {
+            let mut code = String::from("# Custom Fish Commands\n");
+            for command in commands {
+                code.push_str(&format!("{command}\n"));
+            }
+            output.push_str(&section("fish", code + "\n"));
+        }
+
+        if let Some(colors) = &self.colors {
+            let mut code = String::from("# Colors\n");
+            for color in colors {
+                code.push_str(&format!("set -g {}", color.variable));
+                for word in &color.spec {
+                    code.push(' ');
+                    code.push_str(&escape(word));
+                }
+                code.push('\n');
+            }
+            output.push_str(&section("colors", code + "\n"));
+        }
+
+        if let Some(prompt) = &self.prompt
+            && !(prompt.left.is_empty() && prompt.right.is_empty())
+        {
+            let mut code = String::from("# Prompt Configuration\n");
+            for (name, segments) in [
+                ("fish_prompt", &prompt.left),
+                ("fish_right_prompt", &prompt.right),
+            ] {
+                if !segments.is_empty() {
+                    code.push_str(&prompt_function(name, segments, &prompt.separator));
+                }
+            }
+            output.push_str(&section("prompt", code + "\n"));
+        }
+
+        if let Some(key_bindings) = &self.key_bindings {
+            let code = format!("# Key Bindings\n{}", key_binding_commands(key_bindings));
+            output.push_str(&section("key-bindings", code + "\n"));
+        }
+
+        output
+    }
+
+    /// Renders the configuration as every file it needs, starting with
+    /// `config.fish`
+    ///
+    /// With `:autoload [:functions]`, each function gets its own file under
+    /// `fish/functions/`, where fish loads it the first time it is called;
+    /// event handlers stay in `config.fish`, since fish must define them for
+    /// the events to reach them.
+    /// Likewise `:completions` go under `fish/completions/`, loaded the first
+    /// time their command is completed.
+    pub fn to_files(&self) -> Vec<OutputFile> {
+        let mut files = vec![OutputFile {
+            path: CONFIG_FILE.to_string(),
+            contents: self.to_fish(),
+        }];
+        if self.autoload_functions {
+            for function in self.functions.iter().flatten() {
+                if function.handles_events() {
+                    continue;
+                }
+                files.push(self.autoload_file(
+                    FUNCTIONS_DIR,
+                    &function.name,
+                    function_definition(function),
+                ));
+            }
+        }
+        if self.autoload_completions {
+            for completion in self.completions.iter().flatten() {
+                files.push(self.autoload_file(
+                    COMPLETIONS_DIR,
+                    &completion.command,
+                    completion_commands(completion),
+                ));
+            }
+        }
+        files
+    }
+
+    /// `<dir>/<name>.fish`, with the preamble before `code`
+    fn autoload_file(&self, dir: &str, name: &str, code: String) -> OutputFile {
+        let mut contents = String::new();
+        if let Some(preamble) = &self.preamble {
+            contents.push_str(preamble);
+            contents.push('\n');
+        }
+        contents.push_str(&code);
+        OutputFile {
+            path: format!("{dir}/{name}.fish"),
+            contents,
+        }
+    }
+}
+
+/// Where `config.fish` is written, relative to the tilde output directory
+pub const CONFIG_FILE: &str = "fish/config.fish";
+
+/// The directory fish autoloads functions from, relative to the tilde output
+/// directory
+pub const FUNCTIONS_DIR: &str = "fish/functions";
+
+/// The directory fish autoloads completions from, relative to the tilde
+/// output directory
+pub const COMPLETIONS_DIR: &str = "fish/completions";
+
+/// A generated file and its path relative to the tilde output directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputFile {
+    pub path: String,
+    pub contents: String,
+}
+
+/// `function name [options]`, the indented body and `end`
+fn function_definition(function: &FishFunction) -> String {
+    let mut definition = function_header(function);
+    definition.push('\n');
+    let quoted = syntax::quoted_lines(&function.body);
+    for (number, line) in (1..).zip(function.body.lines()) {
+        if quoted.contains(&number) {
+            definition.push_str(&format!("{line}\n"));
+        } else if !line.trim().is_empty() {
+            definition.push_str(&format!("    {line}\n"));
+        }
+    }
+    definition.push_str("end\n");
+    definition
+}
+
+/// Wraps a section in the `if status ...` block for its scope, keeping
+/// the blank lines after it outside
+fn gate(scope: Scope, code: &str) -> String {
+    let test = match scope {
+        Scope::Always => return code.to_string(),
+        Scope::Interactive => "status is-interactive",
+        Scope::Login => "status is-login",
+    };
+    let body = code.trim_end_matches('\n');
+    if body.is_empty() {
+        return code.to_string();
+    }
+    format!("if {test}\n{}end{}", indent(body), &code[body.len()..])
+}
+
+/// A prompt function writing each segment that has something to show,
+/// after the separator of the one before it
+fn prompt_function(name: &str, segments: &[PromptSegment], separator: &str) -> String {
+    let mut body = String::new();
+    if segments
+        .iter()
+        .any(|segment| segment.kind == SegmentKind::Status)
+    {
+        // Read before any command in the prompt replaces it
+        body.push_str("set -l last_status $status\n");
+    }
+    body.push_str("set -l separator ''\n");
+    for segment in segments {
+        let mut words = vec!["$separator".to_string()];
+        if !segment.color.is_empty() {
+            let spec: Vec<String> = segment.color.iter().map(|word| escape(word)).collect();
+            words.push(format!("(set_color {})", spec.join(" ")));
+        }
+        if !segment.prefix.is_empty() {
+            words.push(quote_inline(&segment.prefix));
+        }
+        words.push("$segment".to_string());
+        if !segment.suffix.is_empty() {
+            words.push(quote_inline(&segment.suffix));
+        }
+        if !segment.color.is_empty() {
+            words.push("(set_color normal)".to_string());
+        }
+        let next_separator = segment.separator.as_deref().unwrap_or(separator);
+        body.push_str(&format!(
+            "set -l segment {}\nif test -n \"$segment\"\n{}end\n",
+            segment_value(&segment.kind),
+            indent(&format!(
+                "echo -n -s {}\nset separator {}",
+                words.join(" "),
+                quote_inline(next_separator)
+            ))
+        ));
+    }
+    format!("function {name}\n{}end\n", indent(&body))
+}
+
+/// The fish expression for what a segment shows, empty when there is
+/// nothing to show
+fn segment_value(kind: &SegmentKind) -> String {
+    match kind {
+        SegmentKind::Cwd => "(prompt_pwd)".to_string(),
+        SegmentKind::GitBranch => "(fish_git_prompt '%s')".to_string(),
+        SegmentKind::GitDirty(symbol) => format!(
+            "(command git status --porcelain --ignore-submodules 2>/dev/null | string length -q; and echo {})",
+            quote_inline(symbol)
+        ),
+        SegmentKind::Status => "(test $last_status -ne 0; and echo $last_status)".to_string(),
+        SegmentKind::Duration => {
+            "(set -q CMD_DURATION; and test $CMD_DURATION -ge 1000; and math -s1 $CMD_DURATION / 1000)s"
+                .to_string()
+        }
+        SegmentKind::Jobs => "(jobs -q; and jobs -p | count)".to_string(),
+        SegmentKind::Venv => {
+            "(set -q VIRTUAL_ENV; and string replace -r '.*/' '' -- $VIRTUAL_ENV)".to_string()
+        }
+        SegmentKind::Hostname => "(prompt_hostname)".to_string(),
+        SegmentKind::Time => "(date +%H:%M)".to_string(),
+        SegmentKind::Text(text) => quote_inline(text),
+    }
+}
+
+/// Quotes a value for a line of a function body, writing newlines as `\n`
+/// escapes so that indenting the body can't change the value
+fn quote_inline(value: &str) -> String {
+    if !value.contains('\n') {
+        return quote(value);
+    }
+    value
+        .split('\n')
+        .map(|part| {
+            if part.is_empty() {
+                String::new()
+            } else {
+                quote(part)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\\n")
+}
+
+/// Indents each line of `code` by four spaces, leaving blank lines empty
+/// and lines that continue a quoted string as they are
+fn indent(code: &str) -> String {
+    let quoted = syntax::quoted_lines(code);
+    (1..)
+        .zip(code.lines())
+        .map(|(number, line)| {
+            if line.is_empty() || quoted.contains(&number) {
+                format!("{line}\n")
+            } else {
+                format!("    {line}\n")
+            }
+        })
+        .collect()
+}
+
+/// The commands testing a `:when` condition, joined with `; and`
+fn condition_test(condition: &Condition) -> String {
+    let mut tests = Vec::new();
+    match &condition.os[..] {
+        [] => {}
+        [os] => tests.push(format!("test (uname) = {}", os.uname())),
+        systems => {
+            let names: Vec<&str> = systems.iter().map(|os| os.uname()).collect();
+            tests.push(format!("contains -- (uname) {}", names.join(" ")));
+        }
+    }
+    match &condition.hostname[..] {
+        [] => {}
+        [hostname] => tests.push(format!("test $hostname = {}", escape(hostname))),
+        hostnames => {
+            let names: Vec<String> = hostnames.iter().map(|name| escape(name)).collect();
+            tests.push(format!("contains -- $hostname {}", names.join(" ")));
+        }
+    }
+    if let Some(regex) = &condition.hostname_regex {
+        tests.push(format!(
+            "string match -qr -- {} $hostname",
+            quote(&whole_match(regex))
+        ));
+    }
+    for var in &condition.env {
+        let name = escape(&var.name);
+        tests.push(match &var.test {
+            EnvTest::Set => format!("set -q {name}"),
+            EnvTest::Unset => format!("not set -q {name}"),
+            EnvTest::Equals(value) => format!("test \"${name}\" = {}", quote(value)),
+        });
+    }
+    tests.join("; and ")
+}
+
+/// One `complete -c` line per option spec
+fn completion_commands(completion: &Completion) -> String {
+    completion
+        .options
+        .iter()
+        .map(|option| format!("{}\n", complete_command(&completion.command, option)))
+        .collect()
+}
+
+/// `complete -c command [options]`
+fn complete_command(command: &str, option: &CompletionOption) -> String {
+    let mut line = format!("complete -c {}", escape(command));
+    if let Some(condition) = &option.condition {
+        line.push_str(&format!(" -n {}", quote(condition)));
+    }
+    if let Some(short) = &option.short {
+        line.push_str(&format!(" -s {}", escape(short)));
+    }
+    if let Some(long) = &option.long {
+        line.push_str(&format!(" -l {}", escape(long)));
+    }
+    if option.no_files {
+        line.push_str(" -f");
+    }
+    if option.require_parameter {
+        line.push_str(" -r");
+    }
+    if let Some(arguments) = &option.arguments {
+        line.push_str(&format!(" -a {}", quote(arguments)));
+    }
+    if let Some(description) = &option.description {
+        line.push_str(&format!(" -d {}", quote(description)));
+    }
+    line
+}
+
+/// `set -gx name values...`, with the flags for the variable's options, or
+/// `set -e name` for `nil`
+fn set_command(var: &EnvVar) -> String {
+    let scope = var.scope.map(|scope| match scope {
+        VariableScope::Global => 'g',
+        VariableScope::Universal => 'U',
+        VariableScope::Local => 'l',
+    });
+    let Some(values) = &var.value else {
+        return match scope {
+            Some(scope) => format!("set -e -{scope} {}", escape(&var.name)),
+            None => format!("set -e {}", escape(&var.name)),
+        };
+    };
+    // Without `-u`, a variable inherited from the environment would stay
+    // exported
+    let export = if var.export { 'x' } else { 'u' };
+    let mut command = format!("set -{}{export}", scope.unwrap_or('g'));
+    if var.path {
+        command.push_str(" --path");
+    }
+    command.push_str(&format!(" {}", escape(&var.name)));
+    for value in values {
+        command.push_str(&format!(" {}", quote(value)));
+    }
+    command
+}
+
+/// Vi bindings with the emacs ones added to every mode, as the fish
+/// documentation suggests, since fish has no built-in hybrid mode
+const HYBRID_KEY_BINDINGS: &str = "\
+function fish_hybrid_key_bindings --description 'Vi bindings with the emacs bindings in every mode'
+    for mode in default insert visual
+        fish_default_key_bindings -M $mode
+    end
+    fish_vi_key_bindings --no-erase
+end
+";
+
+/// Selects the built-in bindings for the mode, then defines
+/// `fish_user_key_bindings`, which fish runs after them
+fn key_binding_commands(key_bindings: &KeyBindings) -> String {
+    let mut commands = String::new();
+    let built_in = match key_bindings.mode {
+        None => None,
+        Some(KeyMode::Emacs) => Some("fish_default_key_bindings"),
+        Some(KeyMode::Vi) => Some("fish_vi_key_bindings"),
+        Some(KeyMode::Hybrid) => {
+            commands.push_str(HYBRID_KEY_BINDINGS);
+            Some("fish_hybrid_key_bindings")
+        }
+    };
+    if let Some(built_in) = built_in {
+        commands.push_str(&format!("set -g fish_key_bindings {built_in}\n"));
+    }
+    if key_bindings.bindings.is_empty() {
+        return commands;
+    }
+    commands.push_str("function fish_user_key_bindings\n");
+    for binding in &key_bindings.bindings {
+        let mut command = String::from("bind");
+        if let Some(mode) = &binding.mode {
+            command.push_str(&format!(" -M {mode}"));
+        }
+        if let Some(mode) = &binding.sets_mode {
+            command.push_str(&format!(" -m {mode}"));
+        }
+        // Unquoted, so fish decodes the escapes
+        command.push_str(&format!(" {}", binding.keys));
+        match &binding.action {
+            KeyAction::Functions(functions) => {
+                command.push_str(&format!(" {}", functions.join(" ")))
+            }
+            KeyAction::Fish(code) => command.push_str(&format!(" {}", quote(code))),
+        }
+        commands.push_str(&format!("    {command}\n"));
+    }
+    commands.push_str("end\n");
+    commands
+}
+
+/// `fish_add_path [options] path`
+fn add_path_command(entry: &PathEntry) -> String {
+    let mut command = String::from("fish_add_path");
+    if entry.global {
+        command.push_str(" --global");
+    }
+    if entry.append {
+        command.push_str(" --append");
+    }
+    if entry.move_existing {
+        command.push_str(" --move");
+    }
+    command.push_str(&format!(" {}", escape_path(&entry.path)));
+    command
+}
+
+/// The commands adding each directory of a `:path-vars` list, unless it is
+/// already there
+///
+/// `MANPATH` and `INFOPATH` start from an empty element when unset, which
+/// stands for the system directories that setting them would otherwise
+/// hide; `CDPATH` starts from `.` for the same reason.
+fn path_var_commands(var: &PathVar) -> String {
+    let name = &var.name;
+    // Variables ending in `PATH` are path variables already
+    let path = if name.ends_with("PATH") {
+        ""
+    } else {
+        " --path"
+    };
+    let mut commands = String::new();
+    match name.as_str() {
+        "MANPATH" | "INFOPATH" => {
+            commands.push_str(&format!("set -q {name}; or set -gx {name} ''\n"))
+        }
+        "CDPATH" => commands.push_str("set -q CDPATH; or set -gx CDPATH .\n"),
+        _ => {}
+    }
+    for entry in &var.entries {
+        let directory = escape_path(&entry.path);
+        let position = if entry.append {
+            "--append"
+        } else {
+            "--prepend"
+        };
+        let set = format!("set -gx{path} {position} {name} {directory}");
+        let code = if entry.move_existing {
+            format!(
+                "if set -l index (contains -i -- {directory} ${name})\n    set -e {name}[$index]\nend\n{set}\n"
+            )
+        } else {
+            format!("contains -- {directory} ${name}; or {set}\n")
+        };
+        if entry.if_exists {
+            commands.push_str(&format!("if test -d {directory}\n{}end\n", indent(&code)));
+        } else {
+            commands.push_str(&code);
+        }
+    }
+    commands
+}
+
+/// `abbr -a [options] -- name [expansion]`
+fn abbr_command(abbr: &Abbr) -> String {
+    let mut command = String::from("abbr -a");
+    if abbr.position == AbbrPosition::Anywhere {
+        command.push_str(" --position anywhere");
+    }
+    if let Some(regex) = &abbr.regex {
+        command.push_str(&format!(" --regex {}", quote(regex)));
+    }
+    match abbr.set_cursor.as_deref() {
+        Some("%") => command.push_str(" --set-cursor"),
+        Some(marker) => command.push_str(&format!(" --set-cursor={}", escape(marker))),
+        None => {}
+    }
+    if let AbbrExpansion::Function(function) = &abbr.expansion {
+        command.push_str(&format!(" --function {}", escape(function)));
+    }
+    command.push_str(&format!(" -- {}", escape(&abbr.name)));
+    if let AbbrExpansion::Text(text) = &abbr.expansion {
+        command.push_str(&format!(" {}", quote(text)));
+    }
+    command
+}
+
+/// `function name [options]`
+fn function_header(function: &FishFunction) -> String {
+    let mut header = format!("function {}", escape(&function.name));
+    if let Some(description) = &function.description {
+        header.push_str(&format!(" --description {}", quote(description)));
+    }
+    if !function.argument_names.is_empty() {
+        header.push_str(" --argument-names");
+        for name in &function.argument_names {
+            header.push_str(&format!(" {}", escape(name)));
+        }
+    }
+    if let Some(wraps) = &function.wraps {
+        header.push_str(&format!(" --wraps {}", quote(wraps)));
+    }
+    for (flag, values) in [
+        ("--on-event", &function.on_event),
+        ("--on-variable", &function.on_variable),
+        ("--on-signal", &function.on_signal),
+        ("--on-job-exit", &function.on_job_exit),
+        ("--inherit-variable", &function.inherit_variable),
+    ] {
+        for value in values {
+            header.push_str(&format!(" {flag} {}", escape(value)));
+        }
+    }
+    if function.no_scope_shadowing {
+        header.push_str(" --no-scope-shadowing");
+    }
+    header
+}
@@ -0,0 +1,510 @@
+//! Source locations for diagnostics
+//!
+//! The EDN reader doesn't keep track of where values came from, so
+//! [`SourceMap`] re-scans the input text and records the position of every
+//! map entry and vector element by its [`Segment`] path. Diagnostics are
+//! then matched to the text by their path.
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind, Segment};
+use std::collections::HashMap;
+
+/// A position in the source text, with the number of characters to
+/// underline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// Line number, counting from 1
+    pub line: usize,
+    /// Column number, counting from 1, in chars
+    pub column: usize,
+    /// Characters covered on the first line, at least 1
+    pub width: usize,
+    offset: usize,
+}
+
+/// Where a value and the key naming it were found
+#[derive(Debug, Clone)]
+struct Entry {
+    key: Option<Span>,
+    /// The key as written, such as `:ll` or `"ll"`
+    raw_key: Option<String>,
+    value: Span,
+}
+
+/// The positions of the values in one EDN document
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    name: String,
+    text: String,
+    /// Usually one entry per path, but a keyword and a string key with the
+    /// same name share a path; keywords come first, as the reader sees them
+    entries: HashMap<Vec<Segment>, Vec<Entry>>,
+}
+
+impl SourceMap {
+    /// Scans `text`, which is reported as `name` (usually the file name)
+    ///
+    /// Text that isn't valid EDN is scanned as far as possible.
+    pub fn new(name: &str, text: &str) -> Self {
+        let mut scanner = Scanner {
+            text,
+            offset: 0,
+            line: 1,
+            column: 1,
+            entries: HashMap::new(),
+        };
+        if let Some(span) = scanner.form(Some(&[])) {
+            scanner.entries.insert(
+                Vec::new(),
+                vec![Entry {
+                    key: None,
+                    raw_key: None,
+                    value: span,
+                }],
+            );
+        }
+        SourceMap {
+            name: name.to_string(),
+            text: text.to_string(),
+            entries: scanner.entries,
+        }
+    }
+
+    /// The name the text is reported as
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The location of the value or key a diagnostic is about
+    ///
+    /// Syntax errors point into the string holding the code. When a keyword
+    /// and a string key share the path, a conflict points at the string key,
+    /// which is the one left out, wherever it is in the text.
+    pub fn span(&self, diagnostic: &Diagnostic) -> Option<Span> {
+        let entries = self.entries.get(&diagnostic.path)?;
+        let entry = match &diagnostic.kind {
+            DiagnosticKind::Conflict { with } => entries
+                .iter()
+                .find(|entry| entry.raw_key.as_ref() != Some(with))
+                .unwrap_or(&entries[0]),
+            _ => &entries[0],
+        };
+        match &diagnostic.kind {
+            DiagnosticKind::UnknownKey { .. }
+            | DiagnosticKind::InvalidName { .. }
+            | DiagnosticKind::UnsupportedCondition
+            | DiagnosticKind::Conflict { .. }
+            | DiagnosticKind::TypeMismatch {
+                expected: "keyword or string key",
+                ..
+            } => entry.key.or(Some(entry.value)),
+            DiagnosticKind::Syntax(error) => {
+                Some(self.string_position(entry.value, error.line, error.column))
+            }
+            DiagnosticKind::TypeMismatch { .. }
+            | DiagnosticKind::InvalidValue { .. }
+            | DiagnosticKind::Unreadable { .. }
+            | DiagnosticKind::Unknown { .. } => Some(entry.value),
+        }
+    }
+
+    /// Formats a diagnostic as `name:line:column: severity: message`,
+    /// followed by the offending line with the value underlined
+    pub fn report(&self, severity: &str, diagnostic: &Diagnostic) -> String {
+        let Some(span) = self.span(diagnostic) else {
+            return format!("{}: {severity}: {diagnostic}", self.name);
+        };
+        let line = self.text.lines().nth(span.line - 1).unwrap_or_default();
+        let number = span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // Keep tabs so the caret lines up with the text above it
+        let indent: String = line
+            .chars()
+            .take(span.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!(
+            "{}:{}:{}: {severity}: {diagnostic}\n{gutter} |\n{number} | {line}\n{gutter} | {indent}{}",
+            self.name,
+            span.line,
+            span.column,
+            "^".repeat(span.width)
+        )
+    }
+
+    /// Finds the character at `line` and `column` of the decoded contents of
+    /// the string at `string`, where each escape sequence stands for one char
+    fn string_position(&self, string: Span, line: usize, column: usize) -> Span {
+        let mut position = Span { width: 1, ..string };
+        if !self.text[string.offset..].starts_with('"') {
+            return string;
+        }
+        let (mut decoded_line, mut decoded_column) = (1, 1);
+        let mut chars = self.text[string.offset + 1..].char_indices().peekable();
+        position.column += 1;
+        position.offset += 1;
+        while let Some((index, c)) = chars.next() {
+            if (decoded_line, decoded_column) >= (line, column) || c == '"' {
+                break;
+            }
+            let mut advance = 1;
+            let mut newline = c == '\n';
+            if c == '\\'
+                && let Some(&(_, next)) = chars.peek()
+                && "ntrbf\\\"u".contains(next)
+            {
+                chars.next();
+                advance = 2;
+                newline = next == 'n';
+                if next == 'u' {
+                    // `\uXXXX`; the reader has already checked the digits
+                    chars.nth(3);
+                    advance = 6;
+                }
+            }
+            if newline {
+                decoded_line += 1;
+                decoded_column = 1;
+            } else {
+                decoded_column += 1;
+            }
+            if c == '\n' {
+                position.line += 1;
+                position.column = 1;
+            } else {
+                position.column += advance;
+            }
+            position.offset = string.offset + 1 + index + advance;
+        }
+        position
+    }
+}
+
+/// Walks EDN text form by form, recording each value's position by path
+struct Scanner<'t> {
+    text: &'t str,
+    offset: usize,
+    line: usize,
+    column: usize,
+    /// As in [`SourceMap`]
+    entries: HashMap<Vec<Segment>, Vec<Entry>>,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn here(&self) -> Span {
+        Span {
+            line: self.line,
+            column: self.column,
+            width: 1,
+            offset: self.offset,
+        }
+    }
+
+    /// Skips whitespace, commas, comments and `#_` discarded forms
+    fn skip_blank(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == ',' {
+                self.bump();
+            } else if c == ';' {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.bump();
+                }
+            } else if self.text[self.offset..].starts_with("#_") {
+                self.bump();
+                self.bump();
+                self.form(None);
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Reads one form, recording the entries inside it under `path` unless
+    /// that is `None`, and returns its span; `None` at a closing delimiter or
+    /// the end of input
+    fn form(&mut self, path: Option<&[Segment]>) -> Option<Span> {
+        self.skip_blank();
+        let mut span = self.here();
+        let rest = &self.text[self.offset..];
+        match self.peek()? {
+            ')' | ']' | '}' => return None,
+            '{' => {
+                self.bump();
+                self.map(path);
+            }
+            '[' | '(' => {
+                self.bump();
+                self.sequence(path);
+            }
+            '#' if rest.starts_with("#{") => {
+                self.bump();
+                self.bump();
+                // Set elements have no path of their own
+                while self.form(None).is_some() {}
+                self.bump();
+            }
+            '#' if rest.starts_with("#\"") => {
+                self.bump();
+                span.width = self.string() + 1;
+            }
+            '#' => {
+                // A tag, such as `#replace`, which applies to the form after it
+                self.atom();
+                self.form(path);
+            }
+            '^' => {
+                // Metadata, such as `^:replace`, for the form after it
+                self.bump();
+                self.form(None);
+                return self.form(path);
+            }
+            '"' => span.width = self.string(),
+            _ => span.width = self.atom(),
+        }
+        Some(span)
+    }
+
+    fn map(&mut self, path: Option<&[Segment]>) {
+        loop {
+            self.skip_blank();
+            let key_start = self.offset;
+            let Some(mut key) = self.form(None) else {
+                self.bump();
+                return;
+            };
+            let raw = self.text[key_start..self.offset].trim_end();
+            let name = if let Some(keyword) = raw.strip_prefix(':') {
+                keyword
+            } else if raw.len() >= 2 && raw.starts_with('"') && raw.ends_with('"') {
+                &raw[1..raw.len() - 1]
+            } else {
+                raw
+            };
+            key.width = key.width.max(1);
+            let entry_path = path.map(|path| {
+                let mut entry_path = path.to_vec();
+                entry_path.push(Segment::Key(name.to_string()));
+                entry_path
+            });
+            let Some(value) = self.form(entry_path.as_deref()) else {
+                self.bump();
+                return;
+            };
+            if let Some(entry_path) = entry_path {
+                let keyword = raw.starts_with(':');
+                let entry = Entry {
+                    key: Some(key),
+                    raw_key: Some(raw.to_string()),
+                    value,
+                };
+                let entries = self.entries.entry(entry_path).or_default();
+                let position = if keyword {
+                    entries
+                        .iter()
+                        .position(|entry| {
+                            entry
+                                .raw_key
+                                .as_ref()
+                                .is_some_and(|raw| !raw.starts_with(':'))
+                        })
+                        .unwrap_or(entries.len())
+                } else {
+                    entries.len()
+                };
+                entries.insert(position, entry);
+            }
+        }
+    }
+
+    fn sequence(&mut self, path: Option<&[Segment]>) {
+        let mut index = 0;
+        loop {
+            let item_path = path.map(|path| {
+                let mut item_path = path.to_vec();
+                item_path.push(Segment::Index(index));
+                item_path
+            });
+            let Some(value) = self.form(item_path.as_deref()) else {
+                self.bump();
+                return;
+            };
+            if let Some(item_path) = item_path {
+                self.entries.insert(
+                    item_path,
+                    vec![Entry {
+                        key: None,
+                        raw_key: None,
+                        value,
+                    }],
+                );
+            }
+            index += 1;
+        }
+    }
+
+    /// Reads a string literal, returning its width on its first line
+    fn string(&mut self) -> usize {
+        let line = self.line;
+        let start = self.column;
+        let mut end = start + 1;
+        self.bump();
+        while let Some(c) = self.bump() {
+            if self.line == line {
+                end = self.column;
+            }
+            match c {
+                '\\' => {
+                    self.bump();
+                    if self.line == line {
+                        end = self.column;
+                    }
+                }
+                '"' => break,
+                _ => {}
+            }
+        }
+        (end - start).max(1)
+    }
+
+    /// Reads a symbol, keyword, number or character, returning its width
+    fn atom(&mut self) -> usize {
+        let start = self.column;
+        if self.peek() == Some('\\') {
+            // A character literal may itself be a delimiter, as in `\(`
+            self.bump();
+            self.bump();
+        } else {
+            self.bump();
+        }
+        while self
+            .peek()
+            .is_some_and(|c| !(c.is_whitespace() || "()[]{}\",;".contains(c)))
+        {
+            self.bump();
+        }
+        self.column - start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FishConfig;
+    use clojure_reader::edn;
+
+    fn reports(text: &str) -> Vec<String> {
+        let (_, diagnostics) = FishConfig::parse(&edn::read_string(text).unwrap());
+        let source = SourceMap::new("config.edn", text);
+        diagnostics
+            .iter()
+            .map(|diagnostic| source.report("error", diagnostic))
+            .collect()
+    }
+
+    #[test]
+    fn test_spans_of_values_and_keys() {
+        let text =
+            "{:aliases {:ll 42\n           \"my alias\" \"ls\"}\n :paths [\"/bin\" #_ 1 :usr]}";
+        let source = SourceMap::new("config.edn", text);
+        let entry = |path: &[Segment]| source.entries[path][0].clone();
+        let key = |name: &str| Segment::Key(name.to_string());
+
+        let ll = entry(&[key("aliases"), key("ll")]);
+        assert_eq!((ll.value.line, ll.value.column, ll.value.width), (1, 16, 2));
+        let ll_key = ll.key.unwrap();
+        assert_eq!((ll_key.line, ll_key.column, ll_key.width), (1, 12, 3));
+
+        let alias = entry(&[key("aliases"), key("my alias")]).key.unwrap();
+        assert_eq!((alias.line, alias.column, alias.width), (2, 12, 10));
+
+        let usr = entry(&[key("paths"), Segment::Index(1)]).value;
+        assert_eq!((usr.line, usr.column, usr.width), (3, 22, 4));
+    }
+
+    #[test]
+    fn test_report_with_snippet() {
+        assert_eq!(
+            reports("{:aliases\n {:ll 42}}"),
+            [
+                "config.edn:2:7: error: aliases.ll: expected string, found integer\n  |\n2 |  {:ll 42}}\n  |       ^^"
+            ]
+        );
+        assert_eq!(
+            reports("{:env {\"MY-VAR\" \"x\"}}"),
+            [
+                "config.edn:1:8: error: env.MY-VAR: invalid name, variable names may only contain letters, digits and underscores\n  |\n1 | {:env {\"MY-VAR\" \"x\"}}\n  |        ^^^^^^^^"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors_point_into_strings() {
+        let text = r#"{:functions {:bad "echo ok\necho 'oops"}}"#;
+        let [report] = &reports(text)[..] else {
+            panic!("expected one report");
+        };
+        assert!(report.starts_with("config.edn:1:34: error: functions.bad: unterminated"));
+    }
+
+    #[test]
+    fn test_conflict_points_at_dropped_string_key() {
+        assert_eq!(
+            reports("{:aliases {:ll \"ls -la\"\n           \"ll\" \"ls -l\"}}"),
+            [
+                "config.edn:2:12: error: aliases.ll: conflicts with `:ll`\n  |\n2 |            \"ll\" \"ls -l\"}}\n  |            ^^^^"
+            ]
+        );
+        // The keyword is kept even when the string key comes first
+        assert_eq!(
+            reports("{:aliases {\"ll\" \"ls -l\"\n           :ll \"ls -la\"}}"),
+            [
+                "config.edn:1:12: error: aliases.ll: conflicts with `:ll`\n  |\n1 | {:aliases {\"ll\" \"ls -l\"\n  |            ^^^^"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_point_into_layers() {
+        let text = "[{:paths [\"/bin\"] :aliases {:ll 42}}\n {:paths ^:replace [:usr]}]";
+        let (_, diagnostics) =
+            FishConfig::parse(&edn::read_string(&crate::layers::read_markers(text)).unwrap());
+        let source = SourceMap::new("config.edn", text);
+        let reports: Vec<String> = diagnostics
+            .iter()
+            .map(|diagnostic| source.report("error", diagnostic))
+            .collect();
+        assert_eq!(
+            reports,
+            [
+                "config.edn:1:33: error: [0].aliases.ll: expected string, found integer\n  |\n1 | [{:paths [\"/bin\"] :aliases {:ll 42}}\n  |                                 ^^",
+                "config.edn:2:21: error: [1].paths[0]: expected string or map, found keyword\n  |\n2 |  {:paths ^:replace [:usr]}]\n  |                     ^^^^",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_without_span() {
+        let source = SourceMap::new("config.edn", "{}");
+        let diagnostic =
+            Diagnostic::type_mismatch(vec![Segment::Key("paths".to_string())], "vector", "map");
+        assert_eq!(
+            source.report("warning", &diagnostic),
+            "config.edn: warning: paths: expected vector, found map"
+        );
+    }
+}
@@ -0,0 +1,576 @@
+//! A fish tokenizer and block parser for catching broken code before it
+//! reaches `config.fish`
+//!
+//! [`check`] understands fish's quoting rules, backslash escapes and line
+//! continuations, comments, command substitutions and the block structure
+//! of `function`, `if`, `switch`, `for`, `while` and `begin`. It doesn't
+//! know about individual commands, so it only reports mistakes that would
+//! stop fish from parsing the file at all.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The first syntax error found in a piece of fish code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// Line of the error, counting from 1
+    pub line: usize,
+    /// Column of the error, counting chars from 1
+    pub column: usize,
+    pub kind: SyntaxErrorKind,
+}
+
+/// What kind of syntax error was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxErrorKind {
+    /// A `'` or `"` string with no closing quote; points at the opening quote
+    UnterminatedQuote(char),
+    /// A backslash at the very end of the input
+    UnterminatedEscape,
+    /// A `(` or `$(` command substitution with no closing `)`
+    UnclosedSubstitution,
+    /// A `)` with no command substitution to close
+    UnexpectedCloseParen,
+    /// A block keyword with no matching `end`; points at the keyword
+    UnclosedBlock(&'static str),
+    /// An `end` with no block to close
+    UnexpectedEnd,
+    /// `else` outside `if`, `case` outside `switch`, or `break`/`continue`
+    /// outside a loop
+    MisplacedKeyword(&'static str),
+}
+
+impl fmt::Display for SyntaxErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyntaxErrorKind::UnterminatedQuote('\'') => write!(f, "unterminated single quote"),
+            SyntaxErrorKind::UnterminatedQuote(_) => write!(f, "unterminated double quote"),
+            SyntaxErrorKind::UnterminatedEscape => write!(f, "backslash at end of input"),
+            SyntaxErrorKind::UnclosedSubstitution => {
+                write!(f, "command substitution is missing `)`")
+            }
+            SyntaxErrorKind::UnexpectedCloseParen => write!(f, "unexpected `)`"),
+            SyntaxErrorKind::UnclosedBlock(keyword) => {
+                write!(f, "`{keyword}` block is missing `end`")
+            }
+            SyntaxErrorKind::UnexpectedEnd => write!(f, "`end` without a matching block"),
+            SyntaxErrorKind::MisplacedKeyword(keyword @ ("break" | "continue")) => {
+                write!(f, "`{keyword}` outside of a loop")
+            }
+            SyntaxErrorKind::MisplacedKeyword("case") => write!(f, "`case` outside of `switch`"),
+            SyntaxErrorKind::MisplacedKeyword(keyword) => {
+                write!(f, "`{keyword}` outside of `if`")
+            }
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Checks that `source` is syntactically valid fish
+pub fn check(source: &str) -> Result<(), SyntaxError> {
+    Parser::new(source).script(None)
+}
+
+/// Whether `line` starts with a keyword that opens a block
+pub(crate) fn opens_block(line: &str) -> bool {
+    let keyword = line.split_whitespace().next().unwrap_or_default();
+    Block::ALL.iter().any(|block| block.keyword() == keyword)
+}
+
+/// The numbers of the lines of `source` that start inside a quoted string,
+/// which can't be indented without changing the string
+///
+/// Code that doesn't parse is read up to the error.
+pub(crate) fn quoted_lines(source: &str) -> Vec<usize> {
+    let mut parser = Parser::new(source);
+    // Lines read before an error are still accurate
+    let _ = parser.script(None);
+    parser.quoted_lines
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Function,
+    If,
+    Switch,
+    For,
+    While,
+    Begin,
+}
+
+impl Block {
+    const ALL: [Block; 6] = [
+        Block::Function,
+        Block::If,
+        Block::Switch,
+        Block::For,
+        Block::While,
+        Block::Begin,
+    ];
+
+    fn keyword(self) -> &'static str {
+        match self {
+            Block::Function => "function",
+            Block::If => "if",
+            Block::Switch => "switch",
+            Block::For => "for",
+            Block::While => "while",
+            Block::Begin => "begin",
+        }
+    }
+}
+
+/// An open block and where its keyword was
+struct Open {
+    block: Block,
+    line: usize,
+    column: usize,
+}
+
+/// A word read from the input
+struct Word {
+    text: String,
+    /// True when the word had no quotes, escapes or substitutions, so it
+    /// may be a keyword
+    plain: bool,
+    line: usize,
+    column: usize,
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    /// Lines that started inside a quoted string
+    quoted_lines: Vec<usize>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Parser {
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+            quoted_lines: Vec::new(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    /// Like [`Parser::next`], for a char inside a quoted string
+    fn next_quoted(&mut self) -> Option<char> {
+        let c = self.next()?;
+        if c == '\n' {
+            self.quoted_lines.push(self.line);
+        }
+        Some(c)
+    }
+
+    fn error(&self, line: usize, column: usize, kind: SyntaxErrorKind) -> SyntaxError {
+        SyntaxError { line, column, kind }
+    }
+
+    /// Parses a sequence of statements up to the end of input, or up to the
+    /// `)` closing a command substitution that started at `substitution`
+    fn script(&mut self, substitution: Option<(usize, usize)>) -> Result<(), SyntaxError> {
+        let mut blocks: Vec<Open> = Vec::new();
+        let mut command_position = true;
+        let mut after_else = false;
+
+        loop {
+            match self.peek() {
+                None => {
+                    if let Some((line, column)) = substitution {
+                        return Err(self.error(
+                            line,
+                            column,
+                            SyntaxErrorKind::UnclosedSubstitution,
+                        ));
+                    }
+                    return close_all(&blocks);
+                }
+                Some(' ' | '\t' | '\r') => {
+                    self.next();
+                }
+                Some('\n' | ';') => {
+                    self.next();
+                    command_position = true;
+                    after_else = false;
+                }
+                Some('|') => {
+                    self.next();
+                    if self.peek() == Some('|') {
+                        self.next();
+                    }
+                    command_position = true;
+                    after_else = false;
+                }
+                Some('&') => {
+                    let (line, column) = (self.line, self.column);
+                    self.next();
+                    match self.peek() {
+                        Some('>') => {
+                            // `&>file` redirects both streams; it's a word
+                            self.word(line, column)?;
+                        }
+                        Some('&' | '|') => {
+                            self.next();
+                            command_position = true;
+                            after_else = false;
+                        }
+                        _ => {
+                            command_position = true;
+                            after_else = false;
+                        }
+                    }
+                }
+                Some(')') => {
+                    if substitution.is_none() {
+                        return Err(self.error(
+                            self.line,
+                            self.column,
+                            SyntaxErrorKind::UnexpectedCloseParen,
+                        ));
+                    }
+                    self.next();
+                    return close_all(&blocks);
+                }
+                Some('#') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.next();
+                    }
+                }
+                Some(_) => {
+                    let word = self.word(self.line, self.column)?;
+                    if !(command_position && word.plain) {
+                        command_position = false;
+                        after_else = false;
+                        continue;
+                    }
+                    command_position = self.keyword(&word, &mut blocks, after_else)?;
+                    after_else = word.text == "else";
+                }
+            }
+        }
+    }
+
+    /// Applies a word in command position to the block stack, returning
+    /// whether the next word is in command position too
+    fn keyword(
+        &self,
+        word: &Word,
+        blocks: &mut Vec<Open>,
+        after_else: bool,
+    ) -> Result<bool, SyntaxError> {
+        let open = |block| Open {
+            block,
+            line: word.line,
+            column: word.column,
+        };
+        let misplaced = |keyword| {
+            self.error(
+                word.line,
+                word.column,
+                SyntaxErrorKind::MisplacedKeyword(keyword),
+            )
+        };
+
+        Ok(match word.text.as_str() {
+            "function" => {
+                blocks.push(open(Block::Function));
+                false
+            }
+            "if" => {
+                // `else if` continues the enclosing `if` block
+                if !after_else {
+                    blocks.push(open(Block::If));
+                }
+                true
+            }
+            "while" => {
+                blocks.push(open(Block::While));
+                true
+            }
+            "for" => {
+                blocks.push(open(Block::For));
+                false
+            }
+            "switch" => {
+                blocks.push(open(Block::Switch));
+                false
+            }
+            "begin" => {
+                blocks.push(open(Block::Begin));
+                true
+            }
+            "end" => {
+                if blocks.pop().is_none() {
+                    return Err(self.error(word.line, word.column, SyntaxErrorKind::UnexpectedEnd));
+                }
+                false
+            }
+            "else" => {
+                if blocks.last().map(|open| open.block) != Some(Block::If) {
+                    return Err(misplaced("else"));
+                }
+                true
+            }
+            "case" => {
+                if blocks.last().map(|open| open.block) != Some(Block::Switch) {
+                    return Err(misplaced("case"));
+                }
+                false
+            }
+            "break" | "continue" => {
+                let in_loop = blocks
+                    .iter()
+                    .rev()
+                    .take_while(|open| open.block != Block::Function)
+                    .any(|open| matches!(open.block, Block::For | Block::While));
+                if !in_loop {
+                    return Err(misplaced(if word.text == "break" {
+                        "break"
+                    } else {
+                        "continue"
+                    }));
+                }
+                false
+            }
+            "and" | "or" | "not" | "!" | "time" => true,
+            _ => false,
+        })
+    }
+
+    /// Reads one word, including any quoted parts and substitutions in it
+    fn word(&mut self, line: usize, column: usize) -> Result<Word, SyntaxError> {
+        let mut word = Word {
+            text: String::new(),
+            plain: true,
+            line,
+            column,
+        };
+        let mut previous = None;
+
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' | '\n' | ';' | '|' | ')' => break,
+                // `&` only belongs to a word in redirections like `2>&1`
+                '&' if !matches!(previous, Some('>' | '<')) => break,
+                '\'' | '"' => {
+                    word.plain = false;
+                    self.quoted(c)?;
+                }
+                '\\' => {
+                    word.plain = false;
+                    let (line, column) = (self.line, self.column);
+                    self.next();
+                    if self.next().is_none() {
+                        return Err(self.error(line, column, SyntaxErrorKind::UnterminatedEscape));
+                    }
+                }
+                '(' => {
+                    word.plain = false;
+                    let start = (self.line, self.column);
+                    self.next();
+                    self.script(Some(start))?;
+                }
+                _ => {
+                    word.text.push(c);
+                    self.next();
+                }
+            }
+            previous = Some(c);
+        }
+
+        Ok(word)
+    }
+
+    /// Reads a quoted string starting at the opening `quote`
+    fn quoted(&mut self, quote: char) -> Result<(), SyntaxError> {
+        let (line, column) = (self.line, self.column);
+        self.next();
+        loop {
+            let Some(c) = self.next_quoted() else {
+                return Err(self.error(line, column, SyntaxErrorKind::UnterminatedQuote(quote)));
+            };
+            match c {
+                c if c == quote => return Ok(()),
+                // Single quotes only treat `\'` and `\\` as escapes
+                '\\' if quote == '"' || matches!(self.peek(), Some('\'' | '\\')) => {
+                    self.next_quoted();
+                }
+                '$' if quote == '"' && self.peek() == Some('(') => {
+                    let start = (self.line, self.column);
+                    self.next();
+                    self.script(Some(start))?;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Reports the innermost block still open at the end of a script
+fn close_all(blocks: &[Open]) -> Result<(), SyntaxError> {
+    match blocks.last() {
+        Some(open) => Err(SyntaxError {
+            line: open.line,
+            column: open.column,
+            kind: SyntaxErrorKind::UnclosedBlock(open.block.keyword()),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(source: &str) -> SyntaxErrorKind {
+        check(source).unwrap_err().kind
+    }
+
+    #[test]
+    fn test_valid_code() {
+        let valid = [
+            "",
+            "echo 'hello world'",
+            "# just a comment ending in '",
+            "mkdir -p $argv[1]; and cd $argv[1]",
+            "if test -f $argv[1].bak\n    cp $argv[1].bak $argv[1]\nelse if true\n    echo no\nelse\n    echo none\nend",
+            "switch $argv[1]\ncase '*.tar.gz'\n    tar -xzf $argv[1]\ncase '*'\n    echo '?'\nend",
+            "for f in *.txt\n    if test -d $f\n        continue\n    end\n    echo $f\nend",
+            "while read -l line; echo $line; end < file",
+            "function greet --argument-names name\n    echo \"hi $name\"\nend",
+            "set -l files (begin; ls; end | sort)",
+            "echo \"now: $(date)\"",
+            "echo hello \\\n    world",
+            "echo 'it\\'s' \"a \\\"quote\\\"\"",
+            "command ls 2>&1 >/dev/null &",
+            "echo foo &>/dev/null; and echo ok",
+            "echo a#b 'end'",
+            "type -q zoxide; and zoxide init fish | source",
+        ];
+        for source in valid {
+            assert_eq!(check(source), Ok(()), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_unterminated_quotes() {
+        assert_eq!(
+            check("echo 'hello"),
+            Err(SyntaxError {
+                line: 1,
+                column: 6,
+                kind: SyntaxErrorKind::UnterminatedQuote('\''),
+            })
+        );
+        assert_eq!(
+            kind("echo \"hello"),
+            SyntaxErrorKind::UnterminatedQuote('"')
+        );
+        assert_eq!(
+            kind("echo 'it\\'s"),
+            SyntaxErrorKind::UnterminatedQuote('\'')
+        );
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(kind("echo \\"), SyntaxErrorKind::UnterminatedEscape);
+        assert_eq!(check("echo \\'not a quote"), Ok(()));
+    }
+
+    #[test]
+    fn test_substitutions() {
+        assert_eq!(kind("echo (date"), SyntaxErrorKind::UnclosedSubstitution);
+        assert_eq!(
+            kind("echo \"$(date\""),
+            SyntaxErrorKind::UnterminatedQuote('"')
+        );
+        assert_eq!(kind("echo date)"), SyntaxErrorKind::UnexpectedCloseParen);
+        assert_eq!(
+            kind("echo (begin; ls)"),
+            SyntaxErrorKind::UnclosedBlock("begin")
+        );
+        assert_eq!(check("echo \"(not a substitution\""), Ok(()));
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            check("echo start\nif true\n    echo yes\n"),
+            Err(SyntaxError {
+                line: 2,
+                column: 1,
+                kind: SyntaxErrorKind::UnclosedBlock("if"),
+            })
+        );
+        assert_eq!(
+            kind("function f\n    for x in 1 2\n    end\n"),
+            SyntaxErrorKind::UnclosedBlock("function")
+        );
+        assert_eq!(kind("echo hi\nend"), SyntaxErrorKind::UnexpectedEnd);
+        assert_eq!(kind("else"), SyntaxErrorKind::MisplacedKeyword("else"));
+        assert_eq!(
+            kind("if true\ncase x\nend"),
+            SyntaxErrorKind::MisplacedKeyword("case")
+        );
+        assert_eq!(kind("break"), SyntaxErrorKind::MisplacedKeyword("break"));
+        assert_eq!(
+            kind("for x in a\n    function f\n        continue\n    end\nend"),
+            SyntaxErrorKind::MisplacedKeyword("continue")
+        );
+    }
+
+    #[test]
+    fn test_keywords_only_in_command_position() {
+        assert_eq!(check("echo if end function"), Ok(()));
+        assert_eq!(check("echo 'end'; echo \"if\""), Ok(()));
+        assert_eq!(check("not begin; false; end"), Ok(()));
+    }
+
+    #[test]
+    fn test_quoted_lines() {
+        assert_eq!(
+            quoted_lines("echo 'a\nb\n\nc'\necho \"d\\\ne\"\necho f\\\n  g"),
+            [2, 3, 4, 6]
+        );
+        assert_eq!(quoted_lines("echo 'a\nb"), [2]);
+    }
+
+    #[test]
+    fn test_error_display() {
+        let error = check("if true").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`if` block is missing `end` at line 1, column 1"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! The machine a configuration is generated for
+//!
+//! With [`Options::target`](crate::Options::target) set, `:when` conditions
+//! are decided while reading instead of being written as `if` blocks.
+
+use crate::config::{Condition, EnvTest, Os, whole_match};
+use std::collections::BTreeMap;
+
+/// What `:when` conditions are tested against
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Target {
+    /// `None` for a system `:os` can't name, which no `:os` test matches
+    pub os: Option<Os>,
+    pub hostname: String,
+    pub env: BTreeMap<String, String>,
+}
+
+impl Target {
+    /// Describes the machine this process runs on
+    pub fn current() -> Self {
+        Target {
+            os: Os::from_keyword(std::env::consts::OS),
+            hostname: current_hostname(),
+            env: std::env::vars_os()
+                .filter_map(|(name, value)| {
+                    Some((name.into_string().ok()?, value.into_string().ok()?))
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether every part of `condition` holds here
+    pub fn satisfies(&self, condition: &Condition) -> bool {
+        let os = condition.os.is_empty() || self.os.is_some_and(|os| condition.os.contains(&os));
+        let hostname = condition.hostname.is_empty() || condition.hostname.contains(&self.hostname);
+        let hostname_regex = condition.hostname_regex.as_ref().is_none_or(|regex| {
+            regex::Regex::new(&whole_match(regex)).is_ok_and(|regex| regex.is_match(&self.hostname))
+        });
+        let env = condition.env.iter().all(|var| {
+            let value = self.env.get(&var.name);
+            match &var.test {
+                EnvTest::Set => value.is_some(),
+                EnvTest::Unset => value.is_none(),
+                EnvTest::Equals(expected) => value.is_some_and(|value| value == expected),
+            }
+        });
+        os && hostname && hostname_regex && env
+    }
+}
+
+/// The name fish's `$hostname` holds, or an empty string if it can't be found
+fn current_hostname() -> String {
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname").or_else(|_| {
+        let output = std::process::Command::new("uname").arg("-n").output()?;
+        Ok::<_, std::io::Error>(String::from_utf8_lossy(&output.stdout).into_owned())
+    });
+    hostname.unwrap_or_default().trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EnvCondition;
+
+    #[test]
+    fn test_satisfies() {
+        let target = Target {
+            os: Some(Os::Linux),
+            hostname: "build-3".to_string(),
+            env: BTreeMap::from([("CI".to_string(), "true".to_string())]),
+        };
+        let condition = |os: Vec<Os>, regex: &str, test: EnvTest| Condition {
+            os,
+            hostname_regex: Some(regex.to_string()),
+            env: vec![EnvCondition {
+                name: "CI".to_string(),
+                test,
+            }],
+            ..Condition::default()
+        };
+
+        assert!(target.satisfies(&condition(vec![Os::Linux], "build-.*", EnvTest::Set)));
+        assert!(target.satisfies(&condition(
+            vec![Os::MacOs, Os::Linux],
+            "build-\\d",
+            EnvTest::Equals("true".to_string())
+        )));
+        assert!(!target.satisfies(&condition(vec![Os::MacOs], "build-.*", EnvTest::Set)));
+        assert!(!target.satisfies(&condition(vec![], "build", EnvTest::Set)));
+        assert!(!target.satisfies(&condition(vec![], ".*", EnvTest::Unset)));
+    }
+}
@@ -4,7 +4,7 @@ use std::process::{Command, Stdio};
 #[test]
 fn test_plugin_config_command() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "--config"])
+        .args(["run", "--", "--config"])
         .output()
         .expect("Failed to execute command");
 
@@ -27,14 +27,14 @@ fn test_plugin_config_command() {
 #[test]
 fn test_gen_config_command_simple() {
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(b"{:aliases {:ll \"ls -la\"}}")
         .expect("Failed to write to stdin");
@@ -53,14 +53,14 @@ fn test_gen_config_command_aliases() {
     let config = "{:aliases {:ll \"ls -la\" :la \"ls -A\"}}";
 
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(config.as_bytes())
         .expect("Failed to write to stdin");
@@ -83,14 +83,14 @@ fn test_gen_config_command_env_vars() {
     let config = "{:env {:EDITOR \"nvim\" :BROWSER \"firefox\"}}";
 
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(config.as_bytes())
         .expect("Failed to write to stdin");
@@ -112,14 +112,14 @@ fn test_gen_config_command_paths() {
     let config = "{:paths [\"/usr/local/bin\" \"~/.local/bin\"]}";
 
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(config.as_bytes())
         .expect("Failed to write to stdin");
@@ -141,14 +141,14 @@ fn test_gen_config_command_functions() {
     let config = "{:functions {:mkcd \"mkdir -p $argv[1]; and cd $argv[1]\"}}";
 
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(config.as_bytes())
         .expect("Failed to write to stdin");
@@ -171,14 +171,14 @@ fn test_gen_config_command_fish_greeting() {
     let config = "{:fish-greeting \"Welcome to Fish!\"}";
 
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(config.as_bytes())
         .expect("Failed to write to stdin");
@@ -197,14 +197,14 @@ fn test_gen_config_command_abbreviations() {
     let config = "{:abbrs {:gs \"git status\" :gc \"git commit\"}}";
 
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(config.as_bytes())
         .expect("Failed to write to stdin");
@@ -224,14 +224,14 @@ fn test_gen_config_command_abbreviations() {
 #[test]
 fn test_invalid_edn_input() {
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(b"{invalid edn")
         .expect("Failed to write to stdin");
@@ -239,23 +239,24 @@ fn test_invalid_edn_input() {
     drop(stdin);
 
     let output = child.wait_with_output().expect("Failed to read stdout");
-    assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
 
     let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
-    assert!(stderr.contains("Failed to parse EDN"));
+    assert!(stderr.contains("error: <stdin>:1:13: invalid EDN, unexpected end of input"));
 }
 
 #[test]
 fn test_empty_input() {
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin.write_all(b"{}").expect("Failed to write to stdin");
     stdin.flush().expect("Failed to flush stdin");
     drop(stdin);
@@ -271,31 +272,32 @@ fn test_empty_input() {
 #[test]
 fn test_usage_message() {
     let output = Command::new("cargo")
-        .args(&["run", "--"])
+        .args(["run", "--"])
         .output()
         .expect("Failed to execute command");
 
-    assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(2));
 
     let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
     assert!(stderr.contains("Usage:"));
     assert!(stderr.contains("--config"));
     assert!(stderr.contains("--gen-config"));
+    assert!(stderr.contains("gen      Generate config.fish"));
 }
 
 #[test]
 fn test_prompt_configuration() {
-    let config = "{:prompt {:style \"robbyrussell\" :show-git true}}";
+    let config = "{:prompt {:left [:cwd :git-branch {:text \"> \"}] :show-git true}}";
 
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(config.as_bytes())
         .expect("Failed to write to stdin");
@@ -308,23 +310,24 @@ fn test_prompt_configuration() {
     let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
 
     assert!(stdout.contains("# Prompt Configuration"));
-    assert!(stdout.contains("set -g theme robbyrussell"));
-    assert!(stdout.contains("set -g fish_prompt_show_git true"));
+    assert!(stdout.contains("function fish_prompt"));
+    assert!(stdout.contains("set -l segment (prompt_pwd)"));
+    assert!(stdout.contains("set -l segment (fish_git_prompt '%s')"));
 }
 
 #[test]
 fn test_boolean_prompt_values() {
-    let config_false = "{:prompt {:show-git false}}";
+    let config_false = "{:prompt {:left [:cwd :git-branch] :show-git false}}";
 
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(config_false.as_bytes())
         .expect("Failed to write to stdin");
@@ -335,7 +338,8 @@ fn test_boolean_prompt_values() {
     assert!(output.status.success());
 
     let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
-    assert!(stdout.contains("set -g fish_prompt_show_git false"));
+    assert!(stdout.contains("set -l segment (prompt_pwd)"));
+    assert!(!stdout.contains("fish_git_prompt"));
 }
 
 #[test]
@@ -347,18 +351,18 @@ fn test_comprehensive_config() {
         :paths ["/usr/local/bin"]
         :functions {:mkcd "mkdir -p $argv[1]; and cd $argv[1]"}
         :fish ["set -g fish_prompt_pwd_dir_length 3"]
-        :prompt {:style "robbyrussell" :show-git true}
+        :prompt {:left [:cwd :git-branch] :show-git true}
     }"#;
 
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--gen-config"])
+        .args(["run", "--", "--gen-config"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to spawn command");
 
-    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
     stdin
         .write_all(config.as_bytes())
         .expect("Failed to write to stdin");
@@ -383,5 +387,617 @@ fn test_comprehensive_config() {
     assert!(stdout.contains("# Custom Fish Commands"));
     assert!(stdout.contains("set -g fish_prompt_pwd_dir_length 3"));
     assert!(stdout.contains("# Prompt Configuration"));
-    assert!(stdout.contains("set -g theme robbyrussell"));
+    assert!(stdout.contains("function fish_prompt"));
+}
+
+#[test]
+fn test_gen_config_reports_invalid_values() {
+    let config = "{:aliases {:ll \"ls -la\" :bad 42}}";
+
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "--gen-config"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    stdin
+        .write_all(config.as_bytes())
+        .expect("Failed to write to stdin");
+    stdin.flush().expect("Failed to flush stdin");
+    drop(stdin);
+
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert!(stdout.contains("alias ll 'ls -la'"));
+    assert!(!stdout.contains("42"));
+
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains(
+        "<stdin>:1:30: warning: aliases.bad: expected string, found integer\n  |\n1 | {:aliases {:ll \"ls -la\" :bad 42}}\n  |                              ^^\n"
+    ));
+}
+
+#[test]
+fn test_gen_config_strict_rejects_unknown_keys() {
+    let config = "{:alias {:ll \"ls -la\"} :paths [\"/usr/local/bin\"]}";
+
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "--gen-config", "--strict"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    stdin
+        .write_all(config.as_bytes())
+        .expect("Failed to write to stdin");
+    stdin.flush().expect("Failed to flush stdin");
+    drop(stdin);
+
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains("error: alias: unknown key, did you mean `aliases`?"));
+}
+
+#[test]
+fn test_check_reports_problems() {
+    let config = "{:aliases {:ll \"ls -la\" \"my alias\" \"ls\"} :functions {:ll \"ls -l\"}}";
+
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "--check"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    stdin
+        .write_all(config.as_bytes())
+        .expect("Failed to write to stdin");
+    stdin.flush().expect("Failed to flush stdin");
+    drop(stdin);
+
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains("error: aliases.my alias: invalid name"));
+    assert!(stderr.contains("error: aliases.ll: conflicts with `functions.ll`"));
+    assert!(stderr.contains("<stdin>: 2 problems found"));
+}
+
+#[test]
+fn test_check_accepts_valid_file() {
+    let output = Command::new("cargo")
+        .args(["run", "--", "--check", "test_config.edn"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert_eq!(stdout, "test_config.edn: ok\n");
+}
+
+#[test]
+fn test_check_missing_file() {
+    let output = Command::new("cargo")
+        .args(["run", "--", "--check", "does-not-exist.edn"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert_eq!(output.status.code(), Some(4));
+
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains("error: does-not-exist.edn: "));
+    assert!(!stderr.contains("panicked"));
+}
+
+/// A path in the temporary directory that is unique to the calling test
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("tilde-fish-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn test_version() {
+    let output = Command::new("cargo")
+        .args(["run", "--", "--version"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert_eq!(
+        stdout,
+        format!("tilde-fish {}\n", env!("CARGO_PKG_VERSION"))
+    );
+}
+
+#[test]
+fn test_subcommand_help() {
+    for subcommand in ["config", "gen", "check", "diff", "import", "explain"] {
+        let output = Command::new("cargo")
+            .args(["run", "--", subcommand, "--help"])
+            .output()
+            .expect("Failed to execute command");
+
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+        assert!(stdout.contains(&format!("Usage: tilde-fish {subcommand}")));
+    }
+}
+
+#[test]
+fn test_unknown_subcommand() {
+    let output = Command::new("cargo")
+        .args(["run", "--", "generate"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert_eq!(output.status.code(), Some(2));
+
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains("unrecognized subcommand 'generate'"));
+}
+
+#[test]
+fn test_gen_from_file_to_output() {
+    let output_path = temp_path("gen-config.fish");
+    let output = Command::new("cargo")
+        .args(["run", "--", "gen", "test_config.edn", "--output"])
+        .arg(&output_path)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let written = std::fs::read_to_string(&output_path).expect("Output file should exist");
+    std::fs::remove_file(&output_path).ok();
+    assert!(written.contains("alias ll 'ls -la --color=auto'"));
+}
+
+#[test]
+fn test_gen_functions_command() {
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "--gen-functions"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    stdin
+        .write_all(b"{:functions {:gst \"git status\"} :autoload [:functions]}")
+        .expect("Failed to write to stdin");
+    stdin.flush().expect("Failed to flush stdin");
+    drop(stdin);
+
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert_eq!(
+        stdout,
+        "{\"fish/functions/gst.fish\" \"function gst\\n    git status\\nend\\n\"}\n"
+    );
+}
+
+#[test]
+fn test_gen_completions_command() {
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "--gen-completions"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    stdin
+        .write_all(
+            b"{:functions {:gst \"git status\"}
+               :completions {:tool [{:long \"help\"}]}
+               :autoload [:functions :completions]}",
+        )
+        .expect("Failed to write to stdin");
+    stdin.flush().expect("Failed to flush stdin");
+    drop(stdin);
+
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert_eq!(
+        stdout,
+        "{\"fish/completions/tool.fish\" \"complete -c tool -l help\\n\"}\n"
+    );
+}
+
+#[test]
+fn test_gen_resolves_conditions_for_another_machine() {
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "gen", "--os", "macos", "--hostname", "laptop"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    stdin
+        .write_all(
+            b"{:env {:BROWSER {:value \"open\" :when {:os :macos}}
+                     :OPENER {:value \"xdg-open\" :when {:os :linux}}}
+               :conditional [{:when {:hostname \"laptop\"} :fish-greeting nil}]}",
+        )
+        .expect("Failed to write to stdin");
+    stdin.flush().expect("Failed to flush stdin");
+    drop(stdin);
+
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert!(stdout.contains("set fish_greeting\n"));
+    assert!(stdout.contains("set -gx BROWSER 'open'\n"));
+    assert!(!stdout.contains("OPENER"));
+    assert!(!stdout.contains("uname"));
+    assert!(!stdout.contains("$hostname"));
+}
+
+#[test]
+fn test_gen_rejects_unsupported_conditions() {
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "gen"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    stdin
+        .write_all(b"{:path-vars {:MANPATH [{:path \"/x\" :when {:os :linux}}]}}")
+        .expect("Failed to write to stdin");
+    stdin.flush().expect("Failed to flush stdin");
+    drop(stdin);
+
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains(
+        "error: path-vars.MANPATH[0].when: `:when` isn't supported here; use a `:conditional` block"
+    ));
+}
+
+#[test]
+fn test_gen_to_output_dir() {
+    let config = temp_path("autoload.edn");
+    let dir = temp_path("autoload");
+    std::fs::write(
+        &config,
+        "{:aliases {:ll \"ls -la\"} :functions {:gst \"git status\"} :autoload [:functions]}",
+    )
+    .expect("Failed to write config");
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "gen"])
+        .arg(&config)
+        .arg("--output-dir")
+        .arg(&dir)
+        .output()
+        .expect("Failed to execute command");
+
+    let config_fish = std::fs::read_to_string(dir.join("fish/config.fish"));
+    let function = std::fs::read_to_string(dir.join("fish/functions/gst.fish"));
+    std::fs::remove_file(&config).ok();
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(output.status.success());
+
+    let config_fish = config_fish.expect("config.fish should exist");
+    assert!(config_fish.contains("alias ll 'ls -la'"));
+    assert!(!config_fish.contains("function gst"));
+    assert_eq!(
+        function.expect("Function file should exist"),
+        "function gst\n    git status\nend\n"
+    );
+}
+
+#[test]
+fn test_gen_merges_several_files() {
+    let base = temp_path("base.edn");
+    let local = temp_path("local.edn");
+    std::fs::write(
+        &base,
+        "{:aliases {:ll \"ls -l\" :la \"ls -A\"}\n :paths [\"/a\"]}",
+    )
+    .expect("Failed to write base");
+    std::fs::write(
+        &local,
+        "{:aliases {:la nil :g 42}\n :paths ^:replace [\"/b\"]}",
+    )
+    .expect("Failed to write local");
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "gen"])
+        .args([&base, &local])
+        .output()
+        .expect("Failed to execute command");
+    std::fs::remove_file(&base).ok();
+    std::fs::remove_file(&local).ok();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert!(stdout.contains("alias ll 'ls -l'\n"));
+    assert!(!stdout.contains("alias la"));
+    assert!(stdout.contains("fish_add_path /b\n"));
+    assert!(!stdout.contains("/a"));
+
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains(&format!(
+        "{}:1:23: warning: aliases.g: expected string, found integer",
+        local.display()
+    )));
+}
+
+#[test]
+fn test_gen_reads_included_files() {
+    let dir = temp_path("include");
+    std::fs::create_dir_all(dir.join("lang")).expect("Failed to create directory");
+    std::fs::write(
+        dir.join("config.edn"),
+        "{:include [\"git.edn\" \"lang/rust.edn\"]\n :aliases {:gs \"git status -s\"}}",
+    )
+    .expect("Failed to write config.edn");
+    std::fs::write(
+        dir.join("git.edn"),
+        "{:aliases {:gs \"git status\" :gd 42}}",
+    )
+    .expect("Failed to write git.edn");
+    std::fs::write(
+        dir.join("lang/rust.edn"),
+        "{:include [\"../git.edn\"] :paths [\"~/.cargo/bin\"]}",
+    )
+    .expect("Failed to write rust.edn");
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "gen"])
+        .arg(dir.join("config.edn"))
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert!(stdout.contains("alias gs 'git status -s'\n"));
+    assert!(stdout.contains("fish_add_path ~/.cargo/bin\n"));
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains(&format!(
+        "{}:1:33: warning: aliases.gd: expected string, found integer",
+        dir.join("git.edn").display()
+    )));
+
+    // git.edn including config.edn back is a cycle
+    std::fs::write(dir.join("git.edn"), "{:include [\"config.edn\"]}")
+        .expect("Failed to write git.edn");
+    let output = Command::new("cargo")
+        .args(["run", "--", "check"])
+        .arg(dir.join("config.edn"))
+        .output()
+        .expect("Failed to execute command");
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains(&format!(
+        "{}:1:12: error: include[0]: invalid value, includes a file that is already including this one",
+        dir.join("git.edn").display()
+    )));
+}
+
+#[test]
+fn test_gen_reads_theme_next_to_config() {
+    let dir = temp_path("theme");
+    std::fs::create_dir_all(dir.join("lang")).expect("Failed to create directory");
+    std::fs::write(
+        dir.join("config.edn"),
+        "{:include [\"lang/rust.edn\"] :colors {:theme \"my.theme\"}}",
+    )
+    .expect("Failed to write config.edn");
+    std::fs::write(dir.join("my.theme"), "fish_color_command 5f87af --bold\n")
+        .expect("Failed to write my.theme");
+    std::fs::write(
+        dir.join("lang/rust.edn"),
+        "{:conditional [{:when {:os :linux} :colors {:theme \"missing.theme\"}}]}",
+    )
+    .expect("Failed to write rust.edn");
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "gen"])
+        .arg(dir.join("config.edn"))
+        .output()
+        .expect("Failed to execute command");
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert!(stdout.contains("set -g fish_color_command 5f87af --bold\n"));
+    let stderr = String::from_utf8(output.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains(&format!(
+        "{}:1:52: warning: conditional[0].colors.theme: couldn't be read, {}: No such file or directory",
+        dir.join("lang/rust.edn").display(),
+        dir.join("lang/missing.theme").display()
+    )));
+}
+
+#[test]
+fn test_gen_selects_profile() {
+    let config = temp_path("profiles.edn");
+    std::fs::write(
+        &config,
+        "{:env {:EDITOR \"vim\"}\n :profiles {:work {:env {:EDITOR \"code --wait\"}}}}",
+    )
+    .expect("Failed to write config");
+
+    let run = |args: &[&str], profile: Option<&str>| {
+        let mut command = Command::new("cargo");
+        command.args(["run", "--", "gen"]).arg(&config).args(args);
+        match profile {
+            Some(profile) => command.env("TILDE_FISH_PROFILE", profile),
+            None => command.env_remove("TILDE_FISH_PROFILE"),
+        };
+        command.output().expect("Failed to execute command")
+    };
+    let editor = |output: &std::process::Output| {
+        let stdout = String::from_utf8(output.stdout.clone()).expect("Invalid UTF-8");
+        stdout
+            .lines()
+            .find(|line| line.starts_with("set -gx EDITOR"))
+            .map(str::to_string)
+    };
+
+    let base = run(&[], None);
+    let flag = run(&["--profile", "work"], None);
+    let env = run(&[], Some("work"));
+    let unknown = run(&["--strict"], Some("wrk"));
+    std::fs::remove_file(&config).ok();
+
+    assert_eq!(editor(&base).as_deref(), Some("set -gx EDITOR 'vim'"));
+    assert_eq!(
+        editor(&flag).as_deref(),
+        Some("set -gx EDITOR 'code --wait'")
+    );
+    assert_eq!(
+        editor(&env).as_deref(),
+        Some("set -gx EDITOR 'code --wait'")
+    );
+
+    assert_eq!(unknown.status.code(), Some(1));
+    let stderr = String::from_utf8(unknown.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains("error: profiles.wrk: unknown profile, did you mean `work`?"));
+}
+
+#[test]
+fn test_unknown_profile_fails_without_strict() {
+    let config = temp_path("unknown-profile.edn");
+    let target = temp_path("unknown-profile.fish");
+    std::fs::write(
+        &config,
+        "{:env {:EDITOR \"vim\"}\n :profiles {:work {:env {:EDITOR \"code --wait\"}}}}",
+    )
+    .expect("Failed to write config");
+
+    let gen_flag = Command::new("cargo")
+        .args(["run", "--", "gen", "--profile", "nope"])
+        .arg(&config)
+        .env_remove("TILDE_FISH_PROFILE")
+        .output()
+        .expect("Failed to execute command");
+    let diff_env = Command::new("cargo")
+        .args(["run", "--", "diff"])
+        .arg(&config)
+        .arg("--target")
+        .arg(&target)
+        .env("TILDE_FISH_PROFILE", "wrk")
+        .output()
+        .expect("Failed to execute command");
+    std::fs::remove_file(&config).ok();
+
+    assert_eq!(gen_flag.status.code(), Some(1));
+    assert!(gen_flag.stdout.is_empty());
+    let stderr = String::from_utf8(gen_flag.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains("error: profiles.nope: unknown profile"));
+
+    assert_eq!(diff_env.status.code(), Some(1));
+    assert!(diff_env.stdout.is_empty());
+    let stderr = String::from_utf8(diff_env.stderr).expect("Invalid UTF-8");
+    assert!(stderr.contains("error: profiles.wrk: unknown profile, did you mean `work`?"));
+}
+
+#[test]
+fn test_diff_against_existing_file() {
+    let target = temp_path("diff-config.fish");
+    std::fs::write(&target, "\n# Aliases\nalias ll 'ls -l'\n\n").expect("Failed to write target");
+
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "diff", "--target"])
+        .arg(&target)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    stdin
+        .write_all(b"{:aliases {:ll \"ls -la\"}}")
+        .expect("Failed to write to stdin");
+    stdin.flush().expect("Failed to flush stdin");
+    drop(stdin);
+
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    std::fs::remove_file(&target).ok();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert!(stdout.contains("+++ generated\n"));
+    assert!(stdout.contains("-alias ll 'ls -l'\n+alias ll 'ls -la'\n"));
+}
+
+#[test]
+fn test_import_config_fish() {
+    let mut child = Command::new("cargo")
+        .args(["run", "--", "import"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    stdin
+        .write_all(b"alias ll 'ls -la'\nset -gx EDITOR nvim\n")
+        .expect("Failed to write to stdin");
+    stdin.flush().expect("Failed to flush stdin");
+    drop(stdin);
+
+    let output = child.wait_with_output().expect("Failed to read stdout");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert_eq!(
+        stdout,
+        "{:aliases {:ll \"ls -la\"}\n :env {:EDITOR \"nvim\"}}\n"
+    );
+}
+
+#[test]
+fn test_explain_section() {
+    let output = Command::new("cargo")
+        .args(["run", "--", "explain", ":aliases"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Invalid UTF-8");
+    assert!(stdout.starts_with(":aliases - "));
+    assert!(stdout.contains(":aliases {:ll \"ls -la\"}"));
 }
@@ -1,13 +1,52 @@
 use clojure_reader::edn::Edn;
 use proptest::prelude::*;
 use std::collections::BTreeMap;
-use tilde_fish::fish_config;
+use tilde_fish::quote::{escape, escape_path, quote};
+use tilde_fish::{fish_config, names};
+
+/// Reads back a single fish word the way fish would, returning `None` if any
+/// unquoted character would be interpreted by the shell. A leading unquoted
+/// `~` is kept as-is, standing for the home directory fish expands it to.
+fn fish_unquote(word: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = word.chars().peekable();
+    if chars.peek() == Some(&'~') {
+        value.push(chars.next()?);
+    }
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => loop {
+                match chars.next()? {
+                    '\'' => break,
+                    '\\' => match chars.next()? {
+                        escaped @ ('\\' | '\'') => value.push(escaped),
+                        other => {
+                            value.push('\\');
+                            value.push(other);
+                        }
+                    },
+                    other => value.push(other),
+                }
+            },
+            c if c.is_ascii_alphanumeric() || "_-./:,+@=".contains(c) => value.push(c),
+            _ => return None,
+        }
+    }
+    Some(value)
+}
+
+/// Names matching `pattern` that tilde-fish accepts for aliases and functions
+fn arb_function_name(pattern: &'static str) -> impl Strategy<Value = String> {
+    pattern.prop_filter("rejected function name", |name| {
+        names::function(name).is_ok()
+    })
+}
 
 // Simple property test strategies
 prop_compose! {
     fn arb_alias_map()(
         aliases in prop::collection::btree_map(
-            "[a-zA-Z][a-zA-Z0-9_-]*",  // Valid alias names
+            arb_function_name("[a-zA-Z][a-zA-Z0-9_-]*"),  // Valid alias names
             "[a-zA-Z0-9 ._/-]+",       // Valid command strings
             0..10
         )
@@ -296,7 +335,7 @@ proptest! {
     #[test]
     fn test_large_configurations(
         large_aliases in prop::collection::btree_map(
-            "[a-zA-Z][a-zA-Z0-9_]*",
+            arb_function_name("[a-zA-Z][a-zA-Z0-9_]*"),
             "[a-zA-Z0-9 ._/-]+",
             20..50
         )
@@ -320,3 +359,34 @@ proptest! {
         prop_assert!(alias_count >= 20);
     }
 }
+
+// Quoting round-trips through fish's word rules
+proptest! {
+    #[test]
+    fn test_quote_round_trips(value in "[^\\x00]*") {
+        let quoted = quote(&value);
+        prop_assert_eq!(fish_unquote(&quoted), Some(value));
+    }
+
+    #[test]
+    fn test_escape_round_trips(value in "[^\\x00]*") {
+        let escaped = escape(&value);
+        prop_assert_eq!(fish_unquote(&escaped), Some(value));
+    }
+
+    #[test]
+    fn test_escape_handles_quote_heavy_values(value in "['\\\\ a$]{0,12}") {
+        let escaped = escape(&value);
+        prop_assert_eq!(fish_unquote(&escaped), Some(value));
+    }
+
+    #[test]
+    fn test_escape_path_round_trips(path in "(~|~/)?[^\\x00]*") {
+        let escaped = escape_path(&path);
+        let unquoted = fish_unquote(&escaped);
+        prop_assert_eq!(unquoted.as_deref(), Some(path.as_str()));
+        if path.starts_with("~/") || path == "~" {
+            prop_assert!(escaped.starts_with('~'), "home should stay expandable: {}", escaped);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Snapshot tests of the generated fish code for each `:prompt` style, and
+//! for values whose exact text indentation could change
+//!
+//! The expected output lives in `tests/snapshots/<name>.fish`. After an
+//! intended change, rerun with `UPDATE_SNAPSHOTS=1` to rewrite the files and
+//! review the diff.
+
+use clojure_reader::edn;
+use std::fs;
+use std::path::Path;
+use tilde_fish::{fish_config, syntax};
+
+/// Compares the output for `config` with `tests/snapshots/<name>.fish`
+fn assert_snapshot(name: &str, config: &str) {
+    let output = fish_config(edn::read_string(config).expect("Invalid EDN"));
+    assert_eq!(syntax::check(&output), Ok(()), "{name} doesn't parse");
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.fish"));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, &output).expect("Failed to write snapshot");
+        return;
+    }
+    let expected =
+        fs::read_to_string(&path).unwrap_or_else(|_| panic!("Missing snapshot {}", path.display()));
+    assert_eq!(
+        output, expected,
+        "{name} differs from its snapshot; rerun with UPDATE_SNAPSHOTS=1 to update it"
+    );
+}
+
+#[test]
+fn test_minimal_style() {
+    assert_snapshot("style-minimal", "{:prompt {:style :minimal}}");
+}
+
+#[test]
+fn test_robbyrussell_style() {
+    assert_snapshot("style-robbyrussell", "{:prompt {:style :robbyrussell}}");
+}
+
+#[test]
+fn test_two_line_style() {
+    assert_snapshot("style-two-line", "{:prompt {:style :two-line}}");
+}
+
+#[test]
+fn test_powerline_style() {
+    assert_snapshot("style-powerline", "{:prompt {:style :powerline}}");
+}
+
+#[test]
+fn test_style_overrides() {
+    assert_snapshot(
+        "style-overrides",
+        r#"{:prompt {:style "two-line" :right [:jobs] :show-git false}}"#,
+    );
+}
+
+#[test]
+fn test_conditional_multiline_values() {
+    assert_snapshot(
+        "conditional-multiline",
+        r#"{:conditional [{:when {:os :linux}
+                          :fish-greeting "Hello\nLinux"
+                          :abbrs {:m "echo one\n\n  two"}
+                          :env {:MOTD "line one\n  line two"}
+                          :functions {:note "printf '%s\n' 'a\n  b'"}
+                          :fish ["set -g notes 'first\n  second'"]}]}"#,
+    );
+}
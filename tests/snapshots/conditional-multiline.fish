@@ -0,0 +1,30 @@
+
+if test (uname) = Linux
+    if status is-interactive
+        set fish_greeting 'Hello
+Linux'
+    end
+
+    if status is-interactive
+        # Abbreviations
+        abbr -a -- m 'echo one
+
+  two'
+    end
+
+    # Environment Variables
+    set -gx MOTD 'line one
+  line two'
+
+    # Functions
+    function note
+        printf '%s
+' 'a
+  b'
+    end
+
+    # Custom Fish Commands
+    set -g notes 'first
+  second'
+end
+
@@ -0,0 +1,24 @@
+
+if status is-interactive
+    # Prompt Configuration
+    function fish_prompt
+        set -l last_status $status
+        set -l separator ''
+        set -l segment (prompt_pwd)
+        if test -n "$segment"
+            echo -n -s $separator (set_color blue) $segment (set_color normal)
+            set separator ' '
+        end
+        set -l segment (test $last_status -ne 0; and echo $last_status)
+        if test -n "$segment"
+            echo -n -s $separator (set_color red) $segment (set_color normal)
+            set separator ' '
+        end
+        set -l segment '❯ '
+        if test -n "$segment"
+            echo -n -s $separator (set_color magenta) $segment (set_color normal)
+            set separator ' '
+        end
+    end
+end
+
@@ -0,0 +1,39 @@
+
+if status is-interactive
+    # Prompt Configuration
+    function fish_prompt
+        set -l last_status $status
+        set -l separator ''
+        set -l segment (set -q VIRTUAL_ENV; and string replace -r '.*/' '' -- $VIRTUAL_ENV)
+        if test -n "$segment"
+            echo -n -s $separator (set_color white --background=brblack) ' ' $segment ' ' (set_color normal)
+            set separator ''
+        end
+        set -l segment (prompt_pwd)
+        if test -n "$segment"
+            echo -n -s $separator (set_color brblue --background=brblack) ' ' $segment ' ' (set_color normal)
+            set separator ''
+        end
+        set -l segment (fish_git_prompt '%s')
+        if test -n "$segment"
+            echo -n -s $separator (set_color brmagenta --background=brblack) ' ' $segment ' ' (set_color normal)
+            set separator ''
+        end
+        set -l segment (command git status --porcelain --ignore-submodules 2>/dev/null | string length -q; and echo '±')
+        if test -n "$segment"
+            echo -n -s $separator (set_color bryellow --background=brblack) ' ' $segment ' ' (set_color normal)
+            set separator ''
+        end
+        set -l segment (test $last_status -ne 0; and echo $last_status)
+        if test -n "$segment"
+            echo -n -s $separator (set_color brred --background=brblack) ' ' $segment ' ' (set_color normal)
+            set separator ''
+        end
+        set -l segment ''
+        if test -n "$segment"
+            echo -n -s $separator (set_color brblack) $segment ' ' (set_color normal)
+            set separator ''
+        end
+    end
+end
+
@@ -0,0 +1,28 @@
+
+if status is-interactive
+    # Prompt Configuration
+    function fish_prompt
+        set -l separator ''
+        set -l segment '➜'
+        if test -n "$segment"
+            echo -n -s $separator (set_color green --bold) $segment '  ' (set_color normal)
+            set separator ''
+        end
+        set -l segment (prompt_pwd)
+        if test -n "$segment"
+            echo -n -s $separator (set_color cyan --bold) $segment ' ' (set_color normal)
+            set separator ''
+        end
+        set -l segment (fish_git_prompt '%s')
+        if test -n "$segment"
+            echo -n -s $separator (set_color blue --bold) 'git:(' $segment ') ' (set_color normal)
+            set separator ''
+        end
+        set -l segment (command git status --porcelain --ignore-submodules 2>/dev/null | string length -q; and echo '✗')
+        if test -n "$segment"
+            echo -n -s $separator (set_color yellow) $segment ' ' (set_color normal)
+            set separator ''
+        end
+    end
+end
+
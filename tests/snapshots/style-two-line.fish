@@ -0,0 +1,52 @@
+
+if status is-interactive
+    # Prompt Configuration
+    function fish_prompt
+        set -l last_status $status
+        set -l separator ''
+        set -l segment (prompt_pwd)
+        if test -n "$segment"
+            echo -n -s $separator (set_color cyan --bold) $segment (set_color normal)
+            set separator ' '
+        end
+        set -l segment (fish_git_prompt '%s')
+        if test -n "$segment"
+            echo -n -s $separator (set_color magenta) $segment (set_color normal)
+            set separator ' '
+        end
+        set -l segment (command git status --porcelain --ignore-submodules 2>/dev/null | string length -q; and echo '*')
+        if test -n "$segment"
+            echo -n -s $separator (set_color yellow) $segment (set_color normal)
+            set separator ' '
+        end
+        set -l segment (set -q CMD_DURATION; and test $CMD_DURATION -ge 1000; and math -s1 $CMD_DURATION / 1000)s
+        if test -n "$segment"
+            echo -n -s $separator (set_color yellow) $segment (set_color normal)
+            set separator ' '
+        end
+        set -l segment \n
+        if test -n "$segment"
+            echo -n -s $separator $segment
+            set separator ''
+        end
+        set -l segment (test $last_status -ne 0; and echo $last_status)
+        if test -n "$segment"
+            echo -n -s $separator (set_color red) $segment (set_color normal)
+            set separator ' '
+        end
+        set -l segment '❯ '
+        if test -n "$segment"
+            echo -n -s $separator (set_color green) $segment (set_color normal)
+            set separator ' '
+        end
+    end
+    function fish_right_prompt
+        set -l separator ''
+        set -l segment (date +%H:%M)
+        if test -n "$segment"
+            echo -n -s $separator (set_color brblack) $segment (set_color normal)
+            set separator ' '
+        end
+    end
+end
+
@@ -74,7 +74,10 @@ pub fn comprehensive_config() -> Edn<'static> {
 
     // Prompt configuration
     let mut prompt_config = BTreeMap::new();
-    prompt_config.insert(Edn::Key("style"), Edn::Str("robbyrussell"));
+    prompt_config.insert(
+        Edn::Key("left"),
+        Edn::Vector(vec![Edn::Key("cwd"), Edn::Key("git-branch")]),
+    );
     prompt_config.insert(Edn::Key("show-git"), Edn::Bool(true));
 
     // Assemble the main configuration
@@ -253,15 +256,10 @@ pub fn assert_sections_ordered(output: &str, sections: &[&str]) {
 
 /// Validates that the output contains valid fish shell syntax
 pub fn validate_fish_syntax(output: &str) {
-    // Basic syntax validation
-
-    // Check that all 'function' declarations have matching 'end'
-    let function_count = output.matches("function ").count();
-    let end_count = output.matches("\nend\n").count() + output.matches("\nend").count();
-    assert!(
-        end_count >= function_count,
-        "All function declarations should have matching 'end' statements"
-    );
+    // Block structure, quoting and substitutions
+    if let Err(error) = tilde_fish::syntax::check(output) {
+        panic!("Output should be valid fish: {error}\n{output}");
+    }
 
     // Check that aliases are properly quoted
     for line in output.lines() {
@@ -300,7 +298,7 @@ pub fn validate_fish_syntax(output: &str) {
 }
 
 /// Parses an EDN string and returns the result, panicking with a helpful message on failure
-pub fn parse_edn_or_panic(edn_str: &str) -> Edn {
+pub fn parse_edn_or_panic(edn_str: &str) -> Edn<'_> {
     clojure_reader::edn::read_string(edn_str)
         .unwrap_or_else(|e| panic!("Failed to parse EDN: {}\nEDN content: {}", e, edn_str))
 }
@@ -335,7 +333,7 @@ pub fn env_vars_test_config(env_vars: Vec<(&'static str, &'static str)>) -> Edn<
 
 /// Creates a test config with specific paths
 pub fn paths_test_config(paths: Vec<&'static str>) -> Edn<'static> {
-    let paths_vec: Vec<Edn> = paths.into_iter().map(|p| Edn::Str(p)).collect();
+    let paths_vec: Vec<Edn> = paths.into_iter().map(Edn::Str).collect();
 
     let mut config_map = BTreeMap::new();
     config_map.insert(Edn::Key("paths"), Edn::Vector(paths_vec));
@@ -363,14 +361,12 @@ pub fn extract_aliases(output: &str) -> Vec<(String, String)> {
 
     for line in output.lines() {
         let trimmed = line.trim();
-        if trimmed.starts_with("alias ") {
-            if let Some(rest) = trimmed.strip_prefix("alias ") {
-                if let Some(space_pos) = rest.find(' ') {
-                    let name = rest[..space_pos].to_string();
-                    let command = rest[space_pos + 1..].trim_matches('\'').to_string();
-                    aliases.push((name, command));
-                }
-            }
+        if let Some(rest) = trimmed.strip_prefix("alias ")
+            && let Some(space_pos) = rest.find(' ')
+        {
+            let name = rest[..space_pos].to_string();
+            let command = rest[space_pos + 1..].trim_matches('\'').to_string();
+            aliases.push((name, command));
         }
     }
 
@@ -383,14 +379,12 @@ pub fn extract_env_vars(output: &str) -> Vec<(String, String)> {
 
     for line in output.lines() {
         let trimmed = line.trim();
-        if trimmed.starts_with("set -gx ") {
-            if let Some(rest) = trimmed.strip_prefix("set -gx ") {
-                let parts: Vec<&str> = rest.splitn(2, ' ').collect();
-                if parts.len() == 2 {
-                    let name = parts[0].to_string();
-                    let value = parts[1].trim_matches('\'').to_string();
-                    env_vars.push((name, value));
-                }
+        if let Some(rest) = trimmed.strip_prefix("set -gx ") {
+            let parts: Vec<&str> = rest.splitn(2, ' ').collect();
+            if parts.len() == 2 {
+                let name = parts[0].to_string();
+                let value = parts[1].trim_matches('\'').to_string();
+                env_vars.push((name, value));
             }
         }
     }
@@ -404,10 +398,8 @@ pub fn extract_paths(output: &str) -> Vec<String> {
 
     for line in output.lines() {
         let trimmed = line.trim();
-        if trimmed.starts_with("fish_add_path ") {
-            if let Some(path) = trimmed.strip_prefix("fish_add_path ") {
-                paths.push(path.to_string());
-            }
+        if let Some(path) = trimmed.strip_prefix("fish_add_path ") {
+            paths.push(path.to_string());
         }
     }
 
@@ -498,4 +490,10 @@ fish_add_path /usr/local/bin
 
         validate_fish_syntax(valid_output);
     }
+
+    #[test]
+    #[should_panic(expected = "Output should be valid fish")]
+    fn test_validate_fish_syntax_rejects_unclosed_block() {
+        validate_fish_syntax("function test_func\n    echo 'hello'\n");
+    }
 }